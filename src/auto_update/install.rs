@@ -0,0 +1,279 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, OnceLock};
+use anyhow::{anyhow, Result};
+use tokio::{fs, process::Command, time::{timeout, Duration}};
+
+use crate::auto_update::policy::{parse_ffmpeg_version, parse_yt_dlp_version};
+use crate::auto_update::version_manager::{detect_version, VersionManager};
+use crate::yt_dlp_interface::toolchain::lock_toolchain;
+
+/// Real downloads a fresh update gets before it is trusted; if all of them fail it is rolled back
+pub const PROBATION_DOWNLOADS: u32 = 3;
+const SMOKE_TEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// `<file>.previous`, the copy kept for rolling back
+pub fn previous_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".previous");
+    path.with_file_name(name)
+}
+
+async fn run_checked(program: &Path, args: &[&str]) -> Result<String> {
+    let output = timeout(SMOKE_TEST_TIMEOUT, Command::new(program).args(args).kill_on_drop(true).output())
+        .await
+        .map_err(|_| anyhow!("{:?} {} timed out", program, args.join(" ")))??;
+    if !output.status.success() {
+        return Err(anyhow!(
+            "{:?} {} exited with {}: {}",
+            program, args.join(" "), output.status, String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Runs a staged yt-dlp: `--version`, then a metadata probe of the local fixture when there is one.
+///
+/// Returns the version it reports.
+pub async fn smoke_test_yt_dlp(yt_dlp: &Path, fixture: Option<&Path>) -> Result<String> {
+    let version = parse_yt_dlp_version(&run_checked(yt_dlp, &["--version"]).await?)
+        .ok_or_else(|| anyhow!("{:?} --version printed nothing", yt_dlp))?;
+    if let Some(fixture) = fixture {
+        let url = format!("file://{}", fixture.display());
+        run_checked(yt_dlp, &["--ignore-config", "--enable-file-urls", "--skip-download", "--dump-json", &url]).await?;
+    }
+    Ok(version)
+}
+
+/// Runs staged ffmpeg and ffprobe: `-version`, then ffprobe on the local fixture when there is one.
+///
+/// Returns the version ffmpeg reports.
+pub async fn smoke_test_ffmpeg(ffmpeg: &Path, ffprobe: &Path, fixture: Option<&Path>) -> Result<String> {
+    let version = parse_ffmpeg_version(&run_checked(ffmpeg, &["-version"]).await?)
+        .ok_or_else(|| anyhow!("{:?} -version printed no version", ffmpeg))?;
+    run_checked(ffprobe, &["-version"]).await?;
+    if let Some(fixture) = fixture {
+        let fixture = fixture.to_string_lossy();
+        run_checked(ffprobe, &["-v", "error", "-show_format", "-of", "json", &fixture]).await?;
+    }
+    Ok(version)
+}
+
+/// A one-second test clip for the smoke tests, rendered once by the installed ffmpeg
+pub async fn smoke_fixture(libraries_dir: &Path, ffmpeg: &Path) -> Option<PathBuf> {
+    let fixture = libraries_dir.join(".smoke").join("fixture.mp4");
+    if fixture.exists() {
+        return Some(fixture);
+    }
+    fs::create_dir_all(fixture.parent()?).await.ok()?;
+    let output = fixture.to_string_lossy().to_string();
+    let args = ["-y", "-v", "error", "-f", "lavfi", "-i", "testsrc=duration=1:size=64x64:rate=10", "-pix_fmt", "yuv420p", &output];
+    match run_checked(ffmpeg, &args).await {
+        Ok(_) => Some(fixture),
+        Err(e) => {
+            log::warn!("Couldn't render the smoke test fixture, only checking versions: {}", e);
+            None
+        }
+    }
+}
+
+/// Moves a staged file over `target`, keeping the current one as `<target>.previous`.
+///
+/// The rename is atomic, so a running job sees either the old or the new file, never half of one.
+pub async fn install_staged(staged: &Path, target: &Path) -> Result<()> {
+    if target.exists() {
+        fs::copy(target, previous_path(target)).await?;
+    }
+    fs::rename(staged, target).await?;
+    Ok(())
+}
+
+/// Installs `(staged, target)` pairs in order, all or none: when one fails, the ones before it
+/// are restored from their `.previous` copies
+pub async fn install_staged_all(files: &[(&Path, &Path)]) -> Result<()> {
+    for (index, (staged, target)) in files.iter().enumerate() {
+        if let Err(e) = install_staged(staged, target).await {
+            for (_, installed) in &files[..index] {
+                if let Err(restore_error) = restore_previous(installed).await {
+                    log::error!("Failed to restore {:?} after a failed install: {}", installed, restore_error);
+                }
+            }
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+/// Puts `<target>.previous` back in place
+pub async fn restore_previous(target: &Path) -> Result<()> {
+    let previous = previous_path(target);
+    if !previous.exists() {
+        return Err(anyhow!("No previous version of {:?} to roll back to", target));
+    }
+    fs::rename(&previous, target).await?;
+    Ok(())
+}
+
+/// An update that hasn't proven itself on real downloads yet
+#[derive(Debug, Clone)]
+pub struct Probation {
+    pub binary_name: String,
+    /// Installed files, each with a `.previous` copy; the first one reports the version
+    pub files: Vec<PathBuf>,
+    pub release_id: String,
+    pub previous_release_id: String,
+    pub version_manager: VersionManager,
+    failures: u32,
+}
+
+impl Probation {
+    pub fn new(binary_name: &str, files: Vec<PathBuf>, release_id: &str, previous_release_id: String, version_manager: VersionManager) -> Self {
+        Self {
+            binary_name: binary_name.to_string(),
+            files,
+            release_id: release_id.to_string(),
+            previous_release_id,
+            version_manager,
+            failures: 0,
+        }
+    }
+
+    /// Restores the previous files and release, and remembers the release so it isn't installed again
+    pub async fn roll_back(&self) -> Result<()> {
+        log::error!("Rolling back {} release {}", self.binary_name, self.release_id);
+        for file in &self.files {
+            restore_previous(file).await?;
+        }
+        self.version_manager.save_release(&self.binary_name, &self.previous_release_id).await?;
+        match detect_version(&self.binary_name, &self.files[0]).await {
+            Ok(version) => self.version_manager.save_version(&self.binary_name, &version).await?,
+            Err(e) => log::warn!("{}", e),
+        }
+        self.version_manager.save_rejected(&self.binary_name, &self.release_id).await?;
+        // The recorded checksum belongs to the rejected download
+        self.version_manager.save_checksum(&self.binary_name, "").await?;
+        Ok(())
+    }
+}
+
+/// Updates on probation, shared by the updater and the download handlers
+#[derive(Default)]
+pub struct UpdateProbation {
+    pending: Mutex<Vec<Probation>>,
+}
+
+impl UpdateProbation {
+    pub fn start(&self, probation: Probation) {
+        let mut pending = self.pending.lock().unwrap();
+        pending.retain(|p| p.binary_name != probation.binary_name);
+        pending.push(probation);
+    }
+
+    /// Records the outcome of a real download and returns the updates to roll back.
+    ///
+    /// A success clears every probation, since all installed binaries took part in it.
+    pub fn record(&self, success: bool) -> Vec<Probation> {
+        let mut pending = self.pending.lock().unwrap();
+        if success {
+            for probation in pending.drain(..) {
+                log::info!("{} release {} passed probation", probation.binary_name, probation.release_id);
+            }
+            return Vec::new();
+        }
+        for probation in pending.iter_mut() {
+            probation.failures += 1;
+        }
+        let (failed, remaining) = pending.drain(..).partition(|p| p.failures >= PROBATION_DOWNLOADS);
+        *pending = remaining;
+        failed
+    }
+}
+
+pub fn update_probation() -> &'static UpdateProbation {
+    static PROBATION: OnceLock<UpdateProbation> = OnceLock::new();
+    PROBATION.get_or_init(UpdateProbation::default)
+}
+
+/// Reports a real download to the probation tracker, rolling back updates that keep failing
+pub async fn record_download_result(success: bool) {
+    let rollbacks = update_probation().record(success);
+    if rollbacks.is_empty() {
+        return;
+    }
+    // Restored in the background once the jobs running yt-dlp or ffmpeg right now are done
+    tokio::spawn(async move {
+        let _toolchain = lock_toolchain().await;
+        for probation in rollbacks {
+            if let Err(e) = probation.roll_back().await {
+                log::error!("Failed to roll back {}: {}", probation.binary_name, e);
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    async fn script(dir: &Path, name: &str, body: &str) -> PathBuf {
+        use std::os::unix::fs::PermissionsExt;
+        let path = dir.join(name);
+        fs::write(&path, format!("#!/bin/sh\n{}\n", body)).await.unwrap();
+        fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).await.unwrap();
+        path
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_smoke_test_rejects_broken_binary() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let good = script(dir.path(), "good", "echo 2025.01.01").await;
+        let broken = script(dir.path(), "broken", "echo 'Illegal instruction' >&2; exit 132").await;
+
+        assert_eq!(smoke_test_yt_dlp(&good, None).await.unwrap(), "2025.01.01");
+        let error = smoke_test_yt_dlp(&broken, None).await.unwrap_err();
+        assert!(error.to_string().contains("Illegal instruction"));
+    }
+
+    #[tokio::test]
+    async fn test_install_and_roll_back() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let target = dir.path().join("yt-dlp");
+        let staged = dir.path().join("yt-dlp.download");
+        fs::write(&target, "old").await.unwrap();
+        fs::write(&staged, "new").await.unwrap();
+
+        install_staged(&staged, &target).await.unwrap();
+        assert_eq!(fs::read_to_string(&target).await.unwrap(), "new");
+        assert!(!staged.exists());
+
+        // ffprobe goes back to the installed one when ffmpeg can't be installed next to it
+        let ffprobe = dir.path().join("ffprobe");
+        let staged_ffprobe = dir.path().join("ffprobe.staged");
+        fs::write(&ffprobe, "old").await.unwrap();
+        fs::write(&staged_ffprobe, "new").await.unwrap();
+        let missing = dir.path().join("ffmpeg.staged");
+        assert!(install_staged_all(&[(&staged_ffprobe, &ffprobe), (&missing, &dir.path().join("ffmpeg"))]).await.is_err());
+        assert_eq!(fs::read_to_string(&ffprobe).await.unwrap(), "old");
+
+        let version_manager = VersionManager::new(dir.path().join(".versions"));
+        let tracker = UpdateProbation::default();
+        tracker.start(Probation::new("yt-dlp", vec![target.clone()], "v2", "v1".to_string(), version_manager.clone()));
+        for _ in 1..PROBATION_DOWNLOADS {
+            assert!(tracker.record(false).is_empty());
+        }
+        let failed = tracker.record(false);
+        assert_eq!(failed.len(), 1);
+        failed[0].roll_back().await.unwrap();
+
+        assert_eq!(fs::read_to_string(&target).await.unwrap(), "old");
+        assert_eq!(version_manager.get_stored_release("yt-dlp").await.unwrap(), "v1");
+        assert_eq!(version_manager.get_rejected("yt-dlp").await.unwrap(), "v2");
+
+        // A success ends probation without rolling back
+        tracker.start(Probation::new("yt-dlp", vec![target.clone()], "v3", "v1".to_string(), version_manager));
+        assert!(tracker.record(false).is_empty());
+        assert!(tracker.record(true).is_empty());
+        assert!(tracker.pending.lock().unwrap().is_empty());
+    }
+}
@@ -1,3 +1,5 @@
+pub mod install;
+pub mod policy;
 pub mod updater;
 pub mod version_manager;
 
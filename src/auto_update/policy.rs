@@ -0,0 +1,119 @@
+use std::fmt;
+
+/// Which builds an updated binary follows
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    Stable,
+    /// yt-dlp-nightly-builds, ffmpeg master / git snapshots
+    Nightly,
+}
+
+/// How the auto-updater treats one binary
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UpdatePolicy {
+    /// Install every new release of the channel
+    Follow(Channel),
+    /// Install exactly this version and keep it
+    Pinned(String),
+    /// Keep whatever is installed
+    Disabled,
+}
+
+impl Default for UpdatePolicy {
+    fn default() -> Self {
+        UpdatePolicy::Follow(Channel::Stable)
+    }
+}
+
+impl UpdatePolicy {
+    /// Parses `stable`, `nightly`, `disabled` or `pin:<version>` (a bare version also pins)
+    pub fn parse(value: &str) -> Option<Self> {
+        let value = value.trim();
+        match value.to_lowercase().as_str() {
+            "" | "stable" => Some(UpdatePolicy::Follow(Channel::Stable)),
+            "nightly" => Some(UpdatePolicy::Follow(Channel::Nightly)),
+            "disabled" | "off" | "none" => Some(UpdatePolicy::Disabled),
+            _ => {
+                let version = value.strip_prefix("pin:").unwrap_or(value).trim();
+                if version.starts_with(|c: char| c.is_ascii_digit()) {
+                    Some(UpdatePolicy::Pinned(version.to_string()))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Channel the binary is downloaded from; yt-dlp nightly tags have a fourth `.HHMMSS` part
+    pub fn channel(&self) -> Channel {
+        match self {
+            UpdatePolicy::Follow(channel) => *channel,
+            UpdatePolicy::Pinned(version) if version.split('.').count() > 3 => Channel::Nightly,
+            _ => Channel::Stable,
+        }
+    }
+}
+
+impl fmt::Display for UpdatePolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            UpdatePolicy::Follow(Channel::Stable) => write!(f, "stable"),
+            UpdatePolicy::Follow(Channel::Nightly) => write!(f, "nightly"),
+            UpdatePolicy::Pinned(version) => write!(f, "pinned to {}", version),
+            UpdatePolicy::Disabled => write!(f, "disabled"),
+        }
+    }
+}
+
+/// Version printed by `yt-dlp --version`, e.g. `2024.12.23`
+pub fn parse_yt_dlp_version(output: &str) -> Option<String> {
+    output.lines().map(str::trim).find(|line| !line.is_empty()).map(str::to_string)
+}
+
+/// Version from the first line of `ffmpeg -version` (or `ffprobe -version`), e.g. `7.1-static`
+/// or `N-118000-g1234abcd-20250101`
+pub fn parse_ffmpeg_version(output: &str) -> Option<String> {
+    let line = output.lines().next()?.trim();
+    let rest = line.strip_prefix("ffmpeg version ").or_else(|| line.strip_prefix("ffprobe version "))?;
+    rest.split_whitespace().next().map(str::to_string)
+}
+
+/// Whether an installed version satisfies a pin, ignoring build suffixes like `-static`
+pub fn matches_pin(installed: &str, pinned: &str) -> bool {
+    installed == pinned
+        || installed.strip_prefix(pinned).is_some_and(|suffix| suffix.starts_with('-'))
+        || installed.strip_prefix('n').is_some_and(|rest| matches_pin(rest, pinned))
+}
+
+/// Tag of a GitHub release from its Atom entry ID, e.g. `tag:github.com,2008:Repository/1/2024.12.23`
+pub fn release_tag(entry_id: &str) -> &str {
+    entry_id.rsplit('/').next().unwrap_or(entry_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_policy() {
+        assert_eq!(UpdatePolicy::parse("Nightly"), Some(UpdatePolicy::Follow(Channel::Nightly)));
+        assert_eq!(UpdatePolicy::parse("off"), Some(UpdatePolicy::Disabled));
+        assert_eq!(UpdatePolicy::parse("pin:2024.12.23"), Some(UpdatePolicy::Pinned("2024.12.23".to_string())));
+        assert_eq!(UpdatePolicy::parse("7.1"), Some(UpdatePolicy::Pinned("7.1".to_string())));
+        assert_eq!(UpdatePolicy::parse("latest-ish"), None);
+        assert_eq!(UpdatePolicy::Pinned("2024.12.23.232653".to_string()).channel(), Channel::Nightly);
+    }
+
+    #[test]
+    fn test_parse_versions() {
+        assert_eq!(parse_yt_dlp_version("2024.12.23\n").as_deref(), Some("2024.12.23"));
+        let ffmpeg = "ffmpeg version 7.0.2-static https://johnvansickle.com/ffmpeg/  Copyright (c) 2000-2024\nbuilt with gcc 8";
+        assert_eq!(parse_ffmpeg_version(ffmpeg).as_deref(), Some("7.0.2-static"));
+        assert_eq!(parse_ffmpeg_version("Illegal instruction"), None);
+
+        assert!(matches_pin("7.0.2-static", "7.0.2"));
+        assert!(matches_pin("n7.1-20-gabc", "7.1"));
+        assert!(!matches_pin("7.0.21", "7.0.2"));
+        assert_eq!(release_tag("tag:github.com,2008:Repository/307260205/2024.12.23"), "2024.12.23");
+    }
+}
@@ -1,151 +1,170 @@
 use std::path::PathBuf;
 use std::collections::HashMap;
-use tokio::{fs, time::{interval, Duration}};
+use std::fmt;
+use std::sync::Mutex;
+use chrono::{DateTime, Local};
+use futures::future::BoxFuture;
+use tokio::{fs, time::{interval_at, Duration, Instant}};
 use anyhow::Result;
 use log::{info, warn, error};
 use feed_rs::parser;
-use crate::auto_update::version_manager::VersionManager;
-use crate::yt_dlp_interface::downloader::download_file;
+use crate::auto_update::version_manager::{detect_version, VersionManager};
+use crate::auto_update::policy::{matches_pin, release_tag, Channel, UpdatePolicy};
+use crate::config::{update_max_wait, update_policy};
+use crate::yt_dlp_interface::urls::{ffmpeg_pinned_url, ffmpeg_url, ffmpeg_version_source, parse_readme_version, yt_dlp_feed_url, yt_dlp_url, FfmpegVersionSource, Platform};
+use crate::auto_update::install::{install_staged, install_staged_all, smoke_fixture, smoke_test_ffmpeg, smoke_test_yt_dlp, update_probation, Probation};
+use crate::yt_dlp_interface::checksum::{checksum_source_for, download_verified};
+use crate::yt_dlp_interface::ensure::find_binary_in_extracted_dir;
+use crate::utils::proxy::http_client_builder;
+use crate::yt_dlp_interface::toolchain::{lock_toolchain_for_update, Toolchain};
 
 #[derive(Debug, Clone)]
 pub struct BinaryConfig {
-    pub rss_url: String,
     pub binary_path: PathBuf,
-    pub download_url_template: String, // GitHub URL template
+    pub policy: UpdatePolicy,
 }
 
+/// Sends a message to the admins, see `AutoUpdater::with_notifier`
+pub type UpdateNotifier = Box<dyn Fn(String) -> BoxFuture<'static, ()> + Send + Sync>;
+
 pub struct AutoUpdater {
+    libraries_dir: PathBuf,
+    toolchain: Toolchain,
     binaries: HashMap<String, BinaryConfig>,
     version_manager: VersionManager,
     check_interval: Duration,
+    update_lock: tokio::sync::Mutex<()>,
+    last_checks: Mutex<HashMap<String, LastCheck>>,
+    next_check: Mutex<Option<DateTime<Local>>>,
+    notifier: Option<UpdateNotifier>,
 }
 
 impl AutoUpdater {
-    pub fn new(libraries_dir: PathBuf, check_interval_minutes: u64) -> Self {
+    /// Manages the bundled tools of `toolchain`; externally provided ones are only reported
+    pub fn new(toolchain: &Toolchain, libraries_dir: PathBuf, check_interval_minutes: u64) -> Self {
         let mut binaries = HashMap::new();
 
         // Конфигурация для yt-dlp
-        binaries.insert("yt-dlp".to_string(), BinaryConfig {
-            rss_url: "https://github.com/yt-dlp/yt-dlp/releases.atom".to_string(),
-            binary_path: libraries_dir.join(if cfg!(target_os = "windows") { "yt-dlp.exe" } else { "yt-dlp" }),
-            download_url_template: if cfg!(target_os = "windows") {
-                "https://github.com/yt-dlp/yt-dlp/releases/latest/download/yt-dlp.exe".to_string()
-            } else if cfg!(target_os = "linux") {
-                "https://github.com/yt-dlp/yt-dlp/releases/latest/download/yt-dlp_linux".to_string()
-            } else {
-                "https://github.com/yt-dlp/yt-dlp/releases/latest/download/yt-dlp_macos".to_string()
-            },
-        });
+        if toolchain.yt_dlp_managed {
+            binaries.insert("yt-dlp".to_string(), BinaryConfig {
+                binary_path: toolchain.yt_dlp.clone(),
+                policy: update_policy("yt-dlp"),
+            });
+        }
 
         // Конфигурация для FFmpeg
-        let ffmpeg_dir = libraries_dir.join("ffmpeg");
-        binaries.insert("ffmpeg".to_string(), BinaryConfig {
-            rss_url: "https://github.com/BtbN/FFmpeg-Builds/releases.atom".to_string(),
-            binary_path: ffmpeg_dir.join(if cfg!(target_os = "windows") { "ffmpeg.exe" } else { "ffmpeg" }),
-            download_url_template: if cfg!(target_os = "windows") {
-                "https://github.com/BtbN/FFmpeg-Builds/releases/latest/download/ffmpeg-master-latest-win64-gpl.zip".to_string()
-            } else {
-                "https://johnvansickle.com/ffmpeg/builds/ffmpeg-git-amd64-static.tar.xz".to_string()
-            },
-        });
+        if toolchain.ffmpeg_managed {
+            binaries.insert("ffmpeg".to_string(), BinaryConfig {
+                binary_path: toolchain.ffmpeg.clone(),
+                policy: update_policy("ffmpeg"),
+            });
+        }
+
+        for (binary_name, config) in &binaries {
+            info!("Update policy for {}: {}", binary_name, config.policy);
+        }
 
         Self {
             binaries,
             version_manager: VersionManager::new(libraries_dir.join(".versions")),
+            libraries_dir,
+            toolchain: toolchain.clone(),
             check_interval: Duration::from_secs(check_interval_minutes * 60),
+            update_lock: tokio::sync::Mutex::new(()),
+            last_checks: Mutex::new(HashMap::new()),
+            next_check: Mutex::new(None),
+            notifier: None,
+        }
+    }
+
+    /// Reports installed updates and failed checks through `notifier`
+    pub fn with_notifier(mut self, notifier: UpdateNotifier) -> Self {
+        self.notifier = Some(notifier);
+        self
+    }
+
+    /// Latest release ID of the channel, from the feed or the page the platform's builds are announced on
+    async fn get_latest_release_id(&self, binary_name: &str, channel: Channel) -> Result<String> {
+        if binary_name == "yt-dlp" {
+            return self.get_latest_release_id_from_rss(&yt_dlp_feed_url(channel)).await;
+        }
+        let client = http_client_builder()?.build()?;
+        match ffmpeg_version_source(&Platform::current(), channel)? {
+            FfmpegVersionSource::Feed(url) => self.get_latest_release_id_from_rss(url).await,
+            FfmpegVersionSource::Readme(url) => {
+                let readme = client.get(url).send().await?.error_for_status()?.text().await?;
+                parse_readme_version(&readme).ok_or_else(|| anyhow::anyhow!("No version in {}", url))
+            }
+            FfmpegVersionSource::Info(url) => {
+                #[derive(serde::Deserialize)]
+                struct BuildInfo {
+                    version: String,
+                }
+                let info: BuildInfo = client.get(url).send().await?.error_for_status()?.json().await?;
+                Ok(info.version)
+            }
         }
     }
 
     // Get the latest release ID from the RSS feed
     async fn get_latest_release_id_from_rss(&self, rss_url: &str) -> Result<String> {
-        let response = reqwest::get(rss_url).await?;
+        let response = http_client_builder()?.build()?.get(rss_url).send().await?;
         let content = response.text().await?;
         let feed = parser::parse(content.as_bytes())?;
 
         if let Some(entry) = feed.entries.first() {
-            // The entry ID is a stable, unique identifier for the release (e.g., a URL)
-            Ok(entry.id.clone())
+            // yt-dlp tags are its versions; BtbN re-publishes a moving `latest` tag, so its build time tells releases apart
+            let tag = release_tag(&entry.id);
+            match entry.updated.or(entry.published) {
+                Some(updated) if tag == "latest" => Ok(format!("latest-{}", updated.format("%Y-%m-%dT%H:%M"))),
+                _ => Ok(tag.to_string()),
+            }
         } else {
             Err(anyhow::anyhow!("No entries found in RSS feed"))
         }
     }
 
-    async fn update_binary(&self, binary_name: &str, config: &BinaryConfig, new_release_id: &str) -> Result<()> {
+    /// Where the build of `release_id` comes from under the binary's policy
+    async fn download_url(binary_name: &str, policy: &UpdatePolicy, release_id: &str) -> Result<String> {
+        match (binary_name, policy) {
+            ("yt-dlp", _) => yt_dlp_url(&Platform::current(), policy.channel(), Some(release_id)),
+            (_, UpdatePolicy::Pinned(version)) => ffmpeg_pinned_url(&Platform::current(), version),
+            _ => ffmpeg_url(&Platform::current(), policy.channel()).await,
+        }
+    }
+
+    /// Installs `new_release_id` and returns the version it reports; `force` reinstalls the same version too.
+    ///
+    /// `None` when running jobs kept the binaries busy for too long and the install was postponed.
+    async fn update_binary(&self, binary_name: &str, config: &BinaryConfig, new_release_id: &str, force: bool) -> Result<Option<String>> {
         info!("Updating {} to new release: {}", binary_name, new_release_id);
+        let download_url = Self::download_url(binary_name, &config.policy, new_release_id).await?;
 
-        // Form the download URL based on the binary type
-        let download_url = if binary_name == "yt-dlp" {
-            // For yt-dlp, use the /latest/download/ path which works without version substitution
-            config.download_url_template.clone()
-        } else {
-            // For FFmpeg, parse the RSS feed to find the correct download URL
-            // First, get the latest release info from RSS
-            let response = reqwest::get(&config.rss_url).await?;
-            let content = response.text().await?;
-            let feed = parser::parse(content.as_bytes())?;
-            
-            if let Some(entry) = feed.entries.first() {
-                // For FFmpeg, we need to find the correct asset link from the release
-                // GitHub RSS feeds contain links to assets in the content or links sections
-                // Try to extract the correct download link for the platform
-                
-                if binary_name == "ffmpeg" {
-                    // Extract the correct asset URL for the platform
-                    // The link might be in the content or in the links array
-                    if cfg!(target_os = "windows") {
-                        // For Windows, we need to extract the correct asset URL
-                        // GitHub releases might have multiple assets, so we need to find the correct one
-                        // Check the entry's links for direct asset download links
-                        let mut found_asset_url = None;
-                        
-                        for link in &entry.links {
-                            if link.href.contains("github.com/BtbN/FFmpeg-Builds/releases/download/") && 
-                               link.href.contains("win64-gpl") && 
-                               link.href.ends_with(".zip") {
-                                // Found a Windows GPL zip asset
-                                found_asset_url = Some(link.href.clone());
-                                break;
-                            }
-                        }
-                        
-                        if let Some(asset_url) = found_asset_url {
-                            asset_url
-                        } else {
-                            // Fallback if no direct link found in RSS - try with common pattern
-                            // The latest naming seems to follow the pattern like ffmpeg-n7.1-latest-win64-gpl-7.1.zip
-                            // Since we can't know the exact version from RSS, try with latest
-                            "https://github.com/BtbN/FFmpeg-Builds/releases/latest/download/ffmpeg-n7.1-latest-win64-gpl-7.1.zip".to_string()
-                        }
-                    } else if cfg!(target_os = "linux") {
-                        // For Linux, use the johnvansickle.com static build
-                        config.download_url_template.clone()
-                    } else {
-                        // For macOS, use template
-                        config.download_url_template.replace("{}", &new_release_id)
-                    }
-                } else {
-                    // For other binaries, use template with version replacement
-                    config.download_url_template.replace("{}", &new_release_id)
-                }
-            } else {
-                // Fallback to template if no RSS entries
-                config.download_url_template.replace("{}", &new_release_id)
-            }
-        };
+        // Archives are verified before extraction, yt-dlp before it replaces the installed binary
+        let checksum_source = checksum_source_for(&download_url);
+        let mut checksum = None;
+        // New files are staged next to the live ones, smoke-tested and only then renamed into place
+        let ffprobe_path = config.binary_path.with_file_name(if cfg!(target_os = "windows") { "ffprobe.exe" } else { "ffprobe" });
+        let staging_dir = config.binary_path.parent().unwrap().join(".staging");
+        let installed_files;
+        let new_version;
+        let installed_version = self.version_manager.get_stored_version(binary_name).await.unwrap_or_default();
+        let mut _toolchain = None;
 
         if binary_name == "ffmpeg" {
+            fs::remove_dir_all(&staging_dir).await.ok();
+            fs::create_dir_all(&staging_dir).await?;
+
             // FFmpeg requires special handling depending on platform
             if cfg!(target_os = "windows") {
                 // For Windows, download the zip file and extract it
                 let temp_archive_path = config.binary_path.with_extension("zip");
-                download_file(&download_url, &temp_archive_path).await?;
+                checksum = download_verified(&download_url, &temp_archive_path, checksum_source.as_ref()).await?;
                 
                 // Extract ffmpeg.exe and ffprobe.exe from the zip file
-                let _ffmpeg_dir = config.binary_path.parent().unwrap();
-                
                 #[cfg(target_os = "windows")]
                 {
-                    let ffmpeg_dir_pathbuf = config.binary_path.parent().unwrap().to_path_buf();
+                    let ffmpeg_dir_pathbuf = staging_dir.clone();
                     crate::yt_dlp_interface::extract_ffmpeg_windows(&temp_archive_path, &ffmpeg_dir_pathbuf).await?;
                 }
 
@@ -154,11 +173,11 @@ impl AutoUpdater {
             } else if cfg!(target_os = "macos") {
                 // For macOS, download the 7z archive and extract it
                 let temp_archive_path = config.binary_path.with_extension("7z");
-                download_file(&download_url, &temp_archive_path).await?;
+                checksum = download_verified(&download_url, &temp_archive_path, checksum_source.as_ref()).await?;
                 
                 #[cfg(target_os = "macos")]
                 {
-                    let ffmpeg_dir_pathbuf = config.binary_path.parent().unwrap().to_path_buf();
+                    let ffmpeg_dir_pathbuf = staging_dir.clone();
                     crate::yt_dlp_interface::extract_ffmpeg_macos(&temp_archive_path, &ffmpeg_dir_pathbuf).await?;
                 }
 
@@ -168,67 +187,204 @@ impl AutoUpdater {
                 // For Linux, download the tar.xz archive and extract it
                 // Using the johnvansickle.com static builds which are already extracted
                 let temp_archive_path = config.binary_path.with_extension("tar.xz");
-                download_file(&download_url, &temp_archive_path).await?;
+                checksum = download_verified(&download_url, &temp_archive_path, checksum_source.as_ref()).await?;
                 
                 #[cfg(all(unix, not(target_os = "macos")))]
                 {
-                    let ffmpeg_dir_pathbuf = config.binary_path.parent().unwrap().to_path_buf();
+                    let ffmpeg_dir_pathbuf = staging_dir.clone();
                     crate::yt_dlp_interface::extract_ffmpeg_unix(&temp_archive_path, &ffmpeg_dir_pathbuf).await?;
                 }
 
                 // Clean up the temp archive file
                 fs::remove_file(temp_archive_path).await.ok();
             }
+
+            let staged_ffmpeg = find_binary_in_extracted_dir(&staging_dir, &file_name(&config.binary_path)).await
+                .ok_or_else(|| anyhow::anyhow!("ffmpeg not found in the downloaded archive"))?;
+            let staged_ffprobe = find_binary_in_extracted_dir(&staging_dir, &file_name(&ffprobe_path)).await
+                .ok_or_else(|| anyhow::anyhow!("ffprobe not found in the downloaded archive"))?;
+
+            let fixture = smoke_fixture(&self.libraries_dir, &config.binary_path).await;
+            let smoke_test = smoke_test_ffmpeg(&staged_ffmpeg, &staged_ffprobe, fixture.as_deref()).await;
+            let version = match smoke_test {
+                Ok(version) => version,
+                Err(e) => {
+                    fs::remove_dir_all(&staging_dir).await.ok();
+                    return Err(e.context(format!("Smoke test of {} release {} failed, keeping the installed one", binary_name, new_release_id)));
+                }
+            };
+            if force || version != installed_version {
+                _toolchain = match lock_toolchain_for_update(update_max_wait()).await {
+                    Some(guard) => Some(guard),
+                    None => {
+                        fs::remove_dir_all(&staging_dir).await.ok();
+                        return Ok(None);
+                    }
+                };
+                // Both or neither: a new ffprobe doesn't stay next to the old ffmpeg
+                let installed = install_staged_all(&[(&staged_ffprobe, &ffprobe_path), (&staged_ffmpeg, &config.binary_path)]).await;
+                if let Err(e) = installed {
+                    fs::remove_dir_all(&staging_dir).await.ok();
+                    return Err(e.context(format!("Failed to install {} release {}", binary_name, new_release_id)));
+                }
+            }
+            fs::remove_dir_all(&staging_dir).await.ok();
+            installed_files = vec![config.binary_path.clone(), ffprobe_path];
+            new_version = version;
         } else {
-            // For yt-dlp, just download the executable
-            download_file(&download_url, &config.binary_path).await?;
+            // For yt-dlp, download the executable next to the installed one
+            let download_path = config.binary_path.with_extension("download");
+            checksum = download_verified(&download_url, &download_path, checksum_source.as_ref()).await?;
 
             // Устанавливаем права выполнения (Unix)
             #[cfg(unix)]
             {
                 use std::os::unix::fs::PermissionsExt;
-                let mut perms = fs::metadata(&config.binary_path).await?.permissions();
+                let mut perms = fs::metadata(&download_path).await?.permissions();
                 perms.set_mode(0o755);
-                fs::set_permissions(&config.binary_path, perms).await?;
+                fs::set_permissions(&download_path, perms).await?;
             }
+
+            let fixture = smoke_fixture(&self.libraries_dir, &self.toolchain.ffmpeg).await;
+            let version = match smoke_test_yt_dlp(&download_path, fixture.as_deref()).await {
+                Ok(version) => version,
+                Err(e) => {
+                    fs::remove_file(&download_path).await.ok();
+                    return Err(e.context(format!("Smoke test of {} release {} failed, keeping the installed one", binary_name, new_release_id)));
+                }
+            };
+            if force || version != installed_version {
+                _toolchain = match lock_toolchain_for_update(update_max_wait()).await {
+                    Some(guard) => Some(guard),
+                    None => {
+                        fs::remove_file(&download_path).await.ok();
+                        return Ok(None);
+                    }
+                };
+                install_staged(&download_path, &config.binary_path).await?;
+            } else {
+                fs::remove_file(&download_path).await.ok();
+            }
+            installed_files = vec![config.binary_path.clone()];
+            new_version = version;
         }
 
         // Сохраняем новую версию
-        self.version_manager.save_version(binary_name, new_release_id).await?;
-        info!("Successfully updated {} to new release: {}", binary_name, new_release_id);
-        Ok(())
+        let previous_release_id = self.version_manager.get_stored_release(binary_name).await.unwrap_or_default();
+        self.version_manager.save_release(binary_name, new_release_id).await?;
+        if !force && new_version == installed_version {
+            // Rebuilds of the same version (BtbN publishes daily) are not worth a swap
+            info!("{} release {} is the installed version {}, keeping the current files", binary_name, new_release_id, new_version);
+            return Ok(Some(new_version));
+        }
+        update_probation().start(Probation::new(binary_name, installed_files, new_release_id, previous_release_id, self.version_manager.clone()));
+        self.version_manager.save_version(binary_name, &new_version).await?;
+        if let Some(checksum) = checksum {
+            self.version_manager.save_checksum(binary_name, &checksum).await?;
+        }
+        info!("Successfully updated {} to {} (release {})", binary_name, new_version, new_release_id);
+        Ok(Some(new_version))
     }
 
-    async fn check_single_binary(&self, binary_name: &str, config: &BinaryConfig) -> Result<()> {
-        // Get the currently stored release ID
-        let current_id = self.version_manager.get_stored_version(binary_name).await.unwrap_or_default();
+    /// Checks one binary against its policy and installs a new release if there is one.
+    ///
+    /// `force` ignores the policy's "up to date" and rolled-back verdicts and reinstalls.
+    async fn check_single_binary(&self, binary_name: &str, config: &BinaryConfig, force: bool) -> Result<CheckOutcome> {
+        // Record what is actually installed, it may have been replaced by hand
+        let installed_version = match detect_version(binary_name, &config.binary_path).await {
+            Ok(version) => {
+                self.version_manager.save_version(binary_name, &version).await?;
+                version
+            }
+            Err(e) => {
+                warn!("{}", e);
+                String::new()
+            }
+        };
 
-        // Get the latest release ID from the RSS feed
-        match self.get_latest_release_id_from_rss(&config.rss_url).await {
-            Ok(latest_id) => {
-                if latest_id != current_id && !latest_id.is_empty() {
-                    info!("New release found for {}: ID {}",
-                        binary_name, latest_id);
+        let latest_id = match &config.policy {
+            UpdatePolicy::Disabled if !force => {
+                info!("Updates of {} are disabled (installed: {})", binary_name, installed_version);
+                return Ok(CheckOutcome::Disabled { version: installed_version });
+            }
+            UpdatePolicy::Pinned(version) if !force && matches_pin(&installed_version, version) => {
+                info!("{} is at its pinned version {}", binary_name, installed_version);
+                return Ok(CheckOutcome::UpToDate { version: installed_version });
+            }
+            UpdatePolicy::Pinned(version) => version.clone(),
+            policy => self.get_latest_release_id(binary_name, policy.channel()).await
+                .map_err(|e| e.context(format!("Failed to check updates for {}", binary_name)))?,
+        };
 
-                    // Update the binary
-                    if let Err(e) = self.update_binary(binary_name, config, &latest_id).await {
-                        error!("Failed to update {}: {}", binary_name, e);
-                    }
-                } else {
-                    info!("{} is up to date (Release ID: {})", binary_name, current_id);
-                }
+        // yt-dlp release tags are versions, ffmpeg builds are told apart by release
+        let current_id = if binary_name == "yt-dlp" {
+            installed_version.clone()
+        } else {
+            self.version_manager.get_stored_release(binary_name).await.unwrap_or_default()
+        };
+        let rejected_id = self.version_manager.get_rejected(binary_name).await.unwrap_or_default();
+        if !force && latest_id == rejected_id {
+            info!("{} release {} was rolled back before, skipping it", binary_name, latest_id);
+            Ok(CheckOutcome::SkippedRejected { version: installed_version, release: latest_id })
+        } else if force || (latest_id != current_id && !latest_id.is_empty()) {
+            info!("New release found for {}: {} (installed: {})", binary_name, latest_id, installed_version);
+
+            // Update the binary
+            let new_version = self.update_binary(binary_name, config, &latest_id, force).await
+                .map_err(|e| e.context(format!("Failed to update {} to {}", binary_name, latest_id)))?;
+            let Some(new_version) = new_version else {
+                warn!("Downloads kept {} busy, postponing release {} to the next check", binary_name, latest_id);
+                return Ok(CheckOutcome::Postponed { version: installed_version, release: latest_id });
+            };
+            if new_version == installed_version && !force {
+                Ok(CheckOutcome::UpToDate { version: installed_version })
+            } else {
+                Ok(CheckOutcome::Updated { from: installed_version, to: new_version })
             }
+        } else {
+            info!("{} is up to date ({}, release {})", binary_name, installed_version, current_id);
+            Ok(CheckOutcome::UpToDate { version: installed_version })
+        }
+    }
+
+    /// Checks one binary and records the result for `/versions`, notifying admins about installs and failures.
+    ///
+    /// Also runs on demand for `/update`.
+    pub async fn run_check(&self, binary_name: &str, force: bool) -> Result<CheckOutcome> {
+        let config = self.binaries.get(binary_name)
+            .ok_or_else(|| anyhow::anyhow!("{} is provided externally and not updated by the bot", binary_name))?;
+        // One check at a time: a manual /update must not race the periodic one over the same files
+        let _guard = self.update_lock.lock().await;
+        let result = self.check_single_binary(binary_name, config, force).await;
+
+        let summary = match &result {
+            Ok(outcome) => outcome.to_string(),
+            Err(e) => format!("failed: {:#}", e),
+        };
+        self.last_checks.lock().unwrap().insert(binary_name.to_string(), LastCheck { at: Local::now(), summary: summary.clone(), ok: result.is_ok() });
+
+        match &result {
+            Ok(CheckOutcome::Updated { .. }) => self.notify(&format!("⬆️ {}: {}", binary_name, summary)).await,
             Err(e) => {
-                warn!("Failed to check updates for {}: {}", binary_name, e);
+                error!("{:#}", e);
+                self.notify(&format!("⚠️ {}: {}", binary_name, summary)).await;
             }
+            Ok(_) => {}
+        }
+        result
+    }
+
+    async fn notify(&self, text: &str) {
+        if let Some(notifier) = &self.notifier {
+            notifier(text.to_string()).await;
         }
-        Ok(())
     }
 
     pub async fn check_for_updates(&self) -> Result<()> {
         info!("Checking for binary updates...");
-        for (binary_name, config) in &self.binaries {
-            self.check_single_binary(binary_name, config).await?;
+        for binary_name in self.binary_names() {
+            // Failures are recorded and reported by run_check, the other binary is still checked
+            let _ = self.run_check(&binary_name, false).await;
         }
         Ok(())
     }
@@ -236,13 +392,128 @@ impl AutoUpdater {
     pub async fn start_periodic_checks(&self) -> Result<()> {
         info!("Starting periodic update checks every {} minutes",
             self.check_interval.as_secs() / 60);
-        let mut interval = interval(self.check_interval);
-        
+        // main.rs checks once at startup, so the first periodic check is one interval later
+        let mut interval = interval_at(Instant::now() + self.check_interval, self.check_interval);
+
+        let next_check = || Some(Local::now() + chrono::Duration::from_std(self.check_interval).unwrap_or_default());
+        *self.next_check.lock().unwrap() = next_check();
+
         loop {
             interval.tick().await;
+            *self.next_check.lock().unwrap() = next_check();
             if let Err(e) = self.check_for_updates().await {
                 error!("Update check failed: {}", e);
             }
         }
     }
-}
\ No newline at end of file
+
+    /// Managed binaries in a stable order
+    pub fn binary_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.binaries.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// What `/versions` shows about each binary
+    pub async fn status_report(&self) -> String {
+        let next_check = *self.next_check.lock().unwrap();
+        let mut report = String::new();
+        for name in self.binary_names() {
+            let config = &self.binaries[&name];
+            let version = self.version_manager.get_stored_version(&name).await.unwrap_or_default();
+            let installed_at = fs::metadata(&config.binary_path).await
+                .and_then(|metadata| metadata.modified())
+                .map(|modified| DateTime::<Local>::from(modified).format("%Y-%m-%d %H:%M").to_string())
+                .unwrap_or_else(|_| "not installed".to_string());
+            let last_check = self.last_checks.lock().unwrap().get(&name).cloned();
+
+            report.push_str(&format!("📦 {} {}\n", name, if version.is_empty() { "(unknown version)" } else { &version }));
+            report.push_str(&format!("Policy: {}\n", config.policy));
+            report.push_str(&format!("Installed: {}\n", installed_at));
+            match last_check {
+                Some(check) => report.push_str(&format!("Last check: {} {} {}\n", check.at.format("%Y-%m-%d %H:%M"), if check.ok { "✅" } else { "❌" }, check.summary)),
+                None => report.push_str("Last check: none yet\n"),
+            }
+            report.push('\n');
+        }
+        for name in ["yt-dlp", "ffmpeg"] {
+            if self.toolchain.is_managed(name) {
+                continue;
+            }
+            let path = self.toolchain.path_of(name);
+            let version = detect_version(name, path).await.unwrap_or_else(|e| format!("unusable: {}", e));
+            report.push_str(&format!("📦 {} {}\nExternal: {:?}, not updated by the bot\n\n", name, version, path));
+        }
+        match next_check {
+            Some(at) => report.push_str(&format!("Next check: {}", at.format("%Y-%m-%d %H:%M"))),
+            None => report.push_str("Next check: not scheduled"),
+        }
+        report
+    }
+}
+
+/// Result of checking one binary
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CheckOutcome {
+    UpToDate { version: String },
+    Updated { from: String, to: String },
+    Disabled { version: String },
+    /// The latest release was rolled back before and is left alone
+    SkippedRejected { version: String, release: String },
+    /// Running downloads didn't finish in time, the release is installed by a later check
+    Postponed { version: String, release: String },
+}
+
+impl fmt::Display for CheckOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckOutcome::UpToDate { version } => write!(f, "up to date ({})", version),
+            CheckOutcome::Updated { from, to } if from.is_empty() => write!(f, "installed {}", to),
+            CheckOutcome::Updated { from, to } => write!(f, "updated {} → {}", from, to),
+            CheckOutcome::Disabled { version } => write!(f, "updates disabled ({})", version),
+            CheckOutcome::SkippedRejected { version, release } => write!(f, "kept {}, release {} was rolled back", version, release),
+            CheckOutcome::Postponed { version, release } => write!(f, "kept {}, release {} postponed until downloads finish", version, release),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct LastCheck {
+    at: DateTime<Local>,
+    summary: String,
+    ok: bool,
+}
+
+/// Arguments of `/update [yt-dlp|ffmpeg] [--force]`
+pub fn parse_update_args(args: &str) -> Result<(Option<String>, bool)> {
+    let mut binary = None;
+    let mut force = false;
+    for arg in args.split_whitespace() {
+        match arg {
+            "--force" | "-f" => force = true,
+            "yt-dlp" | "ffmpeg" if binary.is_none() => binary = Some(arg.to_string()),
+            _ => return Err(anyhow::anyhow!("Unexpected argument {:?}", arg)),
+        }
+    }
+    Ok((binary, force))
+}
+
+fn file_name(path: &std::path::Path) -> String {
+    path.file_name().unwrap_or_default().to_string_lossy().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_update_args() {
+        assert_eq!(parse_update_args("").unwrap(), (None, false));
+        assert_eq!(parse_update_args("yt-dlp --force").unwrap(), (Some("yt-dlp".to_string()), true));
+        assert_eq!(parse_update_args("--force ffmpeg").unwrap(), (Some("ffmpeg".to_string()), true));
+        assert!(parse_update_args("youtube-dl").is_err());
+
+        let outcome = CheckOutcome::Updated { from: "2024.12.23".to_string(), to: "2025.01.15".to_string() };
+        assert_eq!(outcome.to_string(), "updated 2024.12.23 → 2025.01.15");
+    }
+}
@@ -1,7 +1,10 @@
-use std::path::PathBuf;
-use tokio::fs;
-use anyhow::Result;
+use std::path::{Path, PathBuf};
+use tokio::{fs, process::Command};
+use anyhow::{anyhow, Result};
 
+use crate::auto_update::policy::{parse_ffmpeg_version, parse_yt_dlp_version};
+
+#[derive(Debug, Clone)]
 pub struct VersionManager {
     storage_dir: PathBuf,
 }
@@ -11,6 +14,7 @@ impl VersionManager {
         Self { storage_dir }
     }
 
+    /// Version string the installed binary reports, see `detect_version`
     pub async fn get_stored_version(&self, binary_name: &str) -> Result<String> {
         let version_file = self.storage_dir.join(format!("{}.version", binary_name));
         if version_file.exists() {
@@ -26,4 +30,64 @@ impl VersionManager {
         fs::write(&version_file, version).await?;
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// Release the installed binary came from (feed tag or pinned version), used to spot new releases
+    pub async fn get_stored_release(&self, binary_name: &str) -> Result<String> {
+        let release_file = self.storage_dir.join(format!("{}.release", binary_name));
+        if release_file.exists() {
+            Ok(fs::read_to_string(&release_file).await?.trim().to_string())
+        } else {
+            Ok(String::new())
+        }
+    }
+
+    pub async fn save_release(&self, binary_name: &str, release_id: &str) -> Result<()> {
+        fs::create_dir_all(&self.storage_dir).await?;
+        let release_file = self.storage_dir.join(format!("{}.release", binary_name));
+        fs::write(&release_file, release_id).await?;
+        Ok(())
+    }
+
+    /// Digest the installed download was verified against, empty if it wasn't
+    pub async fn get_stored_checksum(&self, binary_name: &str) -> Result<String> {
+        let checksum_file = self.storage_dir.join(format!("{}.checksum", binary_name));
+        if checksum_file.exists() {
+            Ok(fs::read_to_string(&checksum_file).await?.trim().to_string())
+        } else {
+            Ok(String::new())
+        }
+    }
+
+    pub async fn save_checksum(&self, binary_name: &str, checksum: &str) -> Result<()> {
+        fs::create_dir_all(&self.storage_dir).await?;
+        let checksum_file = self.storage_dir.join(format!("{}.checksum", binary_name));
+        fs::write(&checksum_file, checksum).await?;
+        Ok(())
+    }
+
+    /// Release that was rolled back and must not be installed again
+    pub async fn get_rejected(&self, binary_name: &str) -> Result<String> {
+        let rejected_file = self.storage_dir.join(format!("{}.rejected", binary_name));
+        if rejected_file.exists() {
+            Ok(fs::read_to_string(&rejected_file).await?.trim().to_string())
+        } else {
+            Ok(String::new())
+        }
+    }
+
+    pub async fn save_rejected(&self, binary_name: &str, release_id: &str) -> Result<()> {
+        fs::create_dir_all(&self.storage_dir).await?;
+        let rejected_file = self.storage_dir.join(format!("{}.rejected", binary_name));
+        fs::write(&rejected_file, release_id).await?;
+        Ok(())
+    }
+}
+
+/// Asks an installed binary for its version: `yt-dlp --version`, `ffmpeg -version`
+pub async fn detect_version(binary_name: &str, binary_path: &Path) -> Result<String> {
+    let flag = if binary_name == "yt-dlp" { "--version" } else { "-version" };
+    let output = Command::new(binary_path).arg(flag).kill_on_drop(true).output().await?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let version = if binary_name == "yt-dlp" { parse_yt_dlp_version(&stdout) } else { parse_ffmpeg_version(&stdout) };
+    version.ok_or_else(|| anyhow!("Couldn't read the version of {:?}", binary_path))
+}
@@ -10,6 +10,7 @@ pub enum Command {
 }
 
 #[derive(BotCommands, Clone)]
+#[command(rename_rule = "lowercase")]
 pub enum AdminCommand {
     #[command(description = "add a channel: /addchannel <id_name>")]
     AddChannel(String),
@@ -19,4 +20,10 @@ pub enum AdminCommand {
     ListChannels,
     #[command(description = "toggle mandatory subscription.")]
     ToggleSubscription,
+    #[command(description = "show installed yt-dlp and ffmpeg versions and update checks.")]
+    Versions,
+    #[command(description = "update now: /update [yt-dlp|ffmpeg] [--force]")]
+    Update(String),
+    #[command(description = "send cookies.txt with the caption /setcookies <platform>; without arguments lists the stored cookies")]
+    SetCookies(String),
 }
@@ -1,14 +1,17 @@
 use std::path::PathBuf;
+use std::time::Duration;
 use anyhow::Result;
 
+use crate::auto_update::policy::UpdatePolicy;
+
 pub fn find_dotenv() -> Result<Option<PathBuf>> {
     // 1. Check directory where the executable is located
-    if let Ok(current_exe) = std::env::current_exe() {
-        if let Some(exe_dir) = current_exe.parent() {
-            let exe_dir_dotenv = exe_dir.join(".env");
-            if exe_dir_dotenv.exists() {
-                return Ok(Some(exe_dir_dotenv));
-            }
+    if let Ok(current_exe) = std::env::current_exe()
+        && let Some(exe_dir) = current_exe.parent()
+    {
+        let exe_dir_dotenv = exe_dir.join(".env");
+        if exe_dir_dotenv.exists() {
+            return Ok(Some(exe_dir_dotenv));
         }
     }
 
@@ -35,6 +38,247 @@ pub fn load_environment() -> Result<()> {
     Ok(())
 }
 
+/// Default public Bot API endpoint used when no custom server is configured
+pub const DEFAULT_BOT_API_URL: &str = "https://api.telegram.org";
+/// Upload limit of the public Bot API (keeping a safety margin below 50MB)
+pub const TELEGRAM_BOT_API_FILE_LIMIT: u64 = 48 * 1024 * 1024; // 48MB
+/// Upload limit of a self-hosted telegram-bot-api server running with --local
+pub const LOCAL_BOT_API_FILE_LIMIT: u64 = 2000 * 1024 * 1024; // 2000MB
+/// Upload limit of MTProto for regular (non-premium) accounts
+pub const MTPROTO_FILE_LIMIT: u64 = 2000 * 1024 * 1024; // 2000MB
+/// Files up to this multiple of the Bot API limit are compressed instead of going through MTProto
+pub const DEFAULT_COMPRESS_MAX_RATIO: f64 = 2.0;
+
+/// Largest single file the bot sends, bigger media is split into parts.
+///
+/// Read from `MAX_UPLOAD_SIZE_MB` and capped at the MTProto limit.
+pub fn max_upload_size() -> u64 {
+    std::env::var("MAX_UPLOAD_SIZE_MB")
+        .ok()
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .filter(|mb| *mb > 0)
+        .map(|mb| mb.saturating_mul(1024 * 1024).min(MTPROTO_FILE_LIMIT))
+        .unwrap_or(MTPROTO_FILE_LIMIT)
+}
+
+/// Re-encode around the cut points of trimmed clips so they start exactly at the requested time.
+///
+/// Read from `PRECISE_CLIP_CUTS`, off by default: cuts then snap to keyframes but need no re-encode.
+pub fn precise_clip_cuts() -> bool {
+    std::env::var("PRECISE_CLIP_CUTS")
+        .map(|value| value.trim().eq_ignore_ascii_case("true"))
+        .unwrap_or(false)
+}
+
+/// Update policy of a managed binary (`yt-dlp` or `ffmpeg`).
+///
+/// Read from `YT_DLP_UPDATE_POLICY` / `FFMPEG_UPDATE_POLICY`: `stable` (default), `nightly`,
+/// `disabled` or `pin:<version>`.
+pub fn update_policy(binary_name: &str) -> UpdatePolicy {
+    let variable = format!("{}_UPDATE_POLICY", binary_name.replace('-', "_").to_uppercase());
+    match std::env::var(&variable) {
+        Ok(value) => UpdatePolicy::parse(&value).unwrap_or_else(|| {
+            log::warn!("Invalid {}={:?}, following stable releases", variable, value);
+            UpdatePolicy::default()
+        }),
+        Err(_) => UpdatePolicy::default(),
+    }
+}
+
+/// Where the bot takes yt-dlp, ffmpeg or ffprobe from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ToolSource {
+    /// Downloaded into the libraries directory and auto-updated
+    Managed,
+    /// Looked up on `PATH`
+    System,
+    Path(PathBuf),
+}
+
+/// Reads `YT_DLP_PATH`, `FFMPEG_PATH` or `FFPROBE_PATH`: unset for a bundled copy, `system` to
+/// use the one on `PATH`, or an explicit path
+pub fn tool_source(variable: &str) -> ToolSource {
+    match std::env::var(variable).map(|value| value.trim().to_string()) {
+        Ok(value) if value.is_empty() || value.eq_ignore_ascii_case("managed") => ToolSource::Managed,
+        Ok(value) if value.eq_ignore_ascii_case("system") => ToolSource::System,
+        Ok(value) => ToolSource::Path(PathBuf::from(value)),
+        Err(_) => ToolSource::Managed,
+    }
+}
+
+/// Message the admins when an automatic yt-dlp / ffmpeg update is installed or fails.
+///
+/// Read from `NOTIFY_ADMINS_ON_UPDATE`, off by default.
+pub fn notify_admins_on_update() -> bool {
+    std::env::var("NOTIFY_ADMINS_ON_UPDATE")
+        .map(|value| value.trim().eq_ignore_ascii_case("true"))
+        .unwrap_or(false)
+}
+
+/// Longest an update waits for running downloads before it is postponed to the next check.
+///
+/// Read from `UPDATE_MAX_WAIT_SECS`, 120 seconds by default. New downloads queue behind a waiting
+/// update, so this is also how long they may be held up.
+pub fn update_max_wait() -> Duration {
+    std::env::var("UPDATE_MAX_WAIT_SECS")
+        .ok()
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .map(Duration::from_secs)
+        .unwrap_or(Duration::from_secs(120))
+}
+
+/// Comma-separated proxy URLs in `variable`; `direct` (or `none`) means an empty list, i.e. no proxy
+fn proxy_list(variable: &str) -> Option<Vec<String>> {
+    let value = std::env::var(variable).ok()?;
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("direct") || value.eq_ignore_ascii_case("none") {
+        return Some(Vec::new());
+    }
+    Some(value.split(',').map(str::trim).filter(|url| !url.is_empty()).map(str::to_string).collect())
+}
+
+/// Proxy pool yt-dlp rotates through, from `PROXY_URLS` (`http://`, `socks5://` ...); empty without one
+pub fn default_proxy_urls() -> Vec<String> {
+    proxy_list("PROXY_URLS").unwrap_or_default()
+}
+
+/// Pools for single platforms from `PROXY_URLS_<PLATFORM>`, e.g. `PROXY_URLS_INSTAGRAM`,
+/// keyed by the lowercase platform name
+pub fn platform_proxy_urls() -> Vec<(String, Vec<String>)> {
+    std::env::vars()
+        .filter_map(|(name, _)| {
+            let platform = name.strip_prefix("PROXY_URLS_")?.to_lowercase();
+            Some((platform, proxy_list(&name)?))
+        })
+        .collect()
+}
+
+/// Proxy for the bot's own HTTP traffic: the Bot API, binary downloads and update checks.
+///
+/// Read from `HTTP_PROXY_URL`, none by default.
+pub fn http_proxy_url() -> Option<String> {
+    std::env::var("HTTP_PROXY_URL").ok().map(|url| url.trim().to_string()).filter(|url| !url.is_empty())
+}
+
+/// SOCKS5 proxy for the MTProto connection, from `MTPROTO_PROXY_URL`
+pub fn mtproto_proxy_url() -> Option<String> {
+    std::env::var("MTPROTO_PROXY_URL").ok().map(|url| url.trim().to_string()).filter(|url| !url.is_empty())
+}
+
+/// Where admin-uploaded cookies.txt files are kept, from `COOKIES_DIR` (default `cookies` next to the executable)
+pub fn cookies_dir(exe_dir: &std::path::Path) -> PathBuf {
+    std::env::var("COOKIES_DIR")
+        .ok()
+        .filter(|dir| !dir.trim().is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| exe_dir.join("cookies"))
+}
+
+/// Limits checked against the metadata probe before a download starts
+#[derive(Clone, Debug, PartialEq)]
+pub struct DownloadLimits {
+    /// Longest video (or requested clip) in seconds
+    pub max_duration: Option<f64>,
+    /// Largest estimated download in bytes
+    pub max_filesize: Option<u64>,
+    pub allow_live: bool,
+}
+
+impl DownloadLimits {
+    /// Reads `MAX_VIDEO_DURATION_MINUTES` (default 60), `MAX_ESTIMATED_FILESIZE_MB` (default 4000)
+    /// and `ALLOW_LIVE_STREAMS` (default false); `0` disables a limit
+    pub fn from_env() -> Self {
+        let limit = |variable: &str, default: u64| {
+            let value = std::env::var(variable).ok().and_then(|value| value.trim().parse::<u64>().ok()).unwrap_or(default);
+            (value > 0).then_some(value)
+        };
+        Self {
+            max_duration: limit("MAX_VIDEO_DURATION_MINUTES", 60).map(|minutes| minutes as f64 * 60.0),
+            max_filesize: limit("MAX_ESTIMATED_FILESIZE_MB", 4000).map(|mb| {
+                mb.checked_mul(1_000_000).unwrap_or_else(|| {
+                    log::warn!("MAX_ESTIMATED_FILESIZE_MB={} is out of range, using the default of 4000", mb);
+                    4000 * 1_000_000
+                })
+            }),
+            allow_live: std::env::var("ALLOW_LIVE_STREAMS")
+                .map(|value| value.trim().eq_ignore_ascii_case("true"))
+                .unwrap_or(false),
+        }
+    }
+}
+
+/// Bot API endpoint settings shared by teloxide and the reqwest based uploaders
+#[derive(Clone, Debug)]
+pub struct BotApiConfig {
+    pub base_url: String,
+    pub local_mode: bool,
+    /// Re-encode videos slightly over the limit so they can still be sent through the Bot API
+    pub compress_oversized: bool,
+    pub compress_max_ratio: f64,
+}
+
+impl BotApiConfig {
+    /// Reads `TELEGRAM_BOT_API_URL`, `TELEGRAM_BOT_API_LOCAL`, `COMPRESS_TO_FIT_BOT_API`
+    /// and `COMPRESS_MAX_RATIO` from the environment
+    pub fn from_env() -> Self {
+        let base_url = std::env::var("TELEGRAM_BOT_API_URL")
+            .ok()
+            .map(|url| url.trim().trim_end_matches('/').to_string())
+            .filter(|url| !url.is_empty())
+            .unwrap_or_else(|| DEFAULT_BOT_API_URL.to_string());
+        let local_mode = std::env::var("TELEGRAM_BOT_API_LOCAL")
+            .map(|value| value.trim().eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+        let compress_oversized = std::env::var("COMPRESS_TO_FIT_BOT_API")
+            .map(|value| value.trim().eq_ignore_ascii_case("true"))
+            .unwrap_or(false);
+        let compress_max_ratio = std::env::var("COMPRESS_MAX_RATIO")
+            .ok()
+            .and_then(|value| value.trim().parse::<f64>().ok())
+            .filter(|ratio| *ratio > 1.0)
+            .unwrap_or(DEFAULT_COMPRESS_MAX_RATIO);
+
+        Self { base_url, local_mode, compress_oversized, compress_max_ratio }
+    }
+
+    pub fn is_custom(&self) -> bool {
+        self.base_url != DEFAULT_BOT_API_URL
+    }
+
+    /// Full URL of a Bot API method, e.g. `https://api.telegram.org/bot<token>/sendVideo`
+    pub fn method_url(&self, bot_token: &str, method: &str) -> String {
+        format!("{}/bot{}/{}", self.base_url, bot_token, method)
+    }
+
+    /// Maximum file size that can be sent through the Bot API
+    pub fn file_limit(&self) -> u64 {
+        if self.local_mode {
+            LOCAL_BOT_API_FILE_LIMIT
+        } else {
+            TELEGRAM_BOT_API_FILE_LIMIT
+        }
+    }
+
+    /// Whether a file over the limit is close enough to it to be worth compressing
+    pub fn should_compress(&self, file_size: u64) -> bool {
+        let limit = self.file_limit();
+        self.compress_oversized
+            && file_size > limit
+            && file_size as f64 <= limit as f64 * self.compress_max_ratio
+    }
+}
+
+impl Default for BotApiConfig {
+    fn default() -> Self {
+        Self {
+            base_url: DEFAULT_BOT_API_URL.to_string(),
+            local_mode: false,
+            compress_oversized: false,
+            compress_max_ratio: DEFAULT_COMPRESS_MAX_RATIO,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -47,4 +291,36 @@ mod tests {
         // We just test that it doesn't panic
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_bot_api_config_method_url_and_limits() {
+        let default_config = BotApiConfig::default();
+        assert!(!default_config.is_custom());
+        assert_eq!(default_config.method_url("TOKEN", "sendVideo"), "https://api.telegram.org/botTOKEN/sendVideo");
+        assert_eq!(default_config.file_limit(), TELEGRAM_BOT_API_FILE_LIMIT);
+
+        let local_config = BotApiConfig {
+            base_url: "http://localhost:8081".to_string(),
+            local_mode: true,
+            ..BotApiConfig::default()
+        };
+        assert!(local_config.is_custom());
+        assert_eq!(local_config.method_url("TOKEN", "sendAudio"), "http://localhost:8081/botTOKEN/sendAudio");
+        assert_eq!(local_config.file_limit(), LOCAL_BOT_API_FILE_LIMIT);
+    }
+
+    #[test]
+    fn test_should_compress_only_slightly_oversized_files() {
+        let disabled = BotApiConfig::default();
+        assert!(!disabled.should_compress(TELEGRAM_BOT_API_FILE_LIMIT + 1));
+
+        let config = BotApiConfig {
+            compress_oversized: true,
+            ..BotApiConfig::default()
+        };
+        assert!(!config.should_compress(TELEGRAM_BOT_API_FILE_LIMIT));
+        assert!(config.should_compress(TELEGRAM_BOT_API_FILE_LIMIT + 1));
+        assert!(config.should_compress(TELEGRAM_BOT_API_FILE_LIMIT * 2));
+        assert!(!config.should_compress(TELEGRAM_BOT_API_FILE_LIMIT * 2 + 1));
+    }
 }
\ No newline at end of file
@@ -37,11 +37,17 @@ pub fn init_database() -> Result<()> {
     let db_path = get_database_path();
     let conn = Connection::open(db_path)?;
     conn.execute(
-        "CREATE TABLE IF NOT EXISTS users (id INTEGER PRIMARY KEY, telegram_id BIGINT UNIQUE NOT NULL, last_active DATETIME DEFAULT CURRENT_TIMESTAMP, quality_preference TEXT DEFAULT 'h264')",
+        "CREATE TABLE IF NOT EXISTS users (id INTEGER PRIMARY KEY, telegram_id BIGINT UNIQUE NOT NULL, last_active DATETIME DEFAULT CURRENT_TIMESTAMP, quality_preference TEXT DEFAULT 'h264', resolution_preference TEXT DEFAULT '1080', audio_preference TEXT DEFAULT 'mp3_320')",
         (),
     )?;
     // Add the quality_preference column to the users table if it doesn't exist, ignoring the error if it does.
     let _ = conn.execute("ALTER TABLE users ADD COLUMN quality_preference TEXT DEFAULT 'h264'", ());
+    // Same for the resolution cap and audio format added later
+    // Existing users keep what they got before the column: h264 capped at 1080p, h265 uncapped
+    if conn.execute("ALTER TABLE users ADD COLUMN resolution_preference TEXT DEFAULT '1080'", ()).is_ok() {
+        conn.execute("UPDATE users SET resolution_preference = 'best' WHERE quality_preference = 'h265'", ())?;
+    }
+    let _ = conn.execute("ALTER TABLE users ADD COLUMN audio_preference TEXT DEFAULT 'mp3_320'", ());
 
     // Create the table with the new format
     conn.execute(
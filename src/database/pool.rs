@@ -5,6 +5,8 @@ use std::sync::Arc;
 use lru::LruCache;
 use std::num::NonZeroUsize;
 
+use crate::yt_dlp_interface::fetcher::DEFAULT_RESOLUTION;
+
 pub struct DatabasePool {
     db_path: String,
     connection_semaphore: Arc<Semaphore>,
@@ -15,6 +17,8 @@ pub struct DatabasePool {
 #[derive(Clone)]
 pub struct UserInfo {
     pub quality_preference: String,
+    pub resolution_preference: String,
+    pub audio_preference: String,
     pub last_updated: tokio::time::Instant,
 }
 
@@ -69,14 +73,28 @@ impl DatabasePool {
 
     /// Get user quality preference with caching
     pub async fn get_user_quality(&self, user_id: i64) -> Result<String, anyhow::Error> {
+        Ok(self.get_user_info(user_id).await?.quality_preference)
+    }
+
+    /// Get user resolution cap ("480", "720", "1080" or "best") with caching
+    pub async fn get_user_resolution(&self, user_id: i64) -> Result<String, anyhow::Error> {
+        Ok(self.get_user_info(user_id).await?.resolution_preference)
+    }
+
+    /// Get user audio format (see `media::audio_format::AudioFormat`) with caching
+    pub async fn get_user_audio_format(&self, user_id: i64) -> Result<String, anyhow::Error> {
+        Ok(self.get_user_info(user_id).await?.audio_preference)
+    }
+
+    async fn get_user_info(&self, user_id: i64) -> Result<UserInfo, anyhow::Error> {
         // Check LRU cache
         {
             let mut cache = self.user_cache.lock().await;
             if let Some(user_info) = cache.get(&user_id) {
                 // Cache is valid for 5 minutes
                 if user_info.last_updated.elapsed() < Duration::from_secs(300) {
-                    log::info!("Using cached quality preference for user {}: {}", user_id, user_info.quality_preference);
-                    return Ok(user_info.quality_preference.clone());
+                    log::info!("Using cached preferences for user {}: {} / {}", user_id, user_info.quality_preference, user_info.resolution_preference);
+                    return Ok(user_info.clone());
                 }
                 log::info!("Cache expired for user {}, removing from cache", user_id);
                 // LRU automatically moves the element to the front when accessed with get,
@@ -86,44 +104,49 @@ impl DatabasePool {
         }
 
         // Load from DB
-        let quality = self.execute_with_timeout(move |conn| {
+        let (quality, resolution, audio) = self.execute_with_timeout(move |conn| {
             match conn.query_row(
-                "SELECT quality_preference FROM users WHERE telegram_id = ?1",
+                "SELECT quality_preference, resolution_preference, audio_preference FROM users WHERE telegram_id = ?1",
                 params![user_id],
-                |row| Ok(row.get::<_, String>(0)?)
+                |row| Ok((
+                    row.get::<_, Option<String>>(0)?.unwrap_or_else(|| "best".to_string()),
+                    row.get::<_, Option<String>>(1)?.unwrap_or_else(|| DEFAULT_RESOLUTION.to_string()),
+                    row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                ))
             ) {
-                Ok(quality) => {
-                    log::info!("Retrieved quality preference from DB for user {}: {}", user_id, quality);
-                    Ok(quality)
+                Ok(preferences) => {
+                    log::info!("Retrieved preferences from DB for user {}: {} / {} / {}", user_id, preferences.0, preferences.1, preferences.2);
+                    Ok(preferences)
                 },
                 Err(rusqlite::Error::QueryReturnedNoRows) => {
-                    log::info!("No quality preference found for user {}, using default", user_id);
-                    Ok("best".to_string()) // Default value
+                    log::info!("No preferences found for user {}, using default", user_id);
+                    Ok(("best".to_string(), DEFAULT_RESOLUTION.to_string(), String::new())) // Default value
                 },
                 Err(e) => {
-                    log::error!("Error retrieving quality preference for user {} from DB: {}", user_id, e);
-                    Ok("best".to_string()) // Default value
+                    log::error!("Error retrieving preferences for user {} from DB: {}", user_id, e);
+                    Ok(("best".to_string(), DEFAULT_RESOLUTION.to_string(), String::new())) // Default value
                 }
             }
         }).await?;
 
+        let user_info = UserInfo {
+            quality_preference: quality,
+            resolution_preference: resolution,
+            audio_preference: audio,
+            last_updated: tokio::time::Instant::now(),
+        };
+
         // Update LRU cache (put automatically evicts old entries)
         {
             let mut cache = self.user_cache.lock().await;
-            log::info!("Caching quality preference for user {}: {}", user_id, quality);
-            cache.put(
-                user_id,
-                UserInfo {
-                    quality_preference: quality.clone(),
-                    last_updated: tokio::time::Instant::now(),
-                }
-            );
+            log::info!("Caching preferences for user {}", user_id);
+            cache.put(user_id, user_info.clone());
         }
-        
-        Ok(quality)
+
+        Ok(user_info)
     }
 
-    /// Invalidate user quality cache
+    /// Invalidate cached user preferences (quality, resolution and audio format)
     pub async fn invalidate_user_quality_cache(&self, user_id: i64) {
         let mut cache = self.user_cache.lock().await;
         cache.pop(&user_id);
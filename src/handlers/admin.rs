@@ -2,28 +2,46 @@ use teloxide::prelude::*;
 use teloxide::utils::command::BotCommands;
 use rusqlite::{Result, params};
 use std::env;
+use std::path::Path;
 use std::sync::Arc;
 
+use crate::auto_update::AutoUpdater;
+use crate::auto_update::updater::parse_update_args;
 use crate::commands::AdminCommand;
 use crate::database::DatabasePool;
+use crate::yt_dlp_interface::YoutubeFetcher;
+use crate::yt_dlp_interface::cookies::{format_expiry, CookieJars, MAX_COOKIE_FILE_SIZE};
+use crate::utils::limited_bot::LimitedBot;
 
-pub async fn is_admin(msg: &Message) -> bool {
+/// Telegram IDs listed in `ADMIN_IDS`
+pub fn admin_ids() -> Vec<i64> {
     let admin_ids_str = env::var("ADMIN_IDS").unwrap_or_default();
-    let admin_ids: Vec<i64> = admin_ids_str
+    admin_ids_str
         .split(',')
         .filter_map(|s| s.trim().parse().ok())
-        .collect();
-    
-    admin_ids.contains(&msg.chat.id.0)
+        .collect()
+}
+
+/// Sends `text` to every admin, failures are only logged
+pub async fn notify_admins(bot: &LimitedBot, text: &str) {
+    for admin_id in admin_ids() {
+        if let Err(e) = bot.send_message(ChatId(admin_id), text).await {
+            log::warn!("Failed to notify admin {}: {}", admin_id, e);
+        }
+    }
 }
 
-pub async fn admin_command_handler(bot: Bot, msg: Message, db_pool: Arc<DatabasePool>) -> Result<(), anyhow::Error> {
+pub async fn is_admin(msg: &Message) -> bool {
+    admin_ids().contains(&msg.chat.id.0)
+}
+
+pub async fn admin_command_handler(bot: LimitedBot, msg: Message, db_pool: Arc<DatabasePool>, auto_updater: Arc<AutoUpdater>, fetcher: Arc<YoutubeFetcher>) -> Result<(), anyhow::Error> {
     if !is_admin(&msg).await {
         bot.send_message(msg.chat.id, "This command is for admins only.").await?;
         return Ok(())
     }
 
-    let text = msg.text().unwrap_or_default();
+    let text = msg.text().or(msg.caption()).unwrap_or_default();
     let cmd = match AdminCommand::parse(text, "admin") {
         Ok(cmd) => cmd,
         Err(_) => {
@@ -112,7 +130,7 @@ pub async fn admin_command_handler(bot: Bot, msg: Message, db_pool: Arc<Database
                     [],
                     |row| row.get(0),
                 )?;
-                let new_value = !(current_value == "true");
+                let new_value = current_value != "true" ;
                 conn.execute(
                     "UPDATE settings SET value = ?1 WHERE key = 'subscription_required'",
                     params![new_value.to_string()],
@@ -131,7 +149,101 @@ pub async fn admin_command_handler(bot: Bot, msg: Message, db_pool: Arc<Database
                 }
             }
         }
+        AdminCommand::Versions => {
+            bot.send_message(msg.chat.id, auto_updater.status_report().await).await?;
+        }
+        AdminCommand::Update(args) => {
+            let (binary, force) = match parse_update_args(&args) {
+                Ok(parsed) => parsed,
+                Err(e) => {
+                    bot.send_message(msg.chat.id, format!("{}. Usage: /update [yt-dlp|ffmpeg] [--force]", e)).await?;
+                    return Ok(())
+                }
+            };
+
+            // A named binary is checked even when external, so the reply says why it wasn't updated
+            let names: Vec<String> = match binary {
+                Some(name) => vec![name],
+                None => auto_updater.binary_names(),
+            };
+            let status = bot.send_message(msg.chat.id, format!("🔄 Checking {}{}...", names.join(" and "), if force { " (forced)" } else { "" })).await?;
+
+            let mut report = String::new();
+            for name in names {
+                bot.edit_message_text(msg.chat.id, status.id, format!("{}⏳ {}: checking and installing...", report, name)).await?;
+                match auto_updater.run_check(&name, force).await {
+                    Ok(outcome) => report.push_str(&format!("✅ {}: {}\n", name, outcome)),
+                    Err(e) => report.push_str(&format!("❌ {}: {:#}\n", name, e)),
+                }
+            }
+            bot.edit_message_text(msg.chat.id, status.id, report).await?;
+        }
+        AdminCommand::SetCookies(platform) => {
+            set_cookies(&bot, &msg, &fetcher.cookie_jars, &platform.trim().to_lowercase()).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// `/setcookies <platform>` with a cookies.txt attached (or replying to one); lists the jars without a platform
+async fn set_cookies(bot: &LimitedBot, msg: &Message, cookie_jars: &CookieJars, platform: &str) -> Result<(), anyhow::Error> {
+    if platform.is_empty() {
+        let mut report = String::from("Stored cookies:\n");
+        let jars = cookie_jars.list().await;
+        if jars.is_empty() {
+            report.push_str("none\n");
+        }
+        for (platform, info) in jars {
+            match info {
+                Ok(info) => report.push_str(&format!("🍪 {}: {}\n", platform, format_expiry(&info))),
+                Err(e) => report.push_str(&format!("⚠️ {}: unusable, {}\n", platform, e)),
+            }
+        }
+        report.push_str("\nSend a cookies.txt with the caption /setcookies <platform> to add or replace one.");
+        bot.send_message(msg.chat.id, report).await?;
+        return Ok(());
+    }
+
+    let (carrier, document) = match (msg.document(), msg.reply_to_message()) {
+        (Some(document), _) => (msg, document),
+        (None, Some(reply)) if reply.document().is_some() => (reply, reply.document().unwrap()),
+        _ => {
+            bot.send_message(msg.chat.id, format!("Attach a Netscape cookies.txt with the caption /setcookies {} or reply to one with it.", platform)).await?;
+            return Ok(());
+        }
+    };
+    if document.file.size > MAX_COOKIE_FILE_SIZE {
+        bot.send_message(msg.chat.id, "❌ That file is too large for a cookies.txt.").await?;
+        return Ok(());
     }
 
+    let file = bot.get_file(document.file.id.clone()).await?;
+    // A local Bot API server hands out paths on its own disk instead of downloadable ones
+    let local_path = Path::new(&file.path);
+    let mut content = Vec::new();
+    if local_path.is_absolute() && local_path.is_file() {
+        content = tokio::fs::read(local_path).await?;
+    } else {
+        bot.download_file(&file.path, &mut content).await?;
+    }
+    let Ok(content) = String::from_utf8(content) else {
+        bot.send_message(msg.chat.id, "❌ That is not a text file, export the cookies in the Netscape cookies.txt format.").await?;
+        return Ok(());
+    };
+
+    match cookie_jars.store(platform, &content).await {
+        Ok(info) => {
+            // The session cookies shouldn't stay around in the chat history
+            let removed = bot.delete_message(carrier.chat.id, carrier.id).await.is_ok();
+            bot.send_message(msg.chat.id, format!(
+                "✅ Cookies for {} saved: {}.{}",
+                platform, format_expiry(&info), if removed { " The uploaded file was deleted from the chat." } else { " Delete the uploaded file from the chat." }
+            )).await?;
+        }
+        Err(e) => {
+            bot.send_message(msg.chat.id, format!("❌ Cookies for {} not saved: {}", platform, e)).await?;
+        }
+    }
     Ok(())
-}
\ No newline at end of file
+}
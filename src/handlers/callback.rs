@@ -6,217 +6,293 @@ use std::sync::Arc;
 
 use crate::database::DatabasePool;
 use crate::handlers::admin::is_admin;
-use crate::handlers::command::{get_main_reply_keyboard, get_format_reply_keyboard, get_subscription_reply_keyboard};
-
-pub async fn callback_handler(bot: Bot, q: CallbackQuery, db_pool: Arc<DatabasePool>) -> Result<(), anyhow::Error> {
+use crate::handlers::command::{get_main_reply_keyboard, get_format_reply_keyboard, get_resolution_reply_keyboard, get_audio_reply_keyboard, get_subscription_reply_keyboard};
+use crate::media::audio_format::AudioFormat;
+use crate::utils::limited_bot::LimitedBot;
+
+/// Resolution caps offered in the settings, as (stored value, button label)
+const QUALITY_OPTIONS: [(&str, &str); 7] = [
+    ("h265", "h265"),
+    ("h264", "h264"),
+    ("audio", "audio"),
+    ("voice", "voice"),
+    ("circle", "circle"),
+    ("animation", "animation"),
+    ("ask", "ask every time"),
+];
+const RESOLUTION_OPTIONS: [(&str, &str); 4] = [("480", "480p"), ("720", "720p"), ("1080", "1080p"), ("best", "best")];
+const RESOLUTION_MENU_TEXT: &str = "Maximum video resolution, applied to every format.\nbest: highest resolution available";
+const AUDIO_MENU_TEXT: &str = "Audio format used by the audio mode.\nmp3: re-encoded at the chosen bitrate\nm4a (original): source AAC without re-encoding\nopus: small files, same codec as voice messages\nflac: lossless";
+
+pub async fn callback_handler(bot: LimitedBot, q: CallbackQuery, db_pool: Arc<DatabasePool>) -> Result<(), anyhow::Error> {
     if let Some(data) = q.data {
         log::info!("Received callback query with data: {}", data);
 
-        if let Some(maybe_message) = q.message {
-            if let Some(message) = maybe_message.regular_message() {
-                if data.starts_with("set_quality_") {
-                    let quality = data.split_at("set_quality_".len()).1;
-                    let user_id = message.chat.id.0;
-                    let quality_string = quality.to_string(); // Make a string copy
+        if let Some(maybe_message) = q.message
+            && let Some(message) = maybe_message.regular_message()
+        {
+            if data.starts_with("set_quality_") {
+                let quality = data.split_at("set_quality_".len()).1;
+                let user_id = message.chat.id.0;
+                let quality_string = quality.to_string(); // Make a string copy
                     
-                    // Use database pool for quality preference update
-                    let result = db_pool.execute_with_timeout(move |conn| {
-                        conn.execute(
-                            "UPDATE users SET quality_preference = ?1 WHERE telegram_id = ?2",
-                            params![quality_string, user_id],
-                        )
-                    }).await;
+                // Use database pool for quality preference update
+                let result = db_pool.execute_with_timeout(move |conn| {
+                    conn.execute(
+                        "UPDATE users SET quality_preference = ?1 WHERE telegram_id = ?2",
+                        params![quality_string, user_id],
+                    )
+                }).await;
                     
-                    match result {
-        Ok(_) => {
-            // Invalidate the cache for this user to ensure the new quality setting is picked up immediately
-            db_pool.invalidate_user_quality_cache(user_id).await;
-            bot.answer_callback_query(q.id).text(&format!("Quality set to {}", quality)).await?;
-        },
-        Err(e) => {
-            log::error!("Failed to update quality preference: {}", e);
-            bot.answer_callback_query(q.id).text("Failed to update quality preference").await?;
-        }
-    }
-                } else {
-                    match data.as_str() {
-                        "settings" => {
-                            let mut keyboard_rows = vec![vec![
-                                InlineKeyboardButton::callback("Format", "format_menu"),
-                            ]];
-
-                            if is_admin(&message).await {
-                                keyboard_rows.push(vec![
-                                    InlineKeyboardButton::callback("Subscription", "subscription_menu"),
-                                ]);
-                            }
-
+                match result {
+                    Ok(_) => {
+                        // Invalidate the cache for this user to ensure the new quality setting is picked up immediately
+                        db_pool.invalidate_user_quality_cache(user_id).await;
+                        bot.answer_callback_query(q.id).text(format!("Quality set to {}", quality)).await?;
+                    },
+                    Err(e) => {
+                        log::error!("Failed to update quality preference: {}", e);
+                        bot.answer_callback_query(q.id).text("Failed to update quality preference").await?;
+                    }
+                }
+            } else if let Some(resolution) = data.strip_prefix("set_resolution_") {
+                match set_user_resolution(&db_pool, message.chat.id.0, resolution).await {
+                    Ok(_) => {
+                        bot.answer_callback_query(q.id).text(format!("Resolution set to {}", resolution_label(resolution))).await?;
+                    },
+                    Err(e) => {
+                        log::error!("Failed to update resolution preference: {}", e);
+                        bot.answer_callback_query(q.id).text("Failed to update resolution preference").await?;
+                    }
+                }
+            } else if let Some(audio_format) = data.strip_prefix("set_audio_") {
+                match set_user_audio_format(&db_pool, message.chat.id.0, audio_format).await {
+                    Ok(format) => {
+                        bot.answer_callback_query(q.id).text(format!("Audio format set to {}", format.label())).await?;
+                    },
+                    Err(e) => {
+                        log::error!("Failed to update audio preference: {}", e);
+                        bot.answer_callback_query(q.id).text("Failed to update audio preference").await?;
+                    }
+                }
+            } else {
+                match data.as_str() {
+                    "settings" => {
+                        let mut keyboard_rows = vec![vec![
+                            InlineKeyboardButton::callback("Format", "format_menu"),
+                            InlineKeyboardButton::callback("Resolution", "resolution_menu"),
+                            InlineKeyboardButton::callback("Audio format", "audio_menu"),
+                        ]];
+
+                        if is_admin(message).await {
                             keyboard_rows.push(vec![
-                                InlineKeyboardButton::callback("Back", "back_to_main"),
+                                InlineKeyboardButton::callback("Subscription", "subscription_menu"),
                             ]);
+                        }
 
-                            let keyboard = InlineKeyboardMarkup::new(keyboard_rows);
+                        keyboard_rows.push(vec![
+                            InlineKeyboardButton::callback("Back", "back_to_main"),
+                        ]);
 
-                            bot.edit_message_text(message.chat.id, message.id, "Settings").await?;
-                            bot.edit_message_reply_markup(message.chat.id, message.id).reply_markup(keyboard).await?;
-                        }
-                        "format_menu" => {
-                            let keyboard = InlineKeyboardMarkup::new(vec![ 
-                                vec![ 
-                                    InlineKeyboardButton::callback("h265", "set_quality_h265"),
-                                    InlineKeyboardButton::callback("h264", "set_quality_h264"),
-                                    InlineKeyboardButton::callback("audio", "set_quality_audio"),
-                                ],
-                                vec![ 
-                                    InlineKeyboardButton::callback("Back", "back_to_settings"),
-                                ]
-                            ]);
-                            let text = "h265: best quality, but may not work on some devices.\nh264: worse quality, but works on many devices.\naudio: audio only";
-                            bot.edit_message_text(message.chat.id, message.id, text).await?;
-                            bot.edit_message_reply_markup(message.chat.id, message.id).reply_markup(keyboard).await?;
-                        }
-                        "back_to_main" => {
-                            let keyboard = InlineKeyboardMarkup::new(vec![vec![ 
-                                InlineKeyboardButton::callback("Settings", "settings"),
-                            ]]);
-                            bot.edit_message_reply_markup(message.chat.id, message.id).reply_markup(keyboard).await?;
-                            bot.send_message(message.chat.id, "").reply_markup(get_main_reply_keyboard()).await?;
-                        }
-                        "back_to_settings" => {
-                            let keyboard = InlineKeyboardMarkup::new(vec![vec![ 
-                                InlineKeyboardButton::callback("Format", "format_menu"),
+                        let keyboard = InlineKeyboardMarkup::new(keyboard_rows);
+
+                        bot.edit_message_text(message.chat.id, message.id, "Settings").await?;
+                        bot.edit_message_reply_markup(message.chat.id, message.id).reply_markup(keyboard).await?;
+                    }
+                    "format_menu" => {
+                        let keyboard = InlineKeyboardMarkup::new(vec![ 
+                            vec![ 
+                                InlineKeyboardButton::callback("h265", "set_quality_h265"),
+                                InlineKeyboardButton::callback("h264", "set_quality_h264"),
+                                InlineKeyboardButton::callback("audio", "set_quality_audio"),
                             ],
                             vec![ 
-                                InlineKeyboardButton::callback("Back", "back_to_main"),
-                            ]]);
-
-                            bot.edit_message_text(message.chat.id, message.id, "Settings").await?;
-                            bot.edit_message_reply_markup(message.chat.id, message.id).reply_markup(keyboard).await?;
-                        }
-                    "toggle_subscription" => {
-                        // This arm is no longer needed as toggle logic is handled by enable/disable
-                        bot.answer_callback_query(q.id).text("Action not available.").await?;
+                                InlineKeyboardButton::callback("voice", "set_quality_voice"),
+                                InlineKeyboardButton::callback("circle", "set_quality_circle"),
+                                InlineKeyboardButton::callback("animation", "set_quality_animation"),
+                            ],
+                            vec![ 
+                                InlineKeyboardButton::callback("ask every time", "set_quality_ask"),
+                            ],
+                            vec![ 
+                                InlineKeyboardButton::callback("Back", "back_to_settings"),
+                            ]
+                        ]);
+                        let text = "h265: best quality, but may not work on some devices.\nh264: worse quality, but works on many devices.\naudio: audio only\nvoice: voice message (OGG/Opus)\ncircle: round video note (square, up to 60 s)\nanimation: silent looping GIF (clips without sound are sent this way automatically)\nask every time: pick from the available variants for each link";
+                        bot.edit_message_text(message.chat.id, message.id, text).await?;
+                        bot.edit_message_reply_markup(message.chat.id, message.id).reply_markup(keyboard).await?;
+                    }
+                    "resolution_menu" => {
+                        let keyboard = InlineKeyboardMarkup::new(vec![
+                            RESOLUTION_OPTIONS
+                                .iter()
+                                .map(|(value, label)| InlineKeyboardButton::callback(*label, format!("set_resolution_{}", value)))
+                                .collect(),
+                            vec![
+                                InlineKeyboardButton::callback("Back", "back_to_settings"),
+                            ]
+                        ]);
+                        bot.edit_message_text(message.chat.id, message.id, RESOLUTION_MENU_TEXT).await?;
+                        bot.edit_message_reply_markup(message.chat.id, message.id).reply_markup(keyboard).await?;
                     }
-                    "enable_subscription" => {
-                        // Using database pool with timeout
-                        let result = db_pool.execute_with_timeout(|conn| {
-                            conn.execute(
-                                "UPDATE settings SET value = ?1 WHERE key = 'subscription_required'",
-                                params!["true"],
-                            )
-                        }).await;
+                    "audio_menu" => {
+                        let mut keyboard_rows: Vec<Vec<InlineKeyboardButton>> = AudioFormat::ALL
+                            .chunks(3)
+                            .map(|row| row
+                                .iter()
+                                .map(|format| InlineKeyboardButton::callback(format.label(), format!("set_audio_{}", format.preference())))
+                                .collect())
+                            .collect();
+                        keyboard_rows.push(vec![
+                            InlineKeyboardButton::callback("Back", "back_to_settings"),
+                        ]);
+                        let keyboard = InlineKeyboardMarkup::new(keyboard_rows);
+                        bot.edit_message_text(message.chat.id, message.id, AUDIO_MENU_TEXT).await?;
+                        bot.edit_message_reply_markup(message.chat.id, message.id).reply_markup(keyboard).await?;
+                    }
+                    "back_to_main" => {
+                        let keyboard = InlineKeyboardMarkup::new(vec![vec![ 
+                            InlineKeyboardButton::callback("Settings", "settings"),
+                        ]]);
+                        bot.edit_message_reply_markup(message.chat.id, message.id).reply_markup(keyboard).await?;
+                        bot.send_message(message.chat.id, "").reply_markup(get_main_reply_keyboard()).await?;
+                    }
+                    "back_to_settings" => {
+                        let keyboard = InlineKeyboardMarkup::new(vec![vec![ 
+                            InlineKeyboardButton::callback("Format", "format_menu"),
+                            InlineKeyboardButton::callback("Resolution", "resolution_menu"),
+                            InlineKeyboardButton::callback("Audio format", "audio_menu"),
+                        ],
+                        vec![ 
+                            InlineKeyboardButton::callback("Back", "back_to_main"),
+                        ]]);
+
+                        bot.edit_message_text(message.chat.id, message.id, "Settings").await?;
+                        bot.edit_message_reply_markup(message.chat.id, message.id).reply_markup(keyboard).await?;
+                    }
+                "toggle_subscription" => {
+                    // This arm is no longer needed as toggle logic is handled by enable/disable
+                    bot.answer_callback_query(q.id).text("Action not available.").await?;
+                }
+                "enable_subscription" => {
+                    // Using database pool with timeout
+                    let result = db_pool.execute_with_timeout(|conn| {
+                        conn.execute(
+                            "UPDATE settings SET value = ?1 WHERE key = 'subscription_required'",
+                            params!["true"],
+                        )
+                    }).await;
                         
-                        match result {
-                            Ok(_) => {
-                                // Update the environment variable asynchronously
-                                if let Err(e) = update_env_subscription_setting(true).await {
-                                    log::error!("Failed to update .env file: {}", e);
-                                }
-                                bot.answer_callback_query(q.id).text("Mandatory subscription enabled.").await?;
-                            },
-                            Err(e) => {
-                                log::error!("Database operation failed: {}", e);
-                                bot.answer_callback_query(q.id).text("Operation failed - please try again.").await?;
+                    match result {
+                        Ok(_) => {
+                            // Update the environment variable asynchronously
+                            if let Err(e) = update_env_subscription_setting(true).await {
+                                log::error!("Failed to update .env file: {}", e);
                             }
+                            bot.answer_callback_query(q.id).text("Mandatory subscription enabled.").await?;
+                        },
+                        Err(e) => {
+                            log::error!("Database operation failed: {}", e);
+                            bot.answer_callback_query(q.id).text("Operation failed - please try again.").await?;
                         }
+                    }
                         
-                        // Refresh the menu
-                        let subscription_required = db_pool.execute_with_timeout(|conn| {
-                            match conn.query_row(
-                                "SELECT value FROM settings WHERE key = 'subscription_required'",
-                                [],
-                                |row| Ok(row.get::<_, String>(0)? == "true")
-                            ) {
-                                Ok(value) => Ok(value),
-                                Err(_) => Ok(true) // Default to true
-                            }
-                        }).await.unwrap_or(true);
+                    // Refresh the menu
+                    let subscription_required = db_pool.execute_with_timeout(|conn| {
+                        match conn.query_row(
+                            "SELECT value FROM settings WHERE key = 'subscription_required'",
+                            [],
+                            |row| Ok(row.get::<_, String>(0)? == "true")
+                        ) {
+                            Ok(value) => Ok(value),
+                            Err(_) => Ok(true) // Default to true
+                        }
+                    }).await.unwrap_or(true);
 
-                        let toggle_button = if subscription_required {
-                            InlineKeyboardButton::callback("Disable Subscription", "disable_subscription")
-                        } else {
-                            InlineKeyboardButton::callback("Enable Subscription", "enable_subscription")
-                        };
+                    let toggle_button = if subscription_required {
+                        InlineKeyboardButton::callback("Disable Subscription", "disable_subscription")
+                    } else {
+                        InlineKeyboardButton::callback("Enable Subscription", "enable_subscription")
+                    };
 
-                        let keyboard = InlineKeyboardMarkup::new(vec![vec![toggle_button],
-                                                                    vec![InlineKeyboardButton::callback("Back", "back_to_settings")]]);
+                    let keyboard = InlineKeyboardMarkup::new(vec![vec![toggle_button],
+                                                                vec![InlineKeyboardButton::callback("Back", "back_to_settings")]]);
 
-                        bot.edit_message_text(message.chat.id, message.id, "Manage Subscription").await?;
-                        bot.edit_message_reply_markup(message.chat.id, message.id).reply_markup(keyboard).await?;
-                    }
-                    "disable_subscription" => {
-                        // Using database pool with timeout
-                        let result = db_pool.execute_with_timeout(|conn| {
-                            conn.execute(
-                                "UPDATE settings SET value = ?1 WHERE key = 'subscription_required'",
-                                params!["false"],
-                            )
-                        }).await;
+                    bot.edit_message_text(message.chat.id, message.id, "Manage Subscription").await?;
+                    bot.edit_message_reply_markup(message.chat.id, message.id).reply_markup(keyboard).await?;
+                }
+                "disable_subscription" => {
+                    // Using database pool with timeout
+                    let result = db_pool.execute_with_timeout(|conn| {
+                        conn.execute(
+                            "UPDATE settings SET value = ?1 WHERE key = 'subscription_required'",
+                            params!["false"],
+                        )
+                    }).await;
                         
-                        match result {
-                            Ok(_) => {
-                                // Update the environment variable asynchronously
-                                if let Err(e) = update_env_subscription_setting(false).await {
-                                    log::error!("Failed to update .env file: {}", e);
-                                }
-                                bot.answer_callback_query(q.id).text("Mandatory subscription disabled.").await?;
-                            },
-                            Err(e) => {
-                                log::error!("Database operation failed: {}", e);
-                                bot.answer_callback_query(q.id).text("Operation failed - please try again.").await?;
+                    match result {
+                        Ok(_) => {
+                            // Update the environment variable asynchronously
+                            if let Err(e) = update_env_subscription_setting(false).await {
+                                log::error!("Failed to update .env file: {}", e);
                             }
+                            bot.answer_callback_query(q.id).text("Mandatory subscription disabled.").await?;
+                        },
+                        Err(e) => {
+                            log::error!("Database operation failed: {}", e);
+                            bot.answer_callback_query(q.id).text("Operation failed - please try again.").await?;
                         }
+                    }
                         
-                        // Refresh the menu
-                        let subscription_required = db_pool.execute_with_timeout(|conn| {
-                            match conn.query_row(
-                                "SELECT value FROM settings WHERE key = 'subscription_required'",
-                                [],
-                                |row| Ok(row.get::<_, String>(0)? == "true")
-                            ) {
-                                Ok(value) => Ok(value),
-                                Err(_) => Ok(true) // Default to true
-                            }
-                        }).await.unwrap_or(true);
+                    // Refresh the menu
+                    let subscription_required = db_pool.execute_with_timeout(|conn| {
+                        match conn.query_row(
+                            "SELECT value FROM settings WHERE key = 'subscription_required'",
+                            [],
+                            |row| Ok(row.get::<_, String>(0)? == "true")
+                        ) {
+                            Ok(value) => Ok(value),
+                            Err(_) => Ok(true) // Default to true
+                        }
+                    }).await.unwrap_or(true);
 
-                        let toggle_button = if subscription_required {
-                            InlineKeyboardButton::callback("Disable Subscription", "disable_subscription")
-                        } else {
-                            InlineKeyboardButton::callback("Enable Subscription", "enable_subscription")
-                        };
+                    let toggle_button = if subscription_required {
+                        InlineKeyboardButton::callback("Disable Subscription", "disable_subscription")
+                    } else {
+                        InlineKeyboardButton::callback("Enable Subscription", "enable_subscription")
+                    };
 
-                        let keyboard = InlineKeyboardMarkup::new(vec![vec![toggle_button],
-                                                                    vec![InlineKeyboardButton::callback("Back", "back_to_settings")]]);
+                    let keyboard = InlineKeyboardMarkup::new(vec![vec![toggle_button],
+                                                                vec![InlineKeyboardButton::callback("Back", "back_to_settings")]]);
 
-                        bot.edit_message_text(message.chat.id, message.id, "Manage Subscription").await?;
-                        bot.edit_message_reply_markup(message.chat.id, message.id).reply_markup(keyboard).await?;
-                    }
-                    "subscription_menu" => {
-                        let subscription_required = db_pool.execute_with_timeout(|conn| {
-                            match conn.query_row(
-                                "SELECT value FROM settings WHERE key = 'subscription_required'",
-                                [],
-                                |row| Ok(row.get::<_, String>(0)? == "true")
-                            ) {
-                                Ok(value) => Ok(value),
-                                Err(_) => Ok(true) // Default to true
-                            }
-                        }).await.unwrap_or(true);
+                    bot.edit_message_text(message.chat.id, message.id, "Manage Subscription").await?;
+                    bot.edit_message_reply_markup(message.chat.id, message.id).reply_markup(keyboard).await?;
+                }
+                "subscription_menu" => {
+                    let subscription_required = db_pool.execute_with_timeout(|conn| {
+                        match conn.query_row(
+                            "SELECT value FROM settings WHERE key = 'subscription_required'",
+                            [],
+                            |row| Ok(row.get::<_, String>(0)? == "true")
+                        ) {
+                            Ok(value) => Ok(value),
+                            Err(_) => Ok(true) // Default to true
+                        }
+                    }).await.unwrap_or(true);
 
-                        let toggle_button = if subscription_required {
-                            InlineKeyboardButton::callback("Disable Subscription", "disable_subscription")
-                        } else {
-                            InlineKeyboardButton::callback("Enable Subscription", "enable_subscription")
-                        };
+                    let toggle_button = if subscription_required {
+                        InlineKeyboardButton::callback("Disable Subscription", "disable_subscription")
+                    } else {
+                        InlineKeyboardButton::callback("Enable Subscription", "enable_subscription")
+                    };
 
-                        let keyboard = InlineKeyboardMarkup::new(vec![vec![toggle_button],
-                                                                    vec![InlineKeyboardButton::callback("Back", "back_to_settings")]]);
+                    let keyboard = InlineKeyboardMarkup::new(vec![vec![toggle_button],
+                                                                vec![InlineKeyboardButton::callback("Back", "back_to_settings")]]);
 
-                        bot.edit_message_text(message.chat.id, message.id, "Manage Subscription").await?;
-                        bot.edit_message_reply_markup(message.chat.id, message.id).reply_markup(keyboard).await?;
-                    }
-                    _ => {}                    }
+                    bot.edit_message_text(message.chat.id, message.id, "Manage Subscription").await?;
+                    bot.edit_message_reply_markup(message.chat.id, message.id).reply_markup(keyboard).await?;
                 }
+                _ => {}                    }
             }
         }
     }
@@ -236,7 +312,7 @@ pub async fn update_env_subscription_setting(enable: bool) -> Result<(), anyhow:
         } else {
             new_content.push_str(line);
         }
-        new_content.push_str("\n");
+        new_content.push('\n');
     }
 
     if !found {
@@ -247,9 +323,11 @@ pub async fn update_env_subscription_setting(enable: bool) -> Result<(), anyhow:
     Ok(())
 }
 
-pub async fn settings_text_handler(bot: Bot, msg: Message) -> Result<(), anyhow::Error> {
+pub async fn settings_text_handler(bot: LimitedBot, msg: Message) -> Result<(), anyhow::Error> {
     let mut keyboard_rows = vec![vec![
         KeyboardButton::new("Format"),
+        KeyboardButton::new("Resolution"),
+        KeyboardButton::new("Audio format"),
     ]];
 
     if is_admin(&msg).await {
@@ -271,13 +349,21 @@ pub async fn settings_text_handler(bot: Bot, msg: Message) -> Result<(), anyhow:
     Ok(())
 }
 
-pub async fn format_text_handler(bot: Bot, msg: Message) -> Result<(), anyhow::Error> {
+pub async fn format_text_handler(bot: LimitedBot, msg: Message) -> Result<(), anyhow::Error> {
     let keyboard = teloxide::types::KeyboardMarkup::new(vec![
         vec![
             KeyboardButton::new("h265"),
             KeyboardButton::new("h264"),
             KeyboardButton::new("audio"),
         ],
+        vec![
+            KeyboardButton::new("voice"),
+            KeyboardButton::new("circle"),
+            KeyboardButton::new("animation"),
+        ],
+        vec![
+            KeyboardButton::new("ask every time"),
+        ],
         vec![
             KeyboardButton::new("Back"),
         ]
@@ -285,13 +371,113 @@ pub async fn format_text_handler(bot: Bot, msg: Message) -> Result<(), anyhow::E
     .resize_keyboard()
     .one_time_keyboard();
 
-    let text = "h265: best quality, but may not work on some devices.\nh264: worse quality, but works on many devices.\naudio: audio only";
+    let text = "h265: best quality, but may not work on some devices.\nh264: worse quality, but works on many devices.\naudio: audio only\nvoice: voice message (OGG/Opus)\ncircle: round video note (square, up to 60 s)\nanimation: silent looping GIF (clips without sound are sent this way automatically)\nask every time: pick from the available variants for each link";
     bot.send_message(msg.chat.id, text).reply_markup(keyboard).await?;
 
     Ok(())
 }
 
-pub async fn subscription_text_handler(bot: Bot, msg: Message, db_pool: Arc<DatabasePool>) -> Result<(), anyhow::Error> {
+pub async fn resolution_text_handler(bot: LimitedBot, msg: Message) -> Result<(), anyhow::Error> {
+    bot.send_message(msg.chat.id, RESOLUTION_MENU_TEXT).reply_markup(get_resolution_reply_keyboard()).await?;
+    Ok(())
+}
+
+/// Handles the reply keyboard buttons produced by `get_resolution_reply_keyboard`
+pub async fn set_resolution_text_handler(bot: LimitedBot, msg: Message, db_pool: Arc<DatabasePool>) -> Result<(), anyhow::Error> {
+    let Some(resolution) = msg.text().and_then(resolution_from_label) else {
+        return Ok(());
+    };
+
+    match set_user_resolution(&db_pool, msg.chat.id.0, resolution).await {
+        Ok(_) => {
+            bot.send_message(msg.chat.id, format!("Resolution set to {}.", resolution_label(resolution)))
+                .reply_markup(get_resolution_reply_keyboard())
+                .await?;
+        },
+        Err(e) => {
+            log::error!("Failed to update resolution preference to {}: {}", resolution, e);
+            bot.send_message(msg.chat.id, "Failed to update resolution preference.").await?;
+        }
+    }
+    Ok(())
+}
+
+/// Stored resolution value for a reply keyboard label, e.g. "720p" -> "720"
+pub fn resolution_from_label(label: &str) -> Option<&'static str> {
+    RESOLUTION_OPTIONS.iter().find(|(_, l)| *l == label).map(|(value, _)| *value)
+}
+
+fn resolution_label(resolution: &str) -> &str {
+    RESOLUTION_OPTIONS.iter().find(|(value, _)| *value == resolution).map(|(_, label)| *label).unwrap_or(resolution)
+}
+
+pub fn resolution_labels() -> impl Iterator<Item = &'static str> {
+    RESOLUTION_OPTIONS.iter().map(|(_, label)| *label)
+}
+
+async fn set_user_resolution(db_pool: &DatabasePool, user_id: i64, resolution: &str) -> Result<(), anyhow::Error> {
+    if !RESOLUTION_OPTIONS.iter().any(|(value, _)| *value == resolution) {
+        return Err(anyhow::anyhow!("Unknown resolution preference: {}", resolution));
+    }
+    let resolution = resolution.to_string();
+    db_pool.execute_with_timeout(move |conn| {
+        conn.execute(
+            "UPDATE users SET resolution_preference = ?1 WHERE telegram_id = ?2",
+            params![resolution, user_id],
+        )
+    }).await?;
+    // Invalidate the cache for this user to ensure the new setting is picked up immediately
+    db_pool.invalidate_user_quality_cache(user_id).await;
+    Ok(())
+}
+
+pub async fn audio_settings_text_handler(bot: LimitedBot, msg: Message) -> Result<(), anyhow::Error> {
+    bot.send_message(msg.chat.id, AUDIO_MENU_TEXT).reply_markup(get_audio_reply_keyboard()).await?;
+    Ok(())
+}
+
+/// Handles the reply keyboard buttons produced by `get_audio_reply_keyboard`
+pub async fn set_audio_format_text_handler(bot: LimitedBot, msg: Message, db_pool: Arc<DatabasePool>) -> Result<(), anyhow::Error> {
+    let Some(format) = msg.text().and_then(audio_format_from_label) else {
+        return Ok(());
+    };
+
+    match set_user_audio_format(&db_pool, msg.chat.id.0, &format.preference()).await {
+        Ok(_) => {
+            bot.send_message(msg.chat.id, format!("Audio format set to {}.", format.label()))
+                .reply_markup(get_audio_reply_keyboard())
+                .await?;
+        },
+        Err(e) => {
+            log::error!("Failed to update audio preference to {}: {}", format.preference(), e);
+            bot.send_message(msg.chat.id, "Failed to update audio preference.").await?;
+        }
+    }
+    Ok(())
+}
+
+/// Audio format for a reply keyboard label, e.g. "mp3 192k" -> `AudioFormat::Mp3(192)`
+pub fn audio_format_from_label(label: &str) -> Option<AudioFormat> {
+    AudioFormat::ALL.into_iter().find(|format| format.label() == label)
+}
+
+async fn set_user_audio_format(db_pool: &DatabasePool, user_id: i64, preference: &str) -> Result<AudioFormat, anyhow::Error> {
+    let Some(format) = AudioFormat::ALL.into_iter().find(|format| format.preference() == preference) else {
+        return Err(anyhow::anyhow!("Unknown audio preference: {}", preference));
+    };
+    let preference = preference.to_string();
+    db_pool.execute_with_timeout(move |conn| {
+        conn.execute(
+            "UPDATE users SET audio_preference = ?1 WHERE telegram_id = ?2",
+            params![preference, user_id],
+        )
+    }).await?;
+    // Invalidate the cache for this user to ensure the new setting is picked up immediately
+    db_pool.invalidate_user_quality_cache(user_id).await;
+    Ok(format)
+}
+
+pub async fn subscription_text_handler(bot: LimitedBot, msg: Message, db_pool: Arc<DatabasePool>) -> Result<(), anyhow::Error> {
     if !is_admin(&msg).await {
         bot.send_message(msg.chat.id, "This option is for admins only.").await?;
         return Ok(());
@@ -323,78 +509,49 @@ pub async fn subscription_text_handler(bot: Bot, msg: Message, db_pool: Arc<Data
     Ok(())
 }
 
-pub async fn back_text_handler(bot: Bot, msg: Message) -> Result<(), anyhow::Error> {
+pub async fn back_text_handler(bot: LimitedBot, msg: Message) -> Result<(), anyhow::Error> {
     bot.send_message(msg.chat.id, "Returning to main menu.").reply_markup(get_main_reply_keyboard()).await?;
     Ok(())
 }
 
-pub async fn set_quality_h265_text_handler(bot: Bot, msg: Message, db_pool: Arc<DatabasePool>) -> Result<(), anyhow::Error> {
-    let result = db_pool.execute_with_timeout(move |conn| {
-        conn.execute(
-            "UPDATE users SET quality_preference = ?1 WHERE telegram_id = ?2",
-            params!["h265", msg.chat.id.0],
-        )
-    }).await;
-
-    match result {
-        Ok(_) => {
-            // Invalidate the cache for this user to ensure the new quality setting is picked up immediately
-            db_pool.invalidate_user_quality_cache(msg.chat.id.0).await;
-            bot.send_message(msg.chat.id, "Quality set to h265.").reply_markup(get_format_reply_keyboard()).await?;
-        },
-        Err(e) => {
-            log::error!("Failed to update quality preference to h265: {}", e);
-            bot.send_message(msg.chat.id, "Failed to update quality preference.").await?;
-        }
-    }
-    Ok(())
-}
+/// Handles the format buttons of `get_format_reply_keyboard`
+pub async fn set_quality_text_handler(bot: LimitedBot, msg: Message, db_pool: Arc<DatabasePool>) -> Result<(), anyhow::Error> {
+    let Some(quality) = msg.text().and_then(quality_from_label) else {
+        return Ok(());
+    };
 
-pub async fn set_quality_h264_text_handler(bot: Bot, msg: Message, db_pool: Arc<DatabasePool>) -> Result<(), anyhow::Error> {
+    let user_id = msg.chat.id.0;
     let result = db_pool.execute_with_timeout(move |conn| {
         conn.execute(
             "UPDATE users SET quality_preference = ?1 WHERE telegram_id = ?2",
-            params!["h264", msg.chat.id.0],
+            params![quality, user_id],
         )
     }).await;
 
     match result {
         Ok(_) => {
             // Invalidate the cache for this user to ensure the new quality setting is picked up immediately
-            db_pool.invalidate_user_quality_cache(msg.chat.id.0).await;
-            bot.send_message(msg.chat.id, "Quality set to h264.").reply_markup(get_format_reply_keyboard()).await?;
+            db_pool.invalidate_user_quality_cache(user_id).await;
+            let text = match quality {
+                "ask" => "Format will be asked for every link.".to_string(),
+                _ => format!("Quality set to {}.", quality),
+            };
+            bot.send_message(msg.chat.id, text).reply_markup(get_format_reply_keyboard()).await?;
         },
         Err(e) => {
-            log::error!("Failed to update quality preference to h264: {}", e);
+            log::error!("Failed to update quality preference to {}: {}", quality, e);
             bot.send_message(msg.chat.id, "Failed to update quality preference.").await?;
         }
     }
     Ok(())
 }
 
-pub async fn set_quality_audio_text_handler(bot: Bot, msg: Message, db_pool: Arc<DatabasePool>) -> Result<(), anyhow::Error> {
-    let result = db_pool.execute_with_timeout(move |conn| {
-        conn.execute(
-            "UPDATE users SET quality_preference = ?1 WHERE telegram_id = ?2",
-            params!["audio", msg.chat.id.0],
-        )
-    }).await;
-
-    match result {
-        Ok(_) => {
-            // Invalidate the cache for this user to ensure the new quality setting is picked up immediately
-            db_pool.invalidate_user_quality_cache(msg.chat.id.0).await;
-            bot.send_message(msg.chat.id, "Quality set to audio.").reply_markup(get_format_reply_keyboard()).await?;
-        },
-        Err(e) => {
-            log::error!("Failed to update quality preference to audio: {}", e);
-            bot.send_message(msg.chat.id, "Failed to update quality preference.").await?;
-        }
-    }
-    Ok(())
+/// Stored quality value for a format keyboard label, e.g. "ask every time" -> "ask"
+pub fn quality_from_label(label: &str) -> Option<&'static str> {
+    QUALITY_OPTIONS.iter().find(|(_, l)| *l == label).map(|(value, _)| *value)
 }
 
-pub async fn enable_subscription_text_handler(bot: Bot, msg: Message, db_pool: Arc<DatabasePool>) -> Result<(), anyhow::Error> {
+pub async fn enable_subscription_text_handler(bot: LimitedBot, msg: Message, db_pool: Arc<DatabasePool>) -> Result<(), anyhow::Error> {
     let result = db_pool.execute_with_timeout(|conn| {
         conn.execute(
             "UPDATE settings SET value = ?1 WHERE key = 'subscription_required'",
@@ -417,7 +574,7 @@ pub async fn enable_subscription_text_handler(bot: Bot, msg: Message, db_pool: A
     Ok(())
 }
 
-pub async fn disable_subscription_text_handler(bot: Bot, msg: Message, db_pool: Arc<DatabasePool>) -> Result<(), anyhow::Error> {
+pub async fn disable_subscription_text_handler(bot: LimitedBot, msg: Message, db_pool: Arc<DatabasePool>) -> Result<(), anyhow::Error> {
     let result = db_pool.execute_with_timeout(|conn| {
         conn.execute(
             "UPDATE settings SET value = ?1 WHERE key = 'subscription_required'",
@@ -4,6 +4,8 @@ use teloxide::utils::command::BotCommands;
 
 use crate::commands::Command;
 use crate::database::DatabasePool;
+use crate::media::audio_format::AudioFormat;
+use crate::utils::limited_bot::LimitedBot;
 use std::sync::Arc;
 
 pub fn get_main_reply_keyboard() -> KeyboardMarkup {
@@ -21,6 +23,14 @@ pub fn get_format_reply_keyboard() -> KeyboardMarkup {
             KeyboardButton::new("h264"),
             KeyboardButton::new("audio"),
         ],
+        vec![
+            KeyboardButton::new("voice"),
+            KeyboardButton::new("circle"),
+            KeyboardButton::new("animation"),
+        ],
+        vec![
+            KeyboardButton::new("ask every time"),
+        ],
         vec![
             KeyboardButton::new("Back"),
         ]
@@ -29,6 +39,28 @@ pub fn get_format_reply_keyboard() -> KeyboardMarkup {
     .one_time_keyboard()
 }
 
+pub fn get_resolution_reply_keyboard() -> KeyboardMarkup {
+    KeyboardMarkup::new(vec![
+        crate::handlers::callback::resolution_labels().map(KeyboardButton::new).collect(),
+        vec![
+            KeyboardButton::new("Back"),
+        ]
+    ])
+    .resize_keyboard()
+    .one_time_keyboard()
+}
+
+pub fn get_audio_reply_keyboard() -> KeyboardMarkup {
+    let mut rows: Vec<Vec<KeyboardButton>> = AudioFormat::ALL
+        .chunks(3)
+        .map(|row| row.iter().map(|format| KeyboardButton::new(format.label())).collect())
+        .collect();
+    rows.push(vec![KeyboardButton::new("Back")]);
+    KeyboardMarkup::new(rows)
+        .resize_keyboard()
+        .one_time_keyboard()
+}
+
 pub fn get_subscription_reply_keyboard(subscription_required: bool) -> KeyboardMarkup {
     let toggle_button = if subscription_required {
         KeyboardButton::new("Disable Subscription")
@@ -42,7 +74,7 @@ pub fn get_subscription_reply_keyboard(subscription_required: bool) -> KeyboardM
         .one_time_keyboard()
 }
 
-pub async fn command_handler(bot: Bot, msg: Message, cmd: Command, db_pool: Arc<DatabasePool>) -> Result<(), anyhow::Error> {
+pub async fn command_handler(bot: LimitedBot, msg: Message, cmd: Command, db_pool: Arc<DatabasePool>) -> Result<(), anyhow::Error> {
     let user_id = msg.chat.id.0;
     let result = db_pool.execute_with_timeout(move |conn| {
         conn.execute("INSERT OR IGNORE INTO users (telegram_id) VALUES (?1)", [user_id])?;
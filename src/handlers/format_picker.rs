@@ -0,0 +1,165 @@
+use teloxide::prelude::*;
+use teloxide::types::{CallbackQuery, InlineKeyboardButton, InlineKeyboardMarkup};
+use std::sync::Arc;
+
+use crate::config::BotApiConfig;
+use crate::database::DatabasePool;
+use crate::handlers::link::process_link;
+use crate::mtproto_uploader::MTProtoUploader;
+use crate::utils::pending_choices::{ChoiceRefusal, PendingFormatChoice, PendingFormatChoices, PENDING_CHOICE_TTL};
+use crate::yt_dlp_interface::YoutubeFetcher;
+use crate::yt_dlp_interface::clip::ClipRange;
+use crate::yt_dlp_interface::probe::{check_limits, format_variants};
+use crate::yt_dlp_interface::toolchain::use_toolchain;
+use crate::utils::limited_bot::LimitedBot;
+
+/// Callback data prefix of the format picker buttons: `pick:<token>:<index>`
+pub const FORMAT_PICK_PREFIX: &str = "pick:";
+
+fn parse_pick_data(data: &str) -> Option<(&str, usize)> {
+    let (token, index) = data.strip_prefix(FORMAT_PICK_PREFIX)?.split_once(':')?;
+    Some((token, index.parse().ok()?))
+}
+
+/// Probes the link and replies with one button per available variant
+pub async fn offer_format_choice(
+    bot: &LimitedBot,
+    msg: &Message,
+    username: Option<String>,
+    url: &str,
+    clip: Option<ClipRange>,
+    fetcher: &YoutubeFetcher,
+    pending_choices: &PendingFormatChoices,
+) -> Result<(), anyhow::Error> {
+    let status = bot.send_message(msg.chat.id, "🔎 Looking up available formats...").await?;
+
+    let probe = {
+        let _toolchain = use_toolchain().await;
+        fetcher.probe_video(url).await
+    };
+    let probed = match probe {
+        Ok(probed) => Arc::new(probed),
+        Err(e) => {
+            log::error!("Failed to probe formats for {}: {}", url, e);
+            bot.edit_message_text(msg.chat.id, status.id, "❌ Could not read the available formats - please try again later")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    // Refused links don't get a menu: checked with the smallest variant (audio), the chosen one
+    // is checked again when it is downloaded
+    let info = &probed.info;
+    if let Err(violation) = check_limits(info, &fetcher.limits, "audio", None, clip) {
+        bot.edit_message_text(msg.chat.id, status.id, violation.to_string()).await?;
+        return Ok(());
+    }
+
+    let variants = format_variants(info);
+    let token = pending_choices.insert(PendingFormatChoice {
+        requester_id: msg.from.as_ref().map(|user| user.id.0),
+        username,
+        url: url.to_string(),
+        clip,
+        variants: variants.clone(),
+        probed: probed.clone(),
+        created_at: tokio::time::Instant::now(),
+    }).await;
+
+    let keyboard = InlineKeyboardMarkup::new(
+        variants
+            .iter()
+            .enumerate()
+            .map(|(index, variant)| vec![
+                InlineKeyboardButton::callback(variant.label.clone(), format!("{}{}:{}", FORMAT_PICK_PREFIX, token, index)),
+            ])
+            .collect::<Vec<_>>(),
+    );
+    let text = format!(
+        "Choose a format{}\n(expires in {} minutes)",
+        info.title.as_ref().map(|title| format!(" for:\n{}", title)).unwrap_or_default(),
+        PENDING_CHOICE_TTL.as_secs() / 60
+    );
+    bot.edit_message_text(msg.chat.id, status.id, text).reply_markup(keyboard).await?;
+
+    Ok(())
+}
+
+/// Starts the download once a format picker button is tapped
+#[allow(clippy::too_many_arguments)]
+pub async fn format_choice_callback_handler(
+    bot: LimitedBot,
+    q: CallbackQuery,
+    fetcher: Arc<YoutubeFetcher>,
+    mtproto_uploader: Arc<MTProtoUploader>,
+    db_pool: Arc<DatabasePool>,
+    upload_semaphore: Arc<tokio::sync::Semaphore>,
+    bot_api: Arc<BotApiConfig>,
+    pending_choices: Arc<PendingFormatChoices>,
+) -> Result<(), anyhow::Error> {
+    let Some((token, index)) = q.data.as_deref().and_then(parse_pick_data) else {
+        bot.answer_callback_query(q.id).await?;
+        return Ok(());
+    };
+    let Some(message) = q.message.as_ref().and_then(|m| m.regular_message()) else {
+        bot.answer_callback_query(q.id).await?;
+        return Ok(());
+    };
+
+    let choice = match pending_choices.take(token, q.from.id.0).await {
+        Ok(choice) => choice,
+        Err(ChoiceRefusal::NotRequester) => {
+            bot.answer_callback_query(q.id).text("Only the person who sent the link can pick the format").await?;
+            return Ok(());
+        }
+        Err(ChoiceRefusal::Expired) => {
+            bot.answer_callback_query(q.id.clone()).text("This choice has expired, please send the link again").await?;
+            bot.edit_message_text(message.chat.id, message.id, "⌛ Format choice expired - send the link again.").await?;
+            return Ok(());
+        }
+    };
+    let Some(variant) = choice.variants.get(index) else {
+        bot.answer_callback_query(q.id).text("Unknown option").await?;
+        return Ok(());
+    };
+
+    log::info!("User picked {} ({}) for {}", variant.label, variant.selector, choice.url);
+    bot.answer_callback_query(q.id.clone()).text(format!("Downloading {}", variant.label)).await?;
+    // Editing the text without a markup also removes the buttons
+    bot.edit_message_text(message.chat.id, message.id, format!("⬇️ {}", variant.label)).await?;
+
+    // Get upload permit to limit concurrent uploads - must stay in scope for the entire function
+    let _upload_permit = upload_semaphore
+        .acquire()
+        .await
+        .map_err(|e| anyhow::anyhow!("Semaphore error: {}", e))?;
+
+    // The picked variant already fixes the resolution
+    process_link(
+        &bot,
+        message.chat.id,
+        choice.username.clone(),
+        &choice.url,
+        &variant.selector,
+        None,
+        choice.clip,
+        Some(choice.probed.clone()),
+        &fetcher,
+        &mtproto_uploader,
+        &db_pool,
+        &bot_api,
+    ).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_pick_data() {
+        assert_eq!(parse_pick_data("pick:abc123:2"), Some(("abc123", 2)));
+        assert_eq!(parse_pick_data("pick:abc123"), None);
+        assert_eq!(parse_pick_data("pick:abc123:x"), None);
+        assert_eq!(parse_pick_data("set_quality_h264"), None);
+    }
+}
@@ -1,25 +1,97 @@
 use teloxide::prelude::*;
 
+use std::collections::HashMap;
 use std::fs;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
 use uuid::Uuid;
-use tokio::time::{Duration, timeout};
-use std::path::PathBuf;
+use tokio::sync::Mutex;
+use tokio::time::{Duration, Instant, timeout};
 use std::pin::Pin;
 use std::future::Future;
 
+use crate::config::{BotApiConfig, max_upload_size};
 use crate::database::DatabasePool;
 use crate::mtproto_uploader::MTProtoUploader;
+use crate::mtproto_uploader::audio_metadata::{AudioMetadata, prepare_audio_metadata, get_audio_duration};
+use crate::media::voice::{convert_to_voice, compute_waveform};
+use crate::media::video_note::convert_to_video_note;
+use crate::media::compress::compress_to_size;
+use crate::media::animation::{probe_streams, strip_audio};
+use crate::media::split::{split_media, part_label};
+use crate::media::audio_format::AudioFormat;
+use crate::auto_update::install::record_download_result;
+use crate::yt_dlp_interface::toolchain::use_toolchain;
 use crate::yt_dlp_interface::YoutubeFetcher;
-use crate::handlers::admin::is_admin;
+use crate::yt_dlp_interface::fetcher::max_height_from_preference;
+use crate::yt_dlp_interface::clip::{ClipRange, parse_clip_request};
+use crate::handlers::admin::{is_admin, notify_admins};
 use crate::handlers::subscription::check_subscription;
+use crate::handlers::format_picker::offer_format_choice;
+use crate::utils::pending_choices::PendingFormatChoices;
 use crate::utils::progress_bar::ProgressBar;
+use crate::utils::progress_sink::{LogProgress, ProgressSink};
+use crate::utils::limited_bot::LimitedBot;
+use crate::utils::temp_file::TempFile;
+use crate::utils::proxy::{is_ip_block_error, platform_of, proxies};
+use crate::yt_dlp_interface::probe::{LimitViolation, ProbedVideo};
+use crate::utils::retry::{RetryDecision, RetryPolicy, extract_flood_wait, retry_unless_flood_wait};
 use crate::utils::{task_manager::TaskManager};
-use crate::telegram_bot_api_uploader::{send_video_with_progress_botapi, send_audio_with_progress_botapi};
+use crate::telegram_bot_api_uploader::{send_video_with_progress_botapi, send_audio_with_progress_botapi, send_voice_with_progress_botapi, send_video_note_with_progress_botapi, send_animation_with_progress_botapi};
 
 const DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(300); // 5 minutes
 const UPLOAD_TIMEOUT: Duration = Duration::from_secs(600);   // 10 minutes
-const TELEGRAM_BOT_API_FILE_LIMIT: u64 = 48 * 1024 * 1024; // 48MB
+/// Three attempts, the deadline only keeps a hung retry chain from running forever
+const DOWNLOAD_RETRY: RetryPolicy = RetryPolicy::new(3).with_deadline(DOWNLOAD_TIMEOUT.saturating_mul(3));
+const UPLOAD_RETRY: RetryPolicy = RetryPolicy::new(3).with_deadline(UPLOAD_TIMEOUT.saturating_mul(3));
+
+/// Errors yt-dlp reports for videos that won't become downloadable by trying again
+fn classify_download_error(error: &anyhow::Error) -> RetryDecision {
+    if error.downcast_ref::<LimitViolation>().is_some() {
+        return RetryDecision::Fail;
+    }
+    let message = error.to_string();
+    let permanent = ["Sign in required", "Video unavailable", "Private video", "This video is age-restricted"];
+    if permanent.iter().any(|marker| message.contains(marker)) {
+        RetryDecision::Fail
+    } else {
+        retry_unless_flood_wait(error)
+    }
+}
+
+/// Longest title put into a caption, well under Telegram's 1024 character caption limit
+const MAX_CAPTION_TITLE_CHARS: usize = 200;
+
+/// The video title followed by `note` (part number, compression notice), `None` when both are missing
+fn media_caption(title: Option<&str>, note: Option<&str>) -> Option<String> {
+    let title = title.map(str::trim).filter(|title| !title.is_empty()).map(|title| {
+        if title.chars().count() > MAX_CAPTION_TITLE_CHARS {
+            format!("{}…", title.chars().take(MAX_CAPTION_TITLE_CHARS).collect::<String>().trim_end())
+        } else {
+            title.to_string()
+        }
+    });
+    match (title, note) {
+        (Some(title), Some(note)) => Some(format!("{}\n{}", title, note)),
+        (Some(title), None) => Some(title),
+        (None, note) => note.map(str::to_string),
+    }
+}
+
+/// Admins hear about a rejected cookie jar at most this often per platform
+const REJECTED_JAR_REPORT_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Whether a rejected jar of `platform` should be reported now, so a burst of failures is one message
+async fn should_report_rejected_jar(platform: &str) -> bool {
+    static LAST_REPORTED: OnceLock<Mutex<HashMap<String, Instant>>> = OnceLock::new();
+    let mut last_reported = LAST_REPORTED.get_or_init(Default::default).lock().await;
+    match last_reported.get(platform) {
+        Some(at) if at.elapsed() < REJECTED_JAR_REPORT_INTERVAL => false,
+        _ => {
+            last_reported.insert(platform.to_string(), Instant::now());
+            true
+        }
+    }
+}
 
 async fn get_subscription_required(db_pool: &DatabasePool) -> Result<bool, anyhow::Error> {
     let result = db_pool.execute_with_timeout(|conn| {
@@ -35,14 +107,17 @@ async fn get_subscription_required(db_pool: &DatabasePool) -> Result<bool, anyho
     Ok(result)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn link_handler(
-    bot: Bot,
+    bot: LimitedBot,
     msg: Message,
     fetcher: Arc<YoutubeFetcher>,
     mtproto_uploader: Arc<MTProtoUploader>,
     db_pool: Arc<DatabasePool>,
     _task_manager: Arc<tokio::sync::Mutex<TaskManager>>,
     upload_semaphore: Arc<tokio::sync::Semaphore>,
+    bot_api: Arc<BotApiConfig>,
+    pending_choices: Arc<PendingFormatChoices>,
 ) -> Result<(), anyhow::Error> {
     let user_id = msg.chat.id.0;
 
@@ -82,19 +157,19 @@ pub async fn link_handler(
             .await
             .unwrap_or_else(|_| "best".to_string());
 
-        let is_audio = quality_preference == "audio";
+        let max_height = max_height_from_preference(
+            &db_pool
+                .get_user_resolution(msg.chat.id.0)
+                .await
+                .unwrap_or_else(|_| "best".to_string()),
+        );
+
         log::info!(
-            "Quality preference: {}, is_audio: {}",
+            "Quality preference: {}, max height: {:?}",
             quality_preference,
-            is_audio
+            max_height
         );
 
-        // Get upload permit to limit concurrent uploads - must stay in scope for the entire function
-        let _upload_permit = upload_semaphore
-            .acquire()
-            .await
-            .map_err(|e| anyhow::anyhow!("Semaphore error: {}", e))?;
-
         let subscription_required = get_subscription_required(&db_pool).await.unwrap_or(true);
 
         if subscription_required {
@@ -106,134 +181,392 @@ pub async fn link_handler(
             }
         }
 
-        // Create a single ProgressBar instance to be used for the entire operation
-        let mut progress_bar = ProgressBar::new(bot.clone(), msg.chat.id);
-        progress_bar.start("🎬 Starting...").await?;
+        // An optional time range after the link (or a `t=` parameter) downloads only that part
+        let (url, clip) = match parse_clip_request(text) {
+            Ok(request) => request,
+            Err(e) => {
+                bot.send_message(msg.chat.id, format!("❌ {}. Use e.g. 1:20-2:05.", e)).await?;
+                return Ok(());
+            }
+        };
+
+        // "Ask every time": show the available variants and wait for a tap
+        if quality_preference == "ask" {
+            return offer_format_choice(&bot, &msg, username, url, clip, &fetcher, &pending_choices).await;
+        }
 
-        // Update the progress bar to show that download is starting
-        progress_bar
-            .update(5, Some("⬇️ Starting download..."))
+        // Get upload permit to limit concurrent uploads - must stay in scope for the entire function
+        let _upload_permit = upload_semaphore
+            .acquire()
+            .await
+            .map_err(|e| anyhow::anyhow!("Semaphore error: {}", e))?;
+
+        process_link(
+            &bot,
+            msg.chat.id,
+            username,
+            url,
+            &quality_preference,
+            max_height,
+            clip,
+            None,
+            &fetcher,
+            &mtproto_uploader,
+            &db_pool,
+            &bot_api,
+        ).await?;
+    } else {
+        bot.send_message(msg.chat.id, "Please send a valid TikTok link.")
             .await?;
+    }
 
-        // Manual retry loop for download
-        let mut retries = 0;
-        let download_result = loop {
-            let file_stem = format!("output/{}", Uuid::new_v4());
-            let download_future = fetcher.download_video_from_url(
-                text.to_string(),
-                &file_stem,
-                &quality_preference,
-                &mut progress_bar,
-            );
-
-            match timeout(DOWNLOAD_TIMEOUT, download_future).await {
-                Ok(Ok(path)) => break Ok(path),
-                Ok(Err(e)) => {
-                    retries += 1;
-                    if retries >= 3 {
-                        break Err(e);
-                    }
-                    let delay_ms = (1000 * 2_u64.pow(retries - 1)).min(30000);
-                    tokio::time::sleep(Duration::from_millis(delay_ms)).await;
-                }
-                Err(e) => { // timeout
-                    retries += 1;
-                    if retries >= 3 {
-                        break Err(anyhow::Error::new(e));
+    Ok(())
+}
+
+/// Downloads the link in the given format and sends the result, reporting failures to the chat
+#[allow(clippy::too_many_arguments)]
+pub async fn process_link(
+    bot: &LimitedBot,
+    chat_id: ChatId,
+    username: Option<String>,
+    url: &str,
+    quality_preference: &str,
+    max_height: Option<u32>,
+    clip: Option<ClipRange>,
+    probed: Option<Arc<ProbedVideo>>,
+    fetcher: &YoutubeFetcher,
+    mtproto_uploader: &MTProtoUploader,
+    db_pool: &DatabasePool,
+    bot_api: &BotApiConfig,
+) -> Result<(), anyhow::Error> {
+    let is_audio = quality_preference == "audio";
+    let audio_format = AudioFormat::from_preference(
+        &db_pool.get_user_audio_format(chat_id.0).await.unwrap_or_default(),
+    );
+
+    // Create a single ProgressBar instance to be used for the entire operation
+    let mut telegram_progress = ProgressBar::new(bot.clone(), chat_id);
+    let mut progress_bar: Box<dyn ProgressSink> = match telegram_progress.start("🎬 Starting...").await {
+        Ok(()) => Box::new(telegram_progress),
+        Err(e) => {
+            // Still deliver the result even if the status message can't be shown
+            log::warn!("Failed to send progress message to {}: {}", chat_id, e);
+            Box::new(LogProgress::new(format!("chat {}", chat_id)))
+        }
+    };
+
+    // Update the progress bar to show that download is starting
+    progress_bar
+        .update(5, Some("⬇️ Starting download..."))
+        .await?;
+
+    // yt-dlp and ffmpeg aren't replaced while they run; the guard is held per stage (per attempt
+    // while downloading), never across uploads or retry backoff, so a waiting update only holds
+    // new jobs back briefly
+    let mut retry = DOWNLOAD_RETRY.start();
+    let mut probed = probed;
+    let download_result = loop {
+        let file_stem = format!("output/{}", Uuid::new_v4());
+        let download_future = fetcher.download_video_from_url(
+            url.to_string(),
+            &file_stem,
+            quality_preference,
+            max_height,
+            audio_format,
+            clip,
+            probed.clone(),
+            progress_bar.as_mut(),
+        );
+
+        let toolchain = use_toolchain().await;
+        let attempt = timeout(DOWNLOAD_TIMEOUT, download_future).await;
+        drop(toolchain);
+        let error = match attempt {
+            Ok(Ok(path)) => break Ok(path),
+            Ok(Err(e)) => e,
+            Err(e) => anyhow::Error::new(e), // timeout
+        };
+        // A blocked IP is worth another attempt through the platform's next proxy
+        let decision = if is_ip_block_error(&error.to_string()) && proxies().rotate(url) {
+            // Format URLs can be tied to the blocked address, the next proxy probes afresh
+            probed = None;
+            RetryDecision::Retry
+        } else {
+            classify_download_error(&error)
+        };
+        if !retry.wait_before_retry(decision).await {
+            break Err(error);
+        }
+    };
+
+    // Fresh binary updates are judged on real downloads; a private, removed or IP-blocked video says nothing about them
+    match &download_result {
+        Ok(_) => record_download_result(true).await,
+        Err(e) if classify_download_error(e) != RetryDecision::Fail && !is_ip_block_error(&e.to_string()) => record_download_result(false).await,
+        Err(_) => {}
+    }
+
+    let (path, title) = match download_result {
+        Ok(media) => (media.path, media.title),
+        Err(e) => {
+            // This handles both timeout and retries failure
+            progress_bar.finish().await?;
+
+            // Analyze error type for more specific message
+            let error_message = if let Some(violation) = e.downcast_ref::<LimitViolation>() {
+                violation.to_string()
+            } else if e.to_string().contains("Sign in required") {
+                let platform = platform_of(url);
+                if fetcher.cookie_jars.jar_for(&platform).is_some() {
+                    log::warn!("{} still requires sign in with the stored {} cookies, they may have expired", url, platform);
+                    if should_report_rejected_jar(&platform).await {
+                        notify_admins(bot, &format!(
+                            "🍪 {} still asks to sign in with the stored cookies ({}), upload fresh ones with /setcookies {}",
+                            platform, url, platform
+                        )).await;
                     }
-                    let delay_ms = (1000 * 2_u64.pow(retries - 1)).min(30000);
-                    tokio::time::sleep(Duration::from_millis(delay_ms)).await;
                 }
-            }
-        };
+                "🔒 Video requires sign in - currently unavailable for download".to_string()
+            } else if e.to_string().contains("Video unavailable")
+                || e.to_string().contains("Requested format is not available")
+            {
+                "🚫 Video is unavailable or has been removed".to_string()
+            } else if e.to_string().contains("Private video") {
+                "🔒 Video is private and cannot be downloaded".to_string()
+            } else if e.to_string().contains("This video is age-restricted") {
+                "🔞 Video is age-restricted and cannot be downloaded".to_string()
+            } else if e.to_string().contains("Failed to parse") || e.to_string().contains("JSON")
+            {
+                "🔧 Error processing TikTok API response. Please try again later.".to_string()
+            } else if e.to_string().contains("timeout") {
+                "⏰ Download timeout - please try again".to_string()
+            } else {
+                format!(
+                    "❌ Failed to download video: {}",
+                    e.to_string().chars().take(100).collect::<String>()
+                )
+            };
 
-        let path = match download_result {
-            Ok(path) => path,
-            Err(e) => {
-                // This handles both timeout and retries failure
-                progress_bar.delete().await?;
-
-                // Analyze error type for more specific message
-                let error_message = if e.to_string().contains("Sign in required") {
-                    "🔒 Video requires sign in to TikTok - currently unavailable for download"
-                        .to_string()
-                } else if e.to_string().contains("Video unavailable")
-                    || e.to_string().contains("Requested format is not available")
-                {
-                    "🚫 Video is unavailable or has been removed".to_string()
-                } else if e.to_string().contains("Private video") {
-                    "🔒 Video is private and cannot be downloaded".to_string()
-                } else if e.to_string().contains("This video is age-restricted") {
-                    "🔞 Video is age-restricted and cannot be downloaded".to_string()
-                } else if e.to_string().contains("Failed to parse") || e.to_string().contains("JSON")
-                {
-                    "🔧 Error processing TikTok API response. Please try again later.".to_string()
-                } else if e.to_string().contains("timeout") {
-                    "⏰ Download timeout - please try again".to_string()
-                } else {
-                    format!(
-                        "❌ Failed to download video: {}",
-                        e.to_string().chars().take(100).collect::<String>()
-                    )
-                };
+            bot.send_message(chat_id, error_message).await?;
+            return Ok(());
+        }
+    };
 
-                bot.send_message(msg.chat.id, error_message).await?;
-                return Ok(());
+    // Create RAII wrapper for file cleanup
+    let _temp_file_guard = TempFile::new(path.clone());
+
+    log::info!(
+        "Downloaded file path: {:?}, is_audio: {}, file_size: {}",
+        path,
+        is_audio,
+        fs::metadata(&path)?.len()
+    );
+
+    // Tagging, conversion, compression and splitting all run ffmpeg
+    let toolchain = use_toolchain().await;
+
+    // Title, performer, duration and cover for audio; tags are also written into the file
+    let audio_metadata = if is_audio {
+        progress_bar.update(82, Some("🏷️ Tagging audio...")).await?;
+        prepare_audio_metadata(&mtproto_uploader.ffmpeg_path, &mtproto_uploader.ffprobe_path, &path).await
+    } else {
+        AudioMetadata::default()
+    };
+    let _cover_guard = audio_metadata.cover_path.clone().map(TempFile::new);
+
+    // Short clips without an audio track are sent as animations unless a special mode was picked
+    let output_mode = if matches!(quality_preference, "audio" | "voice" | "circle" | "animation") {
+        quality_preference
+    } else {
+        match probe_streams(&mtproto_uploader.ffprobe_path, &path).await {
+            Ok(streams) if streams.is_auto_animation() => {
+                log::info!("No audio stream in {:?} ({:.1}s), sending as animation", path, streams.duration);
+                "animation"
             }
-        };
+            Ok(_) => quality_preference,
+            Err(e) => {
+                log::warn!("Failed to check audio streams of {:?}: {}", path, e);
+                quality_preference
+            }
+        }
+    };
 
-        // Create RAII wrapper for file cleanup
-        let _temp_file_guard = TempFile::new(path.clone());
+    // Voice, circle and animation modes convert the download into the Telegram-specific format
+    let conversion = match output_mode {
+        "voice" => {
+            progress_bar.update(82, Some("🎙️ Converting to voice message...")).await?;
+            Some(convert_to_voice(&mtproto_uploader.ffmpeg_path, &path).await)
+        }
+        "circle" => {
+            progress_bar.update(82, Some("⭕ Converting to video note...")).await?;
+            Some(convert_to_video_note(&mtproto_uploader.ffmpeg_path, &path).await)
+        }
+        "animation" => {
+            progress_bar.update(82, Some("🎞️ Converting to animation...")).await?;
+            Some(strip_audio(&mtproto_uploader.ffmpeg_path, &mtproto_uploader.ffprobe_path, &path).await)
+        }
+        _ => None,
+    };
+    let (path, _converted_file_guard) = match conversion {
+        None => (path, None),
+        Some(Ok(converted_path)) => (converted_path.clone(), Some(TempFile::new(converted_path))),
+        Some(Err(e)) => {
+            log::error!("Failed to convert {:?} for {} mode: {}", path, output_mode, e);
+            progress_bar.finish().await?;
+            bot.send_message(chat_id, "❌ Failed to convert the media - please try another format")
+                .await?;
+            return Ok(());
+        }
+    };
 
-        log::info!(
-            "Downloaded file path: {:?}, is_audio: {}, file_size: {}",
-            path,
-            is_audio,
-            fs::metadata(&path)?.len()
-        );
+    // Duration and waveform shown on the voice message bubble
+    let (voice_duration, voice_waveform) = if output_mode == "voice" {
+        let duration = get_audio_duration(mtproto_uploader.ffprobe_path.to_string_lossy().as_ref(), &path)
+            .await
+            .unwrap_or(0.0);
+        let waveform = compute_waveform(&mtproto_uploader.ffmpeg_path, &path).await.map_err(|e| {
+            log::warn!("Failed to compute voice waveform for {:?}: {}", path, e);
+            e
+        }).ok();
+        (duration, waveform)
+    } else {
+        (0.0, None)
+    };
+
+    let file_size = fs::metadata(&path)?.len();
+
+    // Videos slightly over the Bot API limit are re-encoded instead of going through MTProto
+    let is_video = !matches!(output_mode, "audio" | "voice" | "circle");
+    let (path, file_size, compressed_file_guard) = if is_video && bot_api.should_compress(file_size) {
+        progress_bar.update(82, Some("🗜️ Compressing to fit the Telegram limit...")).await?;
+        match compress_to_size(&mtproto_uploader.ffmpeg_path, &mtproto_uploader.ffprobe_path, &path, bot_api.file_limit()).await {
+            Ok(compressed_path) => {
+                let compressed_size = fs::metadata(&compressed_path)?.len();
+                (compressed_path.clone(), compressed_size, Some(TempFile::new(compressed_path)))
+            }
+            Err(e) => {
+                log::warn!("Compression failed for {:?}, falling back to MTProto: {}", path, e);
+                (path, file_size, None)
+            }
+        }
+    } else {
+        (path, file_size, None)
+    };
+    let compressed_caption = compressed_file_guard
+        .is_some()
+        .then_some("🗜️ The video was compressed to fit the Telegram size limit.");
+
+    // Media over the configured maximum is split at keyframes and sent in order
+    let max_size = max_upload_size();
+    let (parts, _part_guards) = if (is_video || is_audio) && file_size > max_size {
+        progress_bar.update(83, Some("✂️ Splitting into parts...")).await?;
+        match split_media(&mtproto_uploader.ffmpeg_path, &mtproto_uploader.ffprobe_path, &path, max_size).await {
+            Ok(parts) => {
+                let guards: Vec<TempFile> = parts.iter().cloned().map(TempFile::new).collect();
+                (parts, guards)
+            }
+            Err(e) => {
+                log::error!("Failed to split {:?}: {}", path, e);
+                progress_bar.finish().await?;
+                bot.send_message(chat_id, "❌ The file is too large and could not be split into parts")
+                    .await?;
+                return Ok(());
+            }
+        }
+    } else {
+        (vec![path.clone()], Vec::new())
+    };
+    let total_parts = parts.len();
+    drop(toolchain);
 
-        let file_size = fs::metadata(&path)?.len();
+    for (index, part_path) in parts.iter().enumerate() {
+        let note = if total_parts > 1 {
+            progress_bar
+                .update(84, Some(&format!("📤 Sending part {}/{}...", index + 1, total_parts)))
+                .await?;
+            Some(part_label(index, total_parts))
+        } else {
+            compressed_caption.map(str::to_string)
+        };
+        let caption = media_caption(title.as_deref(), note.as_deref());
+        let file_size = fs::metadata(part_path)?.len();
+
+        // Each audio part carries its own duration
+        let audio_metadata = if is_audio && total_parts > 1 {
+            let _toolchain = use_toolchain().await;
+            AudioMetadata {
+                duration: get_audio_duration(mtproto_uploader.ffprobe_path.to_string_lossy().as_ref(), part_path)
+                    .await
+                    .unwrap_or(audio_metadata.duration),
+                ..audio_metadata.clone()
+            }
+        } else {
+            audio_metadata.clone()
+        };
 
-        if file_size > TELEGRAM_BOT_API_FILE_LIMIT {
+        let sent = if file_size > bot_api.file_limit() {
             // MTProto upload with timeout and retry
             progress_bar
                 .update(85, Some("📤 Starting upload..."))
                 .await?;
 
             // Use the new reconnect mechanism which includes retries internally
-            let upload_result = if is_audio {
-                mtproto_uploader.upload_audio(
-                    msg.chat.id.0,
+            let upload_result = match output_mode {
+                "audio" => mtproto_uploader.upload_audio(
+                    chat_id.0,
                     username.clone(),
-                    &path,
-                    "",
-                    &mut progress_bar,
-                ).await
-            } else {
-                mtproto_uploader.upload_video(
-                    msg.chat.id.0,
+                    part_path,
+                    caption.as_deref().unwrap_or(""),
+                    &audio_metadata,
+                    progress_bar.as_mut(),
+                ).await,
+                "voice" => mtproto_uploader.upload_voice(
+                    chat_id.0,
+                    username.clone(),
+                    part_path,
+                    caption.as_deref().unwrap_or(""),
+                    voice_duration,
+                    voice_waveform.clone(),
+                    progress_bar.as_mut(),
+                ).await,
+                "circle" => mtproto_uploader.upload_video_note(
+                    chat_id.0,
+                    username.clone(),
+                    part_path,
+                    progress_bar.as_mut(),
+                ).await,
+                "animation" => mtproto_uploader.upload_animation(
+                    chat_id.0,
                     username.clone(),
-                    &path,
-                    "",
-                    &mut progress_bar,
-                ).await
+                    part_path,
+                    caption.as_deref().unwrap_or(""),
+                    progress_bar.as_mut(),
+                ).await,
+                _ => mtproto_uploader.upload_video(
+                    chat_id.0,
+                    username.clone(),
+                    part_path,
+                    caption.as_deref().unwrap_or(""),
+                    progress_bar.as_mut(),
+                ).await,
             };
 
             match upload_result {
                 Ok(_) => {
                     progress_bar.update(100, Some("✅ Done!")).await?;
                     tokio::time::sleep(Duration::from_millis(500)).await; // Brief pause to show completion
-                    progress_bar.delete().await?;
+                    progress_bar.finish().await?;
                     log::info!(
                         "File uploaded successfully for chat {} (audio: {})",
-                        msg.chat.id.0,
+                        chat_id.0,
                         is_audio
                     );
+                    true
                 }
                 Err(e) => {
-                    progress_bar.delete().await?;
+                    progress_bar.finish().await?;
                     let error_msg =
-                        if let Some(wait_seconds) = crate::utils::retry::extract_flood_wait(&e.to_string()) {
+                        if let Some(wait_seconds) = extract_flood_wait(&e.to_string()) {
                             format!(
                                 "⏳ Rate limited. Please wait {} seconds and try again.",
                                 wait_seconds
@@ -241,51 +574,67 @@ pub async fn link_handler(
                         } else {
                             "❌ Upload failed - please try again later".to_string()
                         };
-                    bot.send_message(msg.chat.id, error_msg).await?;
+                    bot.send_message(chat_id, error_msg).await?;
+                    false
                 }
             }
         } else {
             // Regular upload via Bot API with timeout and retry
-            let mut retries = 0;
+            let mut retry = UPLOAD_RETRY.start();
             let send_result = loop {
                  let send_future: Pin<Box<dyn Future<Output = Result<(), anyhow::Error>> + Send>> = Box::pin(async {
-                    if is_audio {
-                        send_audio_with_progress_botapi(
-                            &bot.token(),
-                            msg.chat.id,
-                            &path,
-                            None,
-                            &mut progress_bar,
-                        ).await
-                    } else {
-                        send_video_with_progress_botapi(
-                            &bot.token(),
-                            msg.chat.id,
-                            &path,
-                            None,
-                            &mut progress_bar,
-                        ).await
+                    match output_mode {
+                        "audio" => send_audio_with_progress_botapi(
+                            bot_api,
+                            bot.token(),
+                            chat_id,
+                            part_path,
+                            caption.as_deref(),
+                            &audio_metadata,
+                            progress_bar.as_mut(),
+                        ).await,
+                        "voice" => send_voice_with_progress_botapi(
+                            bot_api,
+                            bot.token(),
+                            chat_id,
+                            part_path,
+                            caption.as_deref(),
+                            voice_duration,
+                            progress_bar.as_mut(),
+                        ).await,
+                        "circle" => send_video_note_with_progress_botapi(
+                            bot_api,
+                            bot.token(),
+                            chat_id,
+                            part_path,
+                            progress_bar.as_mut(),
+                        ).await,
+                        "animation" => send_animation_with_progress_botapi(
+                            bot_api,
+                            bot.token(),
+                            chat_id,
+                            part_path,
+                            caption.as_deref(),
+                            progress_bar.as_mut(),
+                        ).await,
+                        _ => send_video_with_progress_botapi(
+                            bot_api,
+                            bot.token(),
+                            chat_id,
+                            part_path,
+                            caption.as_deref(),
+                            progress_bar.as_mut(),
+                        ).await,
                     }
                 });
 
-                match timeout(UPLOAD_TIMEOUT, send_future).await {
+                let error = match timeout(UPLOAD_TIMEOUT, send_future).await {
                     Ok(Ok(val)) => break Ok(val),
-                    Ok(Err(e)) => {
-                        retries += 1;
-                        if retries >= 3 {
-                            break Err(e);
-                        }
-                        let delay_ms = (1000 * 2_u64.pow(retries - 1)).min(30000);
-                        tokio::time::sleep(Duration::from_millis(delay_ms)).await;
-                    }
-                    Err(e) => { // timeout
-                        retries += 1;
-                        if retries >= 3 {
-                            break Err(anyhow::Error::new(e));
-                        }
-                        let delay_ms = (1000 * 2_u64.pow(retries - 1)).min(30000);
-                        tokio::time::sleep(Duration::from_millis(delay_ms)).await;
-                    }
+                    Ok(Err(e)) => e,
+                    Err(e) => anyhow::Error::new(e), // timeout
+                };
+                if !retry.wait_before_retry(retry_unless_flood_wait(&error)).await {
+                    break Err(error);
                 }
             };
 
@@ -296,71 +645,48 @@ pub async fn link_handler(
                         is_audio
                     );
                     // Progress bar already handled by send functions
+                    true
                 }
                 Err(_e) => {
-                    progress_bar.delete().await?;
-                    bot.send_message(msg.chat.id, "❌ Send failed after retries")
+                    progress_bar.finish().await?;
+                    bot.send_message(chat_id, "❌ Send failed after retries")
                         .await?;
+                    false
                 }
             }
-        }
+        };
 
-        // Logging and cleanup
-        let user_id = msg.chat.id.0;
-        let video_url = text.to_string();
-        let result = db_pool
-            .execute_with_timeout(move |conn| {
-                // Update user activity first (to ensure the user exists in the database)
-                conn.execute(
-                    "INSERT OR IGNORE INTO users (telegram_id) VALUES (?1)",
-                    [user_id],
-                )?;
-                conn.execute(
-                    "UPDATE users SET last_active = CURRENT_TIMESTAMP WHERE telegram_id = ?1",
-                    [user_id],
-                )?;
-                conn.execute(
-                    "INSERT INTO downloads (user_telegram_id, video_url) VALUES (?1, ?2)",
-                    (user_id, video_url),
-                )?;
-                Ok(())
-            })
-            .await;
-
-        if let Err(_e) = result {
-            log::error!("Failed to log download: {}", _e);
+        // Later parts make no sense without the earlier ones
+        if !sent {
+            break;
         }
-    } else {
-        bot.send_message(msg.chat.id, "Please send a valid TikTok link.")
-            .await?;
     }
 
-    Ok(())
-}
-
-// RAII for automatic file cleanup
-struct TempFile {
-    path: PathBuf,
-}
+    // Logging and cleanup
+    let user_id = chat_id.0;
+    let video_url = url.to_string();
+    let result = db_pool
+        .execute_with_timeout(move |conn| {
+            // Update user activity first (to ensure the user exists in the database)
+            conn.execute(
+                "INSERT OR IGNORE INTO users (telegram_id) VALUES (?1)",
+                [user_id],
+            )?;
+            conn.execute(
+                "UPDATE users SET last_active = CURRENT_TIMESTAMP WHERE telegram_id = ?1",
+                [user_id],
+            )?;
+            conn.execute(
+                "INSERT INTO downloads (user_telegram_id, video_url) VALUES (?1, ?2)",
+                (user_id, video_url),
+            )?;
+            Ok(())
+        })
+        .await;
 
-impl TempFile {
-    fn new(path: PathBuf) -> Self {
-        Self { path }
+    if let Err(_e) = result {
+        log::error!("Failed to log download: {}", _e);
     }
-}
 
-impl Drop for TempFile {
-    fn drop(&mut self) {
-        // Use blocking operation in Drop for guaranteed cleanup
-        // Drop should not panic, so we handle errors
-        if std::thread::panicking() {
-            // If already panicking, skip cleanup to avoid double panic
-            log::warn!("Skipping temp file cleanup during panic: {}", self.path.display());
-            return;
-        }
-        match std::fs::remove_file(&self.path) {
-            Ok(_) => log::debug!("Successfully removed temp file: {}", self.path.display()),
-            Err(e) => log::warn!("Failed to cleanup temp file {}: {}", self.path.display(), e),
-        }
-    }
-}
\ No newline at end of file
+    Ok(())
+}
@@ -3,8 +3,10 @@ pub mod subscription;
 pub mod link;
 pub mod callback;
 pub mod command;
+pub mod format_picker;
 
 pub use link::link_handler;
-pub use callback::{callback_handler, settings_text_handler, format_text_handler, subscription_text_handler, back_text_handler, set_quality_h265_text_handler, set_quality_h264_text_handler, set_quality_audio_text_handler, enable_subscription_text_handler, disable_subscription_text_handler};
+pub use format_picker::format_choice_callback_handler;
+pub use callback::{callback_handler, settings_text_handler, format_text_handler, resolution_text_handler, set_resolution_text_handler, resolution_from_label, audio_settings_text_handler, set_audio_format_text_handler, audio_format_from_label, subscription_text_handler, back_text_handler, set_quality_text_handler, quality_from_label, enable_subscription_text_handler, disable_subscription_text_handler};
 pub use command::command_handler;
 pub use admin::admin_command_handler;
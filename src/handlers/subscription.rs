@@ -3,7 +3,9 @@ use teloxide::types::ChatMemberStatus;
 use std::env;
 use anyhow::Error;
 
-pub async fn check_subscription(bot: &Bot, user_id: i64) -> Result<bool, Error> {
+use crate::utils::limited_bot::LimitedBot;
+
+pub async fn check_subscription(bot: &LimitedBot, user_id: i64) -> Result<bool, Error> {
     let channel_ids_str = env::var("CHANNEL_IDS").unwrap_or_default();
     if channel_ids_str.is_empty() {
         return Ok(true);
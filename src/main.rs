@@ -6,11 +6,21 @@ use std::fs;
 
 use anyhow::Error;
 use crate::commands::Command;
+use crate::config::BotApiConfig;
 use crate::database::DatabasePool;
-use crate::handlers::{admin_command_handler, callback_handler, command_handler, link_handler, settings_text_handler, format_text_handler, subscription_text_handler, back_text_handler, set_quality_h265_text_handler, set_quality_h264_text_handler, set_quality_audio_text_handler, enable_subscription_text_handler, disable_subscription_text_handler};
+use crate::handlers::{admin_command_handler, callback_handler, format_choice_callback_handler, command_handler, link_handler, settings_text_handler, format_text_handler, resolution_text_handler, set_resolution_text_handler, resolution_from_label, audio_settings_text_handler, set_audio_format_text_handler, audio_format_from_label, subscription_text_handler, back_text_handler, set_quality_text_handler, quality_from_label, enable_subscription_text_handler, disable_subscription_text_handler};
 use crate::yt_dlp_interface::{YoutubeFetcher, is_executable_present, ensure_binaries};
+use crate::yt_dlp_interface::toolchain::{set_toolchain, Toolchain};
+use crate::yt_dlp_interface::urls::Platform;
 use crate::mtproto_uploader::MTProtoUploader;
 use crate::utils::task_manager::TaskManager;
+use crate::utils::pending_choices::{PendingFormatChoices, PENDING_CHOICE_TTL};
+use crate::handlers::format_picker::FORMAT_PICK_PREFIX;
+use crate::handlers::admin::notify_admins;
+use crate::utils::limited_bot::LimitedBot;
+use crate::utils::proxy::{proxies, with_http_proxy};
+use crate::yt_dlp_interface::cookies::CookieJars;
+use crate::config::{cookies_dir, DownloadLimits};
 use teloxide::dptree;
 
 #[cfg(not(target_os = "android"))]
@@ -28,6 +38,10 @@ mod utils;
 mod telegram_bot_api_uploader;
 pub mod peers;
 mod auto_update;
+mod media;
+
+const COOKIE_EXPIRY_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(24 * 3600);
+const COOKIE_EXPIRY_WARNING: std::time::Duration = std::time::Duration::from_secs(3 * 24 * 3600);
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
@@ -60,7 +74,7 @@ async fn main() -> Result<(), Error> {
 
     // 4. Setup file handle if needed
     let log_file = if file_level_config.is_some() {
-        let file = OpenOptions::new().create(true).write(true).append(true).open("bot_errors.log")?;
+        let file = OpenOptions::new().create(true).append(true).open("bot_errors.log")?;
         Some(Arc::new(Mutex::new(file)))
     } else {
         None
@@ -85,14 +99,12 @@ async fn main() -> Result<(), Error> {
             }
 
             // Write to file if level is sufficient
-            if let Some(file_level) = file_level_config {
-                if record.level() <= file_level {
-                    if let Some(file_handle) = &log_file {
-                        if let Ok(mut guard) = file_handle.lock() {
-                            let _ = writeln!(guard, "{}", formatted_record);
-                        }
-                    }
-                }
+            if let Some(file_level) = file_level_config
+                && record.level() <= file_level
+                && let Some(file_handle) = &log_file
+                && let Ok(mut guard) = file_handle.lock()
+            {
+                let _ = writeln!(guard, "{}", formatted_record);
             }
             Ok(())
         })
@@ -103,11 +115,12 @@ async fn main() -> Result<(), Error> {
 
     if let Err(e) = crate::config::load_environment() {
         log::error!("Failed to load environment: {}", e);
-        return Err(e.into());
+        return Err(e);
     }
 
     let exe_dir = std::env::current_exe()?.parent().ok_or_else(|| anyhow::anyhow!("Failed to get parent directory of executable"))?.to_path_buf();
     log::info!("Executable directory: {:?}", exe_dir);
+    log::info!("Platform for downloaded binaries: {}", Platform::current());
 
     // Dynamic directory for libraries (yt-dlp and ffmpeg)
     let libraries_dir = exe_dir.join("lib");
@@ -115,19 +128,29 @@ async fn main() -> Result<(), Error> {
     // Dynamic directory for output
     let output_dir = exe_dir.join("downloads");
 
+    // yt-dlp and ffmpeg can come from the system instead of the libraries directory
+    let toolchain = match Toolchain::resolve(&libraries_dir) {
+        Ok(toolchain) => toolchain,
+        Err(e) => {
+            log::error!("Failed to resolve yt-dlp/ffmpeg: {}", e);
+            return Err(e);
+        }
+    };
+    set_toolchain(toolchain.clone());
+
     // Ensure required binaries are present before starting the async runtime
-    if let Err(e) = ensure_binaries(&libraries_dir, &output_dir).await {
+    if let Err(e) = ensure_binaries(&toolchain, &libraries_dir, &output_dir).await {
         log::error!("Failed to ensure binaries: {}", e);
-        return Err(e.into());
+        return Err(e);
     }
 
     log::info!("Libraries directory: {:?}", libraries_dir.canonicalize()?);
     log::info!("Contents of libraries directory: {:?}", fs::read_dir(&libraries_dir)?.map(|e| e.unwrap().file_name()).collect::<Vec<_>>());
 
-    let yt_dlp_path = libraries_dir.join(if cfg!(target_os = "windows") { "yt-dlp.exe" } else { "yt-dlp" });
-    let ffmpeg_dir = libraries_dir.join("ffmpeg");
-    let ffmpeg_path = ffmpeg_dir.join(if cfg!(target_os = "windows") { "ffmpeg.exe" } else { "ffmpeg" });
-    let ffprobe_path = ffmpeg_dir.join(if cfg!(target_os = "windows") { "ffprobe.exe" } else { "ffprobe" });
+    let yt_dlp_path = toolchain.yt_dlp.clone();
+    let ffmpeg_dir = toolchain.ffmpeg_dir();
+    let ffmpeg_path = toolchain.ffmpeg.clone();
+    let ffprobe_path = toolchain.ffprobe.clone();
 
     if !is_executable_present(&yt_dlp_path) {
         log::error!("yt-dlp not found at {:?} after attempted download", yt_dlp_path);
@@ -146,31 +169,14 @@ async fn main() -> Result<(), Error> {
         return Err(anyhow::Error::msg("ffprobe not available"));
     }
 
-    // Настройка автообновления ПОСЛЕ ensure_binaries
-    let auto_updater = Arc::new(auto_update::AutoUpdater::new(libraries_dir.clone(), 30)); // Проверка каждые 30 минут
-    
-    // Первоначальная проверка обновлений
-    if let Err(e) = auto_updater.check_for_updates().await {
-        log::warn!("Initial update check failed: {}", e);
-    }
-
-    // Запускаем периодическую проверку в фоне
-    let updater_clone = Arc::clone(&auto_updater);
-    tokio::spawn(async move {
-        if let Err(e) = updater_clone.start_periodic_checks().await {
-            log::error!("Periodic update checker failed: {}", e);
-        }
-    });
-
-    log::info!("Auto-update functionality initialized");
-
     if let Err(e) = database::init_database() {
         log::error!("Failed to initialize the database: {}", e);
         return Err(e.into());
     }
     log::info!("Database initialized successfully.");
 
-    let fetcher = Arc::new(YoutubeFetcher::new(yt_dlp_path, output_dir.clone(), ffmpeg_dir.clone())?);
+    let cookie_jars = CookieJars::new(cookies_dir(&exe_dir));
+    let fetcher = Arc::new(YoutubeFetcher::new(yt_dlp_path, output_dir.clone(), ffmpeg_dir.clone(), cookie_jars.clone(), DownloadLimits::from_env())?);
     let bot_token = env::var("TELOXIDE_TOKEN").expect("TELOXIDE_TOKEN must be set");
     let mtproto_uploader = match MTProtoUploader::new(&bot_token, ffprobe_path.clone(), ffmpeg_path.clone()).await {
         Ok(uploader) => Arc::new(uploader),
@@ -189,33 +195,94 @@ async fn main() -> Result<(), Error> {
     let task_manager = Arc::new(tokio::sync::Mutex::new(TaskManager::new(2))); // For progress tasks
     let upload_semaphore = Arc::new(tokio::sync::Semaphore::new(2)); // Maximum 2 simultaneous uploads
 
-    let bot = Bot::from_env();
+    let bot_api = Arc::new(BotApiConfig::from_env());
+    let pending_choices = Arc::new(PendingFormatChoices::new(PENDING_CHOICE_TTL));
+    // HTTP_PROXY_URL also carries the Bot API traffic
+    let mut bot = if proxies().http.is_some() {
+        Bot::from_env_with_client(with_http_proxy(teloxide::net::default_reqwest_settings())?.build()?)
+    } else {
+        Bot::from_env()
+    };
+    if bot_api.is_custom() {
+        bot = bot.set_api_url(reqwest::Url::parse(&bot_api.base_url)?);
+        log::info!("Using custom Bot API server at {} (local mode: {})", bot_api.base_url, bot_api.local_mode);
+    }
+    // Handlers only get this handle, so every outgoing request goes through the rate limiter
+    let limited_bot = LimitedBot::new(bot.clone());
+
+    // Настройка автообновления ПОСЛЕ ensure_binaries
+    let mut auto_updater = auto_update::AutoUpdater::new(&toolchain, libraries_dir.clone(), 30); // Проверка каждые 30 минут
+    if crate::config::notify_admins_on_update() {
+        let notify_bot = limited_bot.clone();
+        auto_updater = auto_updater.with_notifier(Box::new(move |text| {
+            let bot = notify_bot.clone();
+            Box::pin(async move {
+                notify_admins(&bot, &text).await;
+            })
+        }));
+    }
+    let auto_updater = Arc::new(auto_updater);
+    
+    // Первоначальная проверка обновлений
+    if let Err(e) = auto_updater.check_for_updates().await {
+        log::warn!("Initial update check failed: {}", e);
+    }
+
+    // Запускаем периодическую проверку в фоне
+    let updater_clone = Arc::clone(&auto_updater);
+    tokio::spawn(async move {
+        if let Err(e) = updater_clone.start_periodic_checks().await {
+            log::error!("Periodic update checker failed: {}", e);
+        }
+    });
+
+    log::info!("Auto-update functionality initialized");
+
+    // Remind the admins a few days before uploaded cookies run out
+    let cookies_bot = limited_bot.clone();
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(COOKIE_EXPIRY_CHECK_INTERVAL);
+        loop {
+            interval.tick().await;
+            for warning in cookie_jars.expiry_warnings(COOKIE_EXPIRY_WARNING.as_secs() as i64).await {
+                log::warn!("{}", warning);
+                notify_admins(&cookies_bot, &warning).await;
+            }
+        }
+    });
 
     let handler = dptree::entry()
         .branch(Update::filter_message()
             .filter_async(|msg: Message| async move {
-                msg.text().map_or(false, |text| text.starts_with("/addchannel") || text.starts_with("/delchannel") || text.starts_with("/listchannels"))
+                // /setcookies arrives as the caption of the uploaded file
+                msg.text().or(msg.caption()).is_some_and(|text| text.starts_with("/addchannel") || text.starts_with("/delchannel") || text.starts_with("/listchannels") || text.starts_with("/versions") || text.starts_with("/update") || text.starts_with("/setcookies"))
             })
             .endpoint(admin_command_handler)
         )
         .branch(Update::filter_message().filter_command::<Command>().endpoint(command_handler))
         .branch(Update::filter_message().filter(|msg: Message| msg.text() == Some("⚙️ Settings")).endpoint(settings_text_handler))
         .branch(Update::filter_message().filter(|msg: Message| msg.text() == Some("Format")).endpoint(format_text_handler))
+        .branch(Update::filter_message().filter(|msg: Message| msg.text() == Some("Resolution")).endpoint(resolution_text_handler))
+        .branch(Update::filter_message().filter(|msg: Message| msg.text().and_then(resolution_from_label).is_some()).endpoint(set_resolution_text_handler))
+        .branch(Update::filter_message().filter(|msg: Message| msg.text() == Some("Audio format")).endpoint(audio_settings_text_handler))
+        .branch(Update::filter_message().filter(|msg: Message| msg.text().and_then(audio_format_from_label).is_some()).endpoint(set_audio_format_text_handler))
         .branch(Update::filter_message().filter(|msg: Message| msg.text() == Some("Subscription")).endpoint(subscription_text_handler))
-        .branch(Update::filter_message().filter(|msg: Message| msg.text() == Some("h265")).endpoint(set_quality_h265_text_handler))
-        .branch(Update::filter_message().filter(|msg: Message| msg.text() == Some("h264")).endpoint(set_quality_h264_text_handler))
-        .branch(Update::filter_message().filter(|msg: Message| msg.text() == Some("audio")).endpoint(set_quality_audio_text_handler))
+        .branch(Update::filter_message().filter(|msg: Message| msg.text().and_then(quality_from_label).is_some()).endpoint(set_quality_text_handler))
         .branch(Update::filter_message().filter(|msg: Message| msg.text() == Some("Enable Subscription")).endpoint(enable_subscription_text_handler))
         .branch(Update::filter_message().filter(|msg: Message| msg.text() == Some("Disable Subscription")).endpoint(disable_subscription_text_handler))
         .branch(Update::filter_message().filter(|msg: Message| msg.text() == Some("Back")).endpoint(back_text_handler))
         .branch(Update::filter_message().endpoint(link_handler))
+        .branch(Update::filter_callback_query()
+            .filter(|q: CallbackQuery| q.data.as_deref().is_some_and(|data| data.starts_with(FORMAT_PICK_PREFIX)))
+            .endpoint(format_choice_callback_handler)
+        )
         .branch(Update::filter_callback_query().endpoint(callback_handler));
 
     log::info!("Bot initialization completed in {:.2?}", start_time.elapsed());
     log::info!("Starting to dispatch updates...");
 
     let mut dispatcher = Dispatcher::builder(bot, handler)
-        .dependencies(dptree::deps![fetcher, mtproto_uploader, db_pool, task_manager.clone(), upload_semaphore])
+        .dependencies(dptree::deps![limited_bot, fetcher, mtproto_uploader, db_pool, task_manager.clone(), upload_semaphore, bot_api, pending_choices, auto_updater])
         .enable_ctrlc_handler()
         .build();
 
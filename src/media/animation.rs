@@ -0,0 +1,135 @@
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+use anyhow::Result;
+use serde::Deserialize;
+
+/// Silent clips up to this long are sent as animations without the user asking for it
+pub const AUTO_ANIMATION_MAX_DURATION_SECS: f64 = 60.0;
+
+/// What ffprobe reports about the streams of a download
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MediaStreams {
+    pub has_audio: bool,
+    /// Codec of the first video stream, e.g. `h264` or `hevc`
+    pub video_codec: Option<String>,
+    /// Seconds, 0 when unknown
+    pub duration: f64,
+}
+
+impl MediaStreams {
+    /// Short silent loops go out as animations, longer silent videos stay videos
+    pub fn is_auto_animation(&self) -> bool {
+        !self.has_audio
+            && self.video_codec.is_some()
+            && self.duration > 0.0
+            && self.duration <= AUTO_ANIMATION_MAX_DURATION_SECS
+    }
+}
+
+/// Reads the stream codecs and duration of the file with ffprobe
+pub async fn probe_streams(ffprobe_path: &Path, input_path: &Path) -> Result<MediaStreams> {
+    #[derive(Deserialize)]
+    struct Probe {
+        #[serde(default)]
+        streams: Vec<Stream>,
+        format: Option<Format>,
+    }
+    #[derive(Deserialize)]
+    struct Stream {
+        codec_type: Option<String>,
+        codec_name: Option<String>,
+    }
+    #[derive(Deserialize)]
+    struct Format {
+        duration: Option<String>,
+    }
+
+    let output = Command::new(ffprobe_path)
+        .arg("-v")
+        .arg("error")
+        .arg("-show_entries")
+        .arg("stream=codec_type,codec_name:format=duration")
+        .arg("-of")
+        .arg("json")
+        .arg(input_path)
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        log::error!("ffprobe stream check failed: {}", stderr);
+        return Err(anyhow::anyhow!("ffprobe stream check failed: {}", stderr));
+    }
+
+    let probe: Probe = serde_json::from_slice(&output.stdout)?;
+    let first_of = |kind: &str| probe.streams.iter().find(|s| s.codec_type.as_deref() == Some(kind));
+    Ok(MediaStreams {
+        has_audio: first_of("audio").is_some(),
+        video_codec: first_of("video").and_then(|s| s.codec_name.clone()),
+        duration: probe.format.and_then(|f| f.duration).and_then(|d| d.parse().ok()).unwrap_or(0.0),
+    })
+}
+
+/// Writes the video stream without audio into `<stem>.animation.mp4` for sending as an animation.
+///
+/// H.264 is copied as is; other codecs (HEVC from the h265 preference) are re-encoded, since many
+/// Telegram clients won't autoplay anything else as a GIF.
+pub async fn strip_audio(ffmpeg_path: &Path, ffprobe_path: &Path, input_path: &Path) -> Result<PathBuf> {
+    let output_path = input_path.with_extension("animation.mp4");
+    let video_codec = match probe_streams(ffprobe_path, input_path).await {
+        Ok(streams) => streams.video_codec,
+        Err(e) => {
+            log::warn!("Failed to read the video codec of {:?}, re-encoding: {}", input_path, e);
+            None
+        }
+    };
+
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.arg("-y")
+        .arg("-i")
+        .arg(input_path)
+        .arg("-an");
+    if video_codec.as_deref() == Some("h264") {
+        cmd.arg("-c:v").arg("copy");
+    } else {
+        cmd.arg("-c:v")
+            .arg("libx264")
+            .arg("-preset")
+            .arg("veryfast")
+            .arg("-crf")
+            .arg("23")
+            .arg("-pix_fmt")
+            .arg("yuv420p");
+    }
+    let output = cmd
+        .arg("-movflags")
+        .arg("+faststart")
+        .arg(&output_path)
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        log::error!("ffmpeg audio stripping failed: {}", stderr);
+        let _ = tokio::fs::remove_file(&output_path).await;
+        return Err(anyhow::anyhow!("ffmpeg audio stripping failed: {}", stderr));
+    }
+
+    Ok(output_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_short_silent_clips_become_animations() {
+        let clip = MediaStreams { has_audio: false, video_codec: Some("hevc".to_string()), duration: 12.5 };
+        assert!(clip.is_auto_animation());
+        assert!(!MediaStreams { has_audio: true, ..clip.clone() }.is_auto_animation());
+        assert!(!MediaStreams { duration: 20.0 * 60.0, ..clip.clone() }.is_auto_animation());
+        // Unknown length or no picture at all keep the user's mode
+        assert!(!MediaStreams { duration: 0.0, ..clip.clone() }.is_auto_animation());
+        assert!(!MediaStreams { video_codec: None, ..clip }.is_auto_animation());
+    }
+}
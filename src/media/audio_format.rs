@@ -0,0 +1,126 @@
+use std::path::Path;
+
+/// Output format of the "audio" mode, stored in `users.audio_preference`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AudioFormat {
+    /// MP3 at a constant bitrate in kbit/s
+    Mp3(u32),
+    /// AAC copied from the source into an m4a container without re-encoding
+    M4a,
+    Opus,
+    Flac,
+}
+
+impl AudioFormat {
+    pub const ALL: [AudioFormat; 6] = [
+        AudioFormat::Mp3(128),
+        AudioFormat::Mp3(192),
+        AudioFormat::Mp3(320),
+        AudioFormat::M4a,
+        AudioFormat::Opus,
+        AudioFormat::Flac,
+    ];
+
+    /// Parses a stored preference, unknown values fall back to the default
+    pub fn from_preference(value: &str) -> Self {
+        Self::ALL
+            .into_iter()
+            .find(|format| format.preference() == value)
+            .unwrap_or_default()
+    }
+
+    /// Value stored in the database and used in callback data
+    pub fn preference(&self) -> String {
+        match self {
+            AudioFormat::Mp3(bitrate) => format!("mp3_{}", bitrate),
+            AudioFormat::M4a => "m4a".to_string(),
+            AudioFormat::Opus => "opus".to_string(),
+            AudioFormat::Flac => "flac".to_string(),
+        }
+    }
+
+    /// Button label, e.g. `mp3 192k`
+    pub fn label(&self) -> String {
+        match self {
+            AudioFormat::Mp3(bitrate) => format!("mp3 {}k", bitrate),
+            AudioFormat::M4a => "m4a (original)".to_string(),
+            AudioFormat::Opus => "opus".to_string(),
+            AudioFormat::Flac => "flac".to_string(),
+        }
+    }
+
+    /// yt-dlp `--format` and audio extraction arguments
+    pub fn yt_dlp_args(&self) -> Vec<String> {
+        let (format, audio_format, quality) = match self {
+            AudioFormat::Mp3(bitrate) => ("bestaudio/best[ext=mp4]/best", "mp3", Some(format!("{}K", bitrate))),
+            // yt-dlp copies the stream when the source is already AAC
+            AudioFormat::M4a => ("bestaudio[ext=m4a]/bestaudio[acodec^=mp4a]/best[ext=mp4]/best", "m4a", None),
+            AudioFormat::Opus => ("bestaudio/best[ext=mp4]/best", "opus", None),
+            AudioFormat::Flac => ("bestaudio/best[ext=mp4]/best", "flac", None),
+        };
+
+        let mut args = vec![
+            "--extract-audio".to_string(),
+            "--audio-format".to_string(),
+            audio_format.to_string(),
+        ];
+        if let Some(quality) = quality {
+            args.push("--audio-quality".to_string());
+            args.push(quality);
+        }
+        args.push("--format".to_string());
+        args.push(format.to_string());
+        args
+    }
+}
+
+impl Default for AudioFormat {
+    fn default() -> Self {
+        AudioFormat::Mp3(320)
+    }
+}
+
+/// MIME type for an audio file, used by both the MTProto and the Bot API uploaders
+pub fn audio_mime_type(file_path: &Path) -> &'static str {
+    let ext = file_path.extension().and_then(|s| s.to_str()).unwrap_or_default().to_lowercase();
+    match ext.as_str() {
+        "mp3" => "audio/mpeg",
+        "m4a" => "audio/mp4",
+        "aac" => "audio/aac",
+        "ogg" | "oga" | "opus" => "audio/ogg",
+        "flac" => "audio/flac",
+        "wav" => "audio/wav",
+        _ => "audio/mpeg",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preference_round_trip() {
+        for format in AudioFormat::ALL {
+            assert_eq!(AudioFormat::from_preference(&format.preference()), format);
+        }
+        assert_eq!(AudioFormat::from_preference("unknown"), AudioFormat::Mp3(320));
+    }
+
+    #[test]
+    fn test_yt_dlp_args() {
+        let mp3 = AudioFormat::Mp3(192).yt_dlp_args();
+        assert_eq!(&mp3[..5], &["--extract-audio", "--audio-format", "mp3", "--audio-quality", "192K"]);
+
+        let m4a = AudioFormat::M4a.yt_dlp_args();
+        assert!(!m4a.contains(&"--audio-quality".to_string()));
+        assert_eq!(m4a[2], "m4a");
+    }
+
+    #[test]
+    fn test_audio_mime_type() {
+        assert_eq!(audio_mime_type(Path::new("a.mp3")), "audio/mpeg");
+        assert_eq!(audio_mime_type(Path::new("a.M4A")), "audio/mp4");
+        assert_eq!(audio_mime_type(Path::new("a.opus")), "audio/ogg");
+        assert_eq!(audio_mime_type(Path::new("a.flac")), "audio/flac");
+    }
+}
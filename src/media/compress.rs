@@ -0,0 +1,166 @@
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+use anyhow::{anyhow, Result};
+
+use crate::mtproto_uploader::metadata::get_video_metadata;
+
+/// Audio bitrate kept in compressed videos
+const AUDIO_BITRATE: u64 = 96_000;
+/// Share of the target size given to the encoder, the rest covers container overhead and overshoot
+const SIZE_SAFETY_MARGIN: f64 = 0.95;
+/// Downscale steps as (shorter side, minimum video bitrate that still looks acceptable at it)
+const DOWNSCALE_STEPS: &[(u32, u64)] = &[
+    (1080, 2_500_000),
+    (720, 1_200_000),
+    (540, 700_000),
+    (480, 450_000),
+    (360, 0),
+];
+/// Encoding attempts before giving up when the result is still too large
+const MAX_ATTEMPTS: u32 = 2;
+
+/// Video bitrate (bits/s) that makes a video of `duration` seconds fit into `target_size` bytes
+pub fn target_video_bitrate(target_size: u64, duration: f64) -> Option<u64> {
+    if duration <= 0.0 {
+        return None;
+    }
+    let total_bitrate = target_size as f64 * 8.0 * SIZE_SAFETY_MARGIN / duration;
+    let video_bitrate = total_bitrate - AUDIO_BITRATE as f64;
+    (video_bitrate > 0.0).then_some(video_bitrate as u64)
+}
+
+/// Shorter side the video should be scaled to for the given bitrate, `None` keeps the source size
+pub fn downscale_target(source_short_side: u32, video_bitrate: u64) -> Option<u32> {
+    let short_side = DOWNSCALE_STEPS
+        .iter()
+        .find(|(_, min_bitrate)| video_bitrate >= *min_bitrate)
+        .map(|(side, _)| (*side).min(source_short_side))
+        .unwrap_or(source_short_side);
+    (short_side < source_short_side).then_some(short_side)
+}
+
+/// Re-encodes a video with two-pass H.264 so it fits into `target_size` bytes.
+///
+/// The bitrate is derived from the ffprobe duration and the picture is downscaled
+/// when that bitrate is too low for the source resolution. Returns the path of the
+/// new `<stem>.compressed.mp4`, the caller is responsible for deleting it.
+pub async fn compress_to_size(ffmpeg_path: &Path, ffprobe_path: &Path, input_path: &Path, target_size: u64) -> Result<PathBuf> {
+    let metadata = get_video_metadata(ffprobe_path.to_string_lossy().as_ref(), input_path)
+        .await
+        .map_err(|e| anyhow!("Failed to probe video for compression: {}", e))?;
+    let mut video_bitrate = target_video_bitrate(target_size, metadata.duration)
+        .ok_or_else(|| anyhow!("Video is too long to fit into {} bytes", target_size))?;
+    let output_path = input_path.with_extension("compressed.mp4");
+
+    for attempt in 1..=MAX_ATTEMPTS {
+        let short_side = downscale_target(metadata.width.min(metadata.height), video_bitrate);
+        log::info!(
+            "Compressing {:?} (attempt {}): video bitrate {} b/s, scale to {:?}",
+            input_path, attempt, video_bitrate, short_side
+        );
+
+        let result = encode_two_pass(ffmpeg_path, input_path, &output_path, video_bitrate, short_side).await;
+        if let Err(e) = result {
+            let _ = tokio::fs::remove_file(&output_path).await;
+            return Err(e);
+        }
+
+        let size = tokio::fs::metadata(&output_path).await?.len();
+        if size <= target_size {
+            log::info!("Compressed {:?} to {} bytes", input_path, size);
+            return Ok(output_path);
+        }
+
+        // Encoder overshot: scale the bitrate down by the overshoot and try again
+        log::warn!("Compressed file is still {} bytes (target {}), retrying", size, target_size);
+        video_bitrate = (video_bitrate as f64 * target_size as f64 / size as f64 * SIZE_SAFETY_MARGIN) as u64;
+    }
+
+    let _ = tokio::fs::remove_file(&output_path).await;
+    Err(anyhow!("Could not compress video below {} bytes", target_size))
+}
+
+async fn encode_two_pass(
+    ffmpeg_path: &Path,
+    input_path: &Path,
+    output_path: &Path,
+    video_bitrate: u64,
+    short_side: Option<u32>,
+) -> Result<()> {
+    let passlog = input_path.with_extension("2pass");
+    let null_output = if cfg!(target_os = "windows") { "NUL" } else { "/dev/null" };
+    // Scale the shorter side so portrait and landscape videos are treated alike
+    let filter = short_side.map(|side| {
+        format!("scale='if(gt(iw,ih),-2,{side})':'if(gt(iw,ih),{side},-2)'", side = side)
+    });
+
+    let mut result = Ok(());
+    for pass in 1..=2 {
+        let mut cmd = Command::new(ffmpeg_path);
+        cmd.arg("-y").arg("-i").arg(input_path);
+        if let Some(filter) = &filter {
+            cmd.arg("-vf").arg(filter);
+        }
+        cmd.arg("-c:v").arg("libx264")
+           .arg("-preset").arg("medium")
+           .arg("-b:v").arg(video_bitrate.to_string())
+           .arg("-pix_fmt").arg("yuv420p")
+           .arg("-pass").arg(pass.to_string())
+           .arg("-passlogfile").arg(&passlog);
+        if pass == 1 {
+            cmd.arg("-an").arg("-f").arg("mp4").arg(null_output);
+        } else {
+            cmd.arg("-c:a").arg("aac")
+               .arg("-b:a").arg(AUDIO_BITRATE.to_string())
+               .arg("-movflags").arg("+faststart")
+               .arg(output_path);
+        }
+
+        let output = cmd.output().await?;
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            log::error!("ffmpeg compression pass {} failed: {}", pass, stderr);
+            result = Err(anyhow!("ffmpeg compression pass {} failed: {}", pass, stderr));
+            break;
+        }
+    }
+
+    // ffmpeg names the pass log files after the prefix
+    for suffix in ["-0.log", "-0.log.mbtree"] {
+        let mut log_path = passlog.clone().into_os_string();
+        log_path.push(suffix);
+        let _ = tokio::fs::remove_file(log_path).await;
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_target_video_bitrate() {
+        // 48MB over 100s leaves ~3.73 Mb/s after margin and audio
+        let bitrate = target_video_bitrate(48 * 1024 * 1024, 100.0).unwrap();
+        assert!(bitrate > 3_600_000 && bitrate < 3_900_000, "{}", bitrate);
+
+        assert_eq!(target_video_bitrate(48 * 1024 * 1024, 0.0), None);
+        // Not even the audio track fits
+        assert_eq!(target_video_bitrate(1024, 100.0), None);
+    }
+
+    #[test]
+    fn test_downscale_target() {
+        // Enough bitrate keeps the source resolution
+        assert_eq!(downscale_target(1080, 3_000_000), None);
+        assert_eq!(downscale_target(576, 3_000_000), None);
+        // Step down until the bitrate is acceptable
+        assert_eq!(downscale_target(1080, 1_500_000), Some(720));
+        assert_eq!(downscale_target(1080, 500_000), Some(480));
+        assert_eq!(downscale_target(1080, 100_000), Some(360));
+        // Never upscale
+        assert_eq!(downscale_target(400, 100_000), Some(360));
+        assert_eq!(downscale_target(300, 100_000), None);
+    }
+}
@@ -0,0 +1,6 @@
+pub mod voice;
+pub mod video_note;
+pub mod compress;
+pub mod split;
+pub mod animation;
+pub mod audio_format;
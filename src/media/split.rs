@@ -0,0 +1,131 @@
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+use anyhow::{anyhow, Result};
+
+use crate::mtproto_uploader::audio_metadata::get_audio_duration;
+
+/// Share of the size limit aimed for per segment, cuts only happen on keyframes so segments run long
+const SEGMENT_SIZE_MARGIN: f64 = 0.9;
+/// How much the segment duration shrinks when a segment still came out too large
+const RETRY_SHRINK_FACTOR: f64 = 0.75;
+/// Splitting attempts before giving up
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Segment length in seconds so that, at the average bitrate, each segment stays under `max_part_size`
+pub fn segment_duration(duration: f64, file_size: u64, max_part_size: u64) -> f64 {
+    duration * max_part_size as f64 / file_size as f64 * SEGMENT_SIZE_MARGIN
+}
+
+/// Caption for a segment, e.g. `Part 1/3`
+pub fn part_label(index: usize, total: usize) -> String {
+    format!("Part {}/{}", index + 1, total)
+}
+
+/// Splits media larger than `max_part_size` at keyframes into `<stem>.partNNN.<ext>` segments.
+///
+/// Streams are copied, MP4 segments are written with faststart. Segments are returned in
+/// playback order and the caller is responsible for deleting them.
+pub async fn split_media(ffmpeg_path: &Path, ffprobe_path: &Path, input_path: &Path, max_part_size: u64) -> Result<Vec<PathBuf>> {
+    let file_size = tokio::fs::metadata(input_path).await?.len();
+    let duration = get_audio_duration(ffprobe_path.to_string_lossy().as_ref(), input_path)
+        .await
+        .map_err(|e| anyhow!("Failed to probe media for splitting: {}", e))?;
+    if duration <= 0.0 {
+        return Err(anyhow!("Unknown media duration, cannot split {:?}", input_path));
+    }
+
+    let mut segment_secs = segment_duration(duration, file_size, max_part_size);
+    for attempt in 1..=MAX_ATTEMPTS {
+        log::info!(
+            "Splitting {:?} ({} bytes, {:.1}s) into {:.1}s segments (attempt {})",
+            input_path, file_size, duration, segment_secs, attempt
+        );
+        let parts = run_segmenter(ffmpeg_path, input_path, segment_secs).await?;
+
+        let mut oversized = false;
+        for part in &parts {
+            if tokio::fs::metadata(part).await?.len() > max_part_size {
+                oversized = true;
+                break;
+            }
+        }
+        if !oversized {
+            return Ok(parts);
+        }
+
+        log::warn!("A segment of {:?} exceeds {} bytes, splitting again with shorter segments", input_path, max_part_size);
+        remove_parts(&parts).await;
+        segment_secs *= RETRY_SHRINK_FACTOR;
+    }
+
+    Err(anyhow!("Could not split {:?} into parts below {} bytes", input_path, max_part_size))
+}
+
+async fn run_segmenter(ffmpeg_path: &Path, input_path: &Path, segment_secs: f64) -> Result<Vec<PathBuf>> {
+    let ext = input_path.extension().and_then(|s| s.to_str()).unwrap_or("mp4").to_lowercase();
+    let stem = input_path.file_stem().and_then(|s| s.to_str()).unwrap_or("output");
+    let dir = input_path.parent().unwrap_or_else(|| Path::new("."));
+    let pattern = dir.join(format!("{}.part%03d.{}", stem, ext));
+
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.arg("-y")
+       .arg("-i").arg(input_path)
+       .arg("-map").arg("0")
+       .arg("-c").arg("copy")
+       .arg("-f").arg("segment")
+       .arg("-segment_time").arg(format!("{:.3}", segment_secs))
+       .arg("-reset_timestamps").arg("1");
+    if ext == "mp4" {
+        cmd.arg("-segment_format").arg("mp4")
+           .arg("-segment_format_options").arg("movflags=+faststart");
+    }
+    cmd.arg(&pattern);
+
+    let output = cmd.output().await?;
+
+    // Collect whatever was written so partial output is cleaned up on failure too
+    let mut parts = Vec::new();
+    for index in 0.. {
+        let part = dir.join(format!("{}.part{:03}.{}", stem, index, ext));
+        if !part.exists() {
+            break;
+        }
+        parts.push(part);
+    }
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        log::error!("ffmpeg segmenting failed: {}", stderr);
+        remove_parts(&parts).await;
+        return Err(anyhow!("ffmpeg segmenting failed: {}", stderr));
+    }
+    if parts.is_empty() {
+        return Err(anyhow!("ffmpeg produced no segments for {:?}", input_path));
+    }
+
+    Ok(parts)
+}
+
+async fn remove_parts(parts: &[PathBuf]) {
+    for part in parts {
+        let _ = tokio::fs::remove_file(part).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_segment_duration() {
+        // 5GB over an hour with a 2GB limit: 0.4 of the duration, minus the margin
+        let secs = segment_duration(3600.0, 5_000_000_000, 2_000_000_000);
+        assert!((secs - 1296.0).abs() < 0.001, "{}", secs);
+    }
+
+    #[test]
+    fn test_part_label() {
+        assert_eq!(part_label(0, 3), "Part 1/3");
+        assert_eq!(part_label(2, 3), "Part 3/3");
+    }
+}
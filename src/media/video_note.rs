@@ -0,0 +1,52 @@
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+use anyhow::Result;
+
+/// Telegram video notes are limited to one minute
+pub const VIDEO_NOTE_MAX_DURATION_SECS: u32 = 60;
+/// Maximum diameter of a video note
+pub const VIDEO_NOTE_MAX_SIZE: u32 = 640;
+
+/// Crops the video to a centered square, caps it at 60 s and 640 px and re-encodes it as H.264/AAC
+pub async fn convert_to_video_note(ffmpeg_path: &Path, input_path: &Path) -> Result<PathBuf> {
+    let output_path = input_path.with_extension("circle.mp4");
+    let filter = format!(
+        "crop='min(iw,ih)':'min(iw,ih)',scale='trunc(min({size},iw)/2)*2':'trunc(min({size},ih)/2)*2'",
+        size = VIDEO_NOTE_MAX_SIZE
+    );
+
+    let output = Command::new(ffmpeg_path)
+        .arg("-y")
+        .arg("-i")
+        .arg(input_path)
+        .arg("-t")
+        .arg(VIDEO_NOTE_MAX_DURATION_SECS.to_string())
+        .arg("-vf")
+        .arg(filter)
+        .arg("-c:v")
+        .arg("libx264")
+        .arg("-preset")
+        .arg("veryfast")
+        .arg("-crf")
+        .arg("26")
+        .arg("-pix_fmt")
+        .arg("yuv420p")
+        .arg("-c:a")
+        .arg("aac")
+        .arg("-b:a")
+        .arg("96k")
+        .arg("-movflags")
+        .arg("+faststart")
+        .arg(&output_path)
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        log::error!("ffmpeg video note conversion failed: {}", stderr);
+        let _ = tokio::fs::remove_file(&output_path).await;
+        return Err(anyhow::anyhow!("ffmpeg video note conversion failed: {}", stderr));
+    }
+
+    Ok(output_path)
+}
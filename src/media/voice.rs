@@ -0,0 +1,125 @@
+use std::path::{Path, PathBuf};
+use tokio::process::Command;
+use anyhow::Result;
+
+/// Number of bars in the waveform shown by Telegram clients
+const WAVEFORM_SAMPLES: usize = 100;
+
+/// Transcodes any audio/video file into a mono OGG/Opus file suitable for a voice message
+pub async fn convert_to_voice(ffmpeg_path: &Path, input_path: &Path) -> Result<PathBuf> {
+    let output_path = input_path.with_extension("voice.ogg");
+
+    let output = Command::new(ffmpeg_path)
+        .arg("-y")
+        .arg("-i")
+        .arg(input_path)
+        .arg("-vn")
+        .arg("-map_metadata")
+        .arg("-1")
+        .arg("-ac")
+        .arg("1")
+        .arg("-c:a")
+        .arg("libopus")
+        .arg("-b:a")
+        .arg("48k")
+        .arg("-application")
+        .arg("voip")
+        .arg(&output_path)
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        log::error!("ffmpeg voice conversion failed: {}", stderr);
+        let _ = tokio::fs::remove_file(&output_path).await;
+        return Err(anyhow::anyhow!("ffmpeg voice conversion failed: {}", stderr));
+    }
+
+    Ok(output_path)
+}
+
+/// Computes the 5-bit packed waveform Telegram expects in `DocumentAttributeAudio`
+pub async fn compute_waveform(ffmpeg_path: &Path, audio_path: &Path) -> Result<Vec<u8>> {
+    // Decode to 8kHz mono signed 16-bit PCM on stdout
+    let output = Command::new(ffmpeg_path)
+        .arg("-v")
+        .arg("error")
+        .arg("-i")
+        .arg(audio_path)
+        .arg("-ac")
+        .arg("1")
+        .arg("-ar")
+        .arg("8000")
+        .arg("-f")
+        .arg("s16le")
+        .arg("-")
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        log::error!("ffmpeg waveform decoding failed: {}", stderr);
+        return Err(anyhow::anyhow!("ffmpeg waveform decoding failed: {}", stderr));
+    }
+
+    let samples: Vec<i16> = output.stdout
+        .chunks_exact(2)
+        .map(|b| i16::from_le_bytes([b[0], b[1]]))
+        .collect();
+
+    Ok(encode_waveform(&waveform_levels(&samples, WAVEFORM_SAMPLES)))
+}
+
+/// Reduces PCM samples to `bars` peak levels in the 0..=31 range
+fn waveform_levels(samples: &[i16], bars: usize) -> Vec<u8> {
+    if samples.is_empty() || bars == 0 {
+        return vec![0; bars];
+    }
+
+    let peaks: Vec<u32> = (0..bars)
+        .map(|i| {
+            let start = i * samples.len() / bars;
+            let end = ((i + 1) * samples.len() / bars).max(start + 1).min(samples.len());
+            samples[start..end].iter().map(|s| s.unsigned_abs() as u32).max().unwrap_or(0)
+        })
+        .collect();
+
+    let max_peak = peaks.iter().copied().max().unwrap_or(0).max(1);
+    peaks.iter().map(|&p| (p * 31 / max_peak) as u8).collect()
+}
+
+/// Packs 5-bit values little-endian, the layout used by Telegram voice waveforms
+fn encode_waveform(levels: &[u8]) -> Vec<u8> {
+    let mut data = vec![0u8; (levels.len() * 5).div_ceil(8)];
+    for (i, &level) in levels.iter().enumerate() {
+        let value = (level & 0x1F) as u16;
+        let bit_offset = i * 5;
+        let byte_index = bit_offset / 8;
+        let shifted = value << (bit_offset % 8);
+        data[byte_index] |= (shifted & 0xFF) as u8;
+        if byte_index + 1 < data.len() {
+            data[byte_index + 1] |= (shifted >> 8) as u8;
+        }
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_waveform_packs_five_bits() {
+        // 31 = 0b11111, 1 = 0b00001 -> bits 0..4 set, then bit 5 set
+        assert_eq!(encode_waveform(&[31, 1]), vec![0b0011_1111, 0b0000_0000]);
+        // 100 values take 500 bits = 63 bytes
+        assert_eq!(encode_waveform(&[0; 100]).len(), 63);
+    }
+
+    #[test]
+    fn test_waveform_levels_normalizes_to_max_peak() {
+        let samples = [0i16, 100, -200, 400];
+        assert_eq!(waveform_levels(&samples, 4), vec![0, 7, 15, 31]);
+        assert_eq!(waveform_levels(&[], 3), vec![0, 0, 0]);
+    }
+}
@@ -7,10 +7,13 @@ use rand_chacha::ChaCha8Rng;
 use std::path::Path;
 use log;
 
-use crate::utils::progress_bar::ProgressBar;
+use crate::utils::progress_sink::ProgressSink;
 
 use crate::mtproto_uploader::uploader::MTProtoUploader; // Import MTProtoUploader
-use crate::mtproto_uploader::file_uploader::upload_file_in_parts_with_reconnect;
+use crate::mtproto_uploader::file_uploader::{upload_file_in_parts_with_reconnect, upload_small_file_with_reconnect};
+use crate::mtproto_uploader::audio_metadata::AudioMetadata;
+use crate::media::audio_format::audio_mime_type;
+use crate::utils::rate_limiter::{mtproto_flood_wait, telegram_rate_limiter};
 
 impl MTProtoUploader {
     pub async fn upload_audio(
@@ -19,7 +22,8 @@ impl MTProtoUploader {
         username: Option<String>,
         file_path: &Path,
         caption: &str,
-        progress_bar: &mut ProgressBar,
+        metadata: &AudioMetadata,
+        progress_bar: &mut dyn ProgressSink,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         // Upload the audio file using reconnect mechanism
         let (file_id, total_parts) = upload_file_in_parts_with_reconnect(self, file_path, progress_bar, "audio").await.map_err(|e| {
@@ -27,9 +31,79 @@ impl MTProtoUploader {
             e
         })?;
 
-        // Access the actual client through the mutex
-        let client = self.client.lock().await;
-        
+        // Upload the cover as the audio thumbnail, the track is still sent without it on failure
+        let input_thumb = match &metadata.cover_path {
+            Some(cover_path) => match upload_small_file_with_reconnect(self, cover_path).await {
+                Ok((thumb_id, 1)) => Some(tl::enums::InputFile::File(tl::types::InputFile {
+                    id: thumb_id,
+                    parts: 1,
+                    name: "cover.jpg".to_string(),
+                    md5_checksum: String::new(),
+                })),
+                Ok((thumb_id, thumb_parts)) => Some(tl::enums::InputFile::Big(tl::types::InputFileBig {
+                    id: thumb_id,
+                    parts: thumb_parts,
+                    name: "cover.jpg".to_string(),
+                })),
+                Err(e) => {
+                    log::warn!("Failed to upload audio cover {:?}: {:?}", cover_path, e);
+                    None
+                }
+            },
+            None => None,
+        };
+
+        let audio_attr = tl::enums::DocumentAttribute::Audio(tl::types::DocumentAttributeAudio {
+            voice: false,
+            duration: metadata.duration.round() as i32,
+            title: metadata.title.clone(),
+            performer: metadata.performer.clone(),
+            waveform: None,
+        });
+
+        self.send_audio_document(chat_id, username, file_path, file_id, total_parts, input_thumb, audio_attr, caption).await
+    }
+
+    /// Sends an OGG/Opus file (see `media::voice`) as a voice message with its waveform
+    #[allow(clippy::too_many_arguments)]
+    pub async fn upload_voice(
+        &self,
+        chat_id: i64,
+        username: Option<String>,
+        file_path: &Path,
+        caption: &str,
+        duration: f64,
+        waveform: Option<Vec<u8>>,
+        progress_bar: &mut dyn ProgressSink,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let (file_id, total_parts) = upload_file_in_parts_with_reconnect(self, file_path, progress_bar, "voice").await.map_err(|e| {
+            log::error!("Failed to upload voice file {:?}: {:?}", file_path, e);
+            e
+        })?;
+
+        let voice_attr = tl::enums::DocumentAttribute::Audio(tl::types::DocumentAttributeAudio {
+            voice: true,
+            duration: duration.round() as i32,
+            title: None,
+            performer: None,
+            waveform,
+        });
+
+        self.send_audio_document(chat_id, username, file_path, file_id, total_parts, None, voice_attr, caption).await
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn send_audio_document(
+        &self,
+        chat_id: i64,
+        username: Option<String>,
+        file_path: &Path,
+        file_id: i64,
+        total_parts: i32,
+        input_thumb: Option<tl::enums::InputFile>,
+        audio_attr: tl::enums::DocumentAttribute,
+        caption: &str,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         let input_peer = resolve_peer(&self.client, chat_id, username.as_deref()).await.map_err(|e| {
             log::error!("Failed to resolve peer: {:?}", e);
             e
@@ -48,28 +122,13 @@ impl MTProtoUploader {
                 })?,
         });
 
-        let ext = file_path.extension().and_then(|s| s.to_str()).unwrap_or("").to_lowercase();
-        let mime = match ext.as_str() {
-            "mp3" => "audio/mpeg",
-            "m4a" => "audio/mp4",
-            "aac" => "audio/aac",
-            "ogg" => "audio/ogg",
-            _ => "audio/mpeg",
-        }.to_string();
-
-        let audio_attr = tl::enums::DocumentAttribute::Audio(tl::types::DocumentAttributeAudio {
-            voice: false,
-            duration: 0,              // optionally calculate beforehand
-            title: None,
-            performer: None,
-            waveform: None,
-        });
+        let mime = audio_mime_type(file_path).to_string();
 
         let media = tl::enums::InputMedia::UploadedDocument(tl::types::InputMediaUploadedDocument {
             nosound_video: false,
             spoiler: false,
             file: input_file,
-            thumb: None,
+            thumb: input_thumb,
             mime_type: mime,
             force_file: false,
             attributes: vec![audio_attr],
@@ -100,8 +159,14 @@ impl MTProtoUploader {
             quick_reply_shortcut: None,
         };
         
+        // Access the actual client through the mutex
+        telegram_rate_limiter().acquire(chat_id).await;
+        let client = self.client.lock().await;
         client.invoke(&request).await.map_err(|e| {
             log::error!("Failed to send audio: {:?}", e);
+            if let Some(wait) = mtproto_flood_wait(&e) {
+                telegram_rate_limiter().freeze(Some(chat_id), wait);
+            }
             e
         })?;
         
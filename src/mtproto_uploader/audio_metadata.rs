@@ -0,0 +1,243 @@
+use serde::Deserialize;
+use tokio::process::Command;
+use std::path::{Path, PathBuf};
+use anyhow::anyhow;
+
+use crate::mtproto_uploader::thumbnail::generate_cover_thumbnail;
+use crate::mtproto_uploader::video_metadata::Format;
+
+/// Metadata attached to audio uploads (both MTProto and Bot API)
+#[derive(Debug, Clone, Default)]
+pub struct AudioMetadata {
+    pub duration: f64,
+    pub title: Option<String>,
+    pub performer: Option<String>,
+    /// JPEG thumbnail (max 320px) ready to be sent as the track cover
+    pub cover_path: Option<PathBuf>,
+}
+
+/// Subset of the yt-dlp `.info.json` we care about for audio tags
+#[derive(Debug, Default, Deserialize)]
+pub struct YtDlpInfo {
+    #[serde(default)]
+    pub track: Option<String>,
+    #[serde(default)]
+    pub artist: Option<String>,
+    #[serde(default)]
+    pub artists: Option<Vec<String>>,
+    #[serde(default)]
+    pub creator: Option<String>,
+    #[serde(default)]
+    pub uploader: Option<String>,
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub duration: Option<f64>,
+}
+
+impl YtDlpInfo {
+    /// Music title for TikTok (`track`), falling back to the video title
+    pub fn audio_title(&self) -> Option<String> {
+        non_empty(self.track.as_deref()).or_else(|| non_empty(self.title.as_deref()))
+    }
+
+    /// Music author for TikTok (`artist`), falling back to the uploader
+    pub fn audio_performer(&self) -> Option<String> {
+        non_empty(self.artist.as_deref())
+            .or_else(|| self.artists.as_ref().and_then(|a| non_empty(a.first().map(|s| s.as_str()))))
+            .or_else(|| non_empty(self.creator.as_deref()))
+            .or_else(|| non_empty(self.uploader.as_deref()))
+    }
+}
+
+fn non_empty(value: Option<&str>) -> Option<String> {
+    value.map(str::trim).filter(|s| !s.is_empty()).map(|s| s.to_string())
+}
+
+/// Sidecar file written by yt-dlp `--write-info-json` next to the media file
+pub fn info_json_path(media_path: &Path) -> PathBuf {
+    media_path.with_extension("info.json")
+}
+
+/// Sidecar file written by yt-dlp `--write-thumbnail --convert-thumbnails jpg`
+pub fn raw_thumbnail_path(media_path: &Path) -> PathBuf {
+    media_path.with_extension("jpg")
+}
+
+pub async fn read_info_json(path: &Path) -> Result<YtDlpInfo, Box<dyn std::error::Error + Send + Sync>> {
+    let content = tokio::fs::read(path).await?;
+    Ok(serde_json::from_slice(&content)?)
+}
+
+pub async fn get_audio_duration(ffprobe_path: &str, file_path: &Path) -> Result<f64, Box<dyn std::error::Error + Send + Sync>> {
+    #[derive(Deserialize)]
+    struct FormatOnly {
+        format: Format,
+    }
+
+    let output = Command::new(ffprobe_path)
+        .arg("-v")
+        .arg("error")
+        .arg("-show_entries")
+        .arg("format=duration")
+        .arg("-of")
+        .arg("json")
+        .arg(file_path)
+        .output()
+        .await?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        log::error!("ffprobe failed: {}", stderr);
+        return Err(anyhow!("ffprobe failed: {}", stderr).into());
+    }
+
+    let probe: FormatOnly = serde_json::from_slice(&output.stdout)?;
+    Ok(probe.format.duration)
+}
+
+/// Writes title/artist tags (ID3v2.3 for mp3) and an embedded front cover into the audio file in place
+pub async fn write_audio_tags(
+    ffmpeg_path: &Path,
+    audio_path: &Path,
+    title: Option<&str>,
+    performer: Option<&str>,
+    cover_path: Option<&Path>,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let ext = audio_path.extension().and_then(|s| s.to_str()).unwrap_or("").to_lowercase();
+    let tagged_path = audio_path.with_extension(format!("tagged.{}", ext));
+    // Only mp3 (ID3 APIC), m4a (covr atom) and flac (PICTURE block) can carry an attached picture
+    let cover_path = cover_path.filter(|_| ext == "mp3" || ext == "m4a" || ext == "flac");
+
+    let mut cmd = Command::new(ffmpeg_path);
+    cmd.arg("-y").arg("-i").arg(audio_path);
+    if let Some(cover) = cover_path {
+        cmd.arg("-i").arg(cover).arg("-map").arg("0:a").arg("-map").arg("1:v");
+    }
+    cmd.arg("-c").arg("copy");
+    if cover_path.is_some() {
+        cmd.arg("-metadata:s:v").arg("title=Album cover")
+           .arg("-metadata:s:v").arg("comment=Cover (front)")
+           .arg("-disposition:v").arg("attached_pic");
+    }
+    if ext == "mp3" {
+        cmd.arg("-id3v2_version").arg("3");
+    }
+    if let Some(title) = title {
+        cmd.arg("-metadata").arg(format!("title={}", title));
+    }
+    if let Some(performer) = performer {
+        cmd.arg("-metadata").arg(format!("artist={}", performer));
+    }
+    cmd.arg(&tagged_path);
+
+    let output = cmd.output().await?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        log::error!("ffmpeg audio tagging failed: {}", stderr);
+        let _ = tokio::fs::remove_file(&tagged_path).await;
+        return Err(anyhow!("ffmpeg audio tagging failed: {}", stderr).into());
+    }
+
+    tokio::fs::rename(&tagged_path, audio_path).await?;
+    Ok(())
+}
+
+/// Collects duration, title, performer and cover for a downloaded audio file.
+///
+/// Reads and removes the yt-dlp sidecars (`.info.json`, raw thumbnail), writes tags and
+/// the cover into the file itself, and leaves a Telegram-sized cover JPEG that the caller
+/// is responsible for deleting. Every step is best-effort: failures only drop that piece.
+pub async fn prepare_audio_metadata(ffmpeg_path: &Path, ffprobe_path: &Path, audio_path: &Path) -> AudioMetadata {
+    let info_path = info_json_path(audio_path);
+    let info = match read_info_json(&info_path).await {
+        Ok(info) => info,
+        Err(e) => {
+            log::warn!("No usable yt-dlp metadata for {:?}: {}", audio_path, e);
+            YtDlpInfo::default()
+        }
+    };
+    let _ = tokio::fs::remove_file(&info_path).await;
+
+    let title = info.audio_title();
+    let performer = info.audio_performer();
+
+    let raw_thumbnail = raw_thumbnail_path(audio_path);
+    let cover_path = audio_path.with_extension("cover.jpg");
+    let cover_path = if raw_thumbnail.exists() {
+        match generate_cover_thumbnail(ffmpeg_path, &raw_thumbnail, &cover_path).await {
+            Ok(()) => Some(cover_path),
+            Err(e) => {
+                log::warn!("Failed to prepare audio cover for {:?}: {}", audio_path, e);
+                None
+            }
+        }
+    } else {
+        None
+    };
+
+    if let Err(e) = write_audio_tags(
+        ffmpeg_path,
+        audio_path,
+        title.as_deref(),
+        performer.as_deref(),
+        raw_thumbnail.exists().then_some(raw_thumbnail.as_path()),
+    ).await {
+        log::warn!("Failed to write audio tags for {:?}: {}", audio_path, e);
+    }
+    let _ = tokio::fs::remove_file(&raw_thumbnail).await;
+
+    let duration = match get_audio_duration(ffprobe_path.to_string_lossy().as_ref(), audio_path).await {
+        Ok(duration) if duration > 0.0 => duration,
+        Ok(_) | Err(_) => {
+            log::warn!("Failed to probe audio duration for {:?}, using yt-dlp metadata", audio_path);
+            info.duration.unwrap_or(0.0)
+        }
+    };
+
+    AudioMetadata {
+        duration,
+        title,
+        performer,
+        cover_path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tiktok_music_fields_take_precedence() {
+        let info: YtDlpInfo = serde_json::from_str(r#"{
+            "title": "my dance #fyp",
+            "uploader": "someone",
+            "track": "original sound",
+            "artist": "Famous Artist",
+            "duration": 15
+        }"#).unwrap();
+
+        assert_eq!(info.audio_title().as_deref(), Some("original sound"));
+        assert_eq!(info.audio_performer().as_deref(), Some("Famous Artist"));
+        assert_eq!(info.duration, Some(15.0));
+    }
+
+    #[test]
+    fn test_falls_back_to_title_and_uploader() {
+        let info: YtDlpInfo = serde_json::from_str(r#"{
+            "title": "Some video",
+            "artist": "  ",
+            "uploader": "channel"
+        }"#).unwrap();
+
+        assert_eq!(info.audio_title().as_deref(), Some("Some video"));
+        assert_eq!(info.audio_performer().as_deref(), Some("channel"));
+    }
+
+    #[test]
+    fn test_sidecar_paths() {
+        let media = Path::new("/tmp/output/abc.mp3");
+        assert_eq!(info_json_path(media), PathBuf::from("/tmp/output/abc.info.json"));
+        assert_eq!(raw_thumbnail_path(media), PathBuf::from("/tmp/output/abc.jpg"));
+    }
+}
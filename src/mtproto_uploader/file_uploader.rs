@@ -6,18 +6,19 @@ use std::path::Path;
 use anyhow;
 use rand;
 
-use crate::utils::progress_bar::ProgressBar;
+use crate::utils::progress_sink::{NoopProgress, ProgressSink};
+use crate::utils::transfer_rate::TransferRate;
 use crate::mtproto_uploader::uploader::MTProtoUploader;
 
 pub async fn upload_file_in_parts_with_reconnect(
     mtproto_uploader: &MTProtoUploader,
     file_path: &Path,
-    progress_bar: &mut ProgressBar,
+    progress_bar: &mut dyn ProgressSink,
     file_type: &str, // "video" or "thumbnail" to customize progress calculation
 ) -> Result<(i64, i32), Box<dyn std::error::Error + Send + Sync>> {  // Return (file_id, parts_count)
     let file_path = file_path.to_path_buf();
     let file_type = file_type.to_string();
-    let progress_bar_clone = progress_bar.clone();
+    let progress_bar_clone = progress_bar.clone_box();
     
     mtproto_uploader.with_reconnect_retry(|| {
         let mtproto_uploader = mtproto_uploader.clone();
@@ -28,7 +29,7 @@ pub async fn upload_file_in_parts_with_reconnect(
         Box::pin(async move {
             // Get access to the client
             let client_guard = mtproto_uploader.client.lock().await;
-            let result = upload_file_in_parts(&*client_guard, &file_path, &mut progress_bar, &file_type).await;
+            let result = upload_file_in_parts(&client_guard, &file_path, progress_bar.as_mut(), &file_type).await;
             drop(client_guard); // Release the lock early
             result
         })
@@ -38,7 +39,7 @@ pub async fn upload_file_in_parts_with_reconnect(
 pub async fn upload_file_in_parts(
     client: &Client,
     file_path: &Path,
-    progress_bar: &mut ProgressBar,
+    progress_bar: &mut dyn ProgressSink,
     file_type: &str, // "video" or "thumbnail" to customize progress calculation
 ) -> Result<(i64, i32), Box<dyn std::error::Error + Send + Sync>> {  // Return (file_id, parts_count)
     let file = File::open(file_path)?;
@@ -52,9 +53,10 @@ pub async fn upload_file_in_parts(
         512 * 1024  // 512 KB for videos
     };
     
-    let total_parts = (file_size + part_size - 1) / part_size;
+    let total_parts = file_size.div_ceil(part_size);
 
     let file_id: i64 = rand::random();
+    let rate = TransferRate::new();
 
     // Uploading file in parts
     for part in 0..total_parts {
@@ -102,7 +104,11 @@ pub async fn upload_file_in_parts(
         };
         
         // showing "real" upload
-        let info = format!("📤 Uploading {}... {}/{} parts", file_type, uploaded, total_parts);
+        let mut info = format!("📤 Uploading {}... {}/{} parts", file_type, uploaded, total_parts);
+        let speed_eta = rate.describe((uploaded * part_size).min(file_size) as u64, file_size as u64);
+        if !speed_eta.is_empty() {
+            info.push_str(&format!("\n{}", speed_eta));
+        }
         let _ = progress_bar.update(overall.min(99), Some(&info)).await;
     }
 
@@ -123,7 +129,7 @@ pub async fn upload_small_file_with_reconnect(
         Box::pin(async move {
             // Get access to the client
             let client_guard = mtproto_uploader.client.lock().await;
-            let result = upload_small_file(&*client_guard, &file_path).await;
+            let result = upload_small_file(&client_guard, &file_path).await;
             drop(client_guard); // Release the lock early
             result
         })
@@ -157,7 +163,7 @@ pub async fn upload_small_file(
         let (file_id, parts_count) = upload_file_in_parts(
             client, 
             file_path, 
-            &mut crate::utils::progress_bar::ProgressBar::new_silent(), 
+            &mut NoopProgress,
             "thumbnail"
         ).await?;
         Ok((file_id, parts_count))
@@ -1,11 +1,31 @@
 use grammers_client::{Client, InvocationError};
 use grammers_tl_types as tl;
 use std::{path::Path, sync::Arc};
+use std::time::Duration;
 use anyhow;
 use tokio::sync::Mutex;
 
 use crate::peers::resolve_peer;
+use crate::utils::rate_limiter::{mtproto_flood_wait, telegram_rate_limiter};
+use crate::utils::retry::{RetryDecision, RetryPolicy};
 
+/// How an uploaded video is presented in the chat
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoKind {
+    /// Regular streamable video
+    Regular,
+    /// Round video note (`round_message`)
+    RoundMessage,
+    /// Silent looping clip shown as a GIF (`DocumentAttributeAnimated`)
+    Animation,
+}
+
+/// Short pauses between attempts, flood waits are honoured as long as the deadline allows
+const SEND_MEDIA_RETRY: RetryPolicy = RetryPolicy::new(3)
+    .with_backoff(Duration::from_millis(500), Duration::from_secs(5))
+    .with_deadline(Duration::from_secs(300));
+
+#[allow(clippy::too_many_arguments)]
 pub async fn send_media_with_retry(
     client: &Arc<Mutex<Client>>,
     chat_id: i64,
@@ -20,6 +40,7 @@ pub async fn send_media_with_retry(
     width: u32,
     height: u32,
     caption: &str,
+    kind: VideoKind,
 ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     // Get input peer
     let input_peer = resolve_peer(client, chat_id, username.as_deref()).await.map_err(|e| {
@@ -43,9 +64,9 @@ pub async fn send_media_with_retry(
 
     // Create video attributes
     let video_attr = tl::enums::DocumentAttribute::Video(tl::types::DocumentAttributeVideo {
-        round_message: false,
-        supports_streaming: true,
-        nosound: false,
+        round_message: kind == VideoKind::RoundMessage,
+        supports_streaming: kind == VideoKind::Regular,
+        nosound: kind == VideoKind::Animation,
         duration,
         w: width as i32,
         h: height as i32,
@@ -53,6 +74,11 @@ pub async fn send_media_with_retry(
         video_start_ts: None,
     });
 
+    let mut attributes = vec![video_attr];
+    if kind == VideoKind::Animation {
+        attributes.push(tl::enums::DocumentAttribute::Animated);
+    }
+
     // Create input thumbnail - use InputFile::File for single-part files, InputFile::Big for multi-part
     let input_thumb = if thumb_parts == 1 {
         tl::enums::InputFile::File(tl::types::InputFile {
@@ -85,23 +111,22 @@ pub async fn send_media_with_retry(
 
     // Create media object
     let media = tl::enums::InputMedia::UploadedDocument(tl::types::InputMediaUploadedDocument {
-        nosound_video: false,
+        nosound_video: kind == VideoKind::Animation,
         spoiler: false,
         file: input_file,
         thumb: Some(input_thumb), // Pass the uploaded thumbnail
         mime_type: "video/mp4".to_string(),
         force_file: false,
-        attributes: vec![video_attr],
+        attributes,
         stickers: None,
         ttl_seconds: None,
     });
 
     // Sending message with retry logic
-    let mut attempts = 0;
-    loop {
-        attempts += 1;
-        let random_id: i64 = rand::random();
-        match {
+    let result = SEND_MEDIA_RETRY
+        .run(|| async {
+            telegram_rate_limiter().acquire(chat_id).await;
+            let random_id: i64 = rand::random();
             let actual_client = client.lock().await;
             actual_client.invoke(&tl::functions::messages::SendMedia {
                 silent: false,
@@ -122,29 +147,26 @@ pub async fn send_media_with_retry(
                 invert_media: false,
                 quick_reply_shortcut: None,
             }).await
-        } {
-            Ok(_) => break,
-            Err(InvocationError::Rpc(e)) if e.name.starts_with("FLOOD_WAIT_") => {
-                let secs = e.code as u64;
-                eprintln!("FLOOD_WAIT_X: Waiting for {} seconds", secs);
-                tokio::time::sleep(std::time::Duration::from_secs(secs.min(30))).await;
-            },
-            Err(e) if attempts < 3 => {
-                eprintln!("Attempt {} failed: {:?}. Retrying...", attempts, e);
-                tokio::time::sleep(std::time::Duration::from_millis(500 * attempts as u64)).await;
+        }, |e| match mtproto_flood_wait(e) {
+            Some(wait) => {
+                // Other requests to this chat wait as well
+                telegram_rate_limiter().freeze(Some(chat_id), wait);
+                RetryDecision::RetryAfter(wait)
+            }
+            None => RetryDecision::Retry,
+        })
+        .await;
+
+    if let Err(e) = result {
+        match e {
+            InvocationError::Rpc(ref rpc_err) => {
+                log::error!("sendMedia failed with RPC Error: code={}, name={}, value={:?}", rpc_err.code, rpc_err.name, rpc_err.value);
             },
-            Err(e) => {
-                match e {
-                    InvocationError::Rpc(ref rpc_err) => {
-                        log::error!("sendMedia failed after {} attempts with RPC Error: code={}, name={}, value={:?}", attempts, rpc_err.code, rpc_err.name, rpc_err.value);
-                    },
-                    _ => {
-                        log::error!("sendMedia failed after {} attempts with InvocationError: {:?}", attempts, e);
-                    }
-                }
-                return Err(anyhow::anyhow!("sendMedia failed after {} attempts: {:?}", attempts, e).into());
+            _ => {
+                log::error!("sendMedia failed with InvocationError: {:?}", e);
             }
         }
+        return Err(anyhow::anyhow!("sendMedia failed: {:?}", e).into());
     }
 
     Ok(())
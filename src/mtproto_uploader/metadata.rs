@@ -28,10 +28,10 @@ pub async fn get_video_metadata(ffprobe_path: &str, file_path: &Path) -> Result<
     let ff: FFProbeOutput = serde_json::from_slice(&output.stdout)?;
     let mut s = ff.streams.into_iter().next().ok_or_else(|| anyhow::anyhow!("No video stream"))?;
 
-    if s.duration <= 0.0 {
-        if let Some(fmt) = ff.format {
-            s.duration = fmt.duration;
-        }
+    if s.duration <= 0.0
+        && let Some(fmt) = ff.format
+    {
+        s.duration = fmt.duration;
     }
     Ok(s)
 }
\ No newline at end of file
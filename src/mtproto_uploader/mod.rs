@@ -1,6 +1,7 @@
 pub mod constants;
 pub mod uploader;
 pub mod audio;
+pub mod audio_metadata;
 pub mod thumbnail;
 pub mod metadata;
 pub mod video_metadata;
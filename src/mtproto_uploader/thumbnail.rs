@@ -66,4 +66,41 @@ pub async fn generate_thumbnail(
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Converts a cover image (yt-dlp thumbnail) into a Telegram-compatible JPEG thumbnail
+pub async fn generate_cover_thumbnail(
+    ffmpeg_path: &Path,
+    image_path: &Path,
+    output_path: &Path,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let mut quality = 3;
+    loop {
+        let output = Command::new(ffmpeg_path)
+            .arg("-y")
+            .arg("-i")
+            .arg(image_path)
+            .arg("-vframes")
+            .arg("1")
+            .arg("-vf")
+            .arg("scale='min(320,iw)':'min(320,ih)':force_original_aspect_ratio=decrease")
+            .arg("-q:v")
+            .arg(quality.to_string())
+            .arg(output_path)
+            .output()
+            .await?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            log::error!("ffmpeg cover thumbnail generation failed: {}", stderr);
+            return Err(anyhow!("ffmpeg cover thumbnail generation failed: {}", stderr).into());
+        }
+
+        // Same 200KB budget as video thumbnails
+        let thumbnail_size = tokio::fs::metadata(output_path).await?.len();
+        if thumbnail_size <= 200 * 1024 || quality >= 31 {
+            return Ok(());
+        }
+        quality += 2;
+    }
+}
@@ -9,10 +9,18 @@ use grammers_client::client::InitParams;
 use tokio::sync::Mutex;
 
 use crate::mtproto_uploader::constants::SESSION_FILE;
+use crate::utils::proxy::proxies;
+use crate::utils::retry::{RetryDecision, RetryPolicy};
 
 // Добавляем импорт для tl функций
 use grammers_tl_types as tl;
 
+const RECONNECT_ATTEMPTS: u32 = 3;
+/// About 2 s between reconnects, jittered so concurrent uploads don't reconnect in lockstep
+const RECONNECT_RETRY: RetryPolicy = RetryPolicy::new(RECONNECT_ATTEMPTS)
+    .with_backoff(Duration::from_secs(2), Duration::from_secs(2))
+    .with_jitter(0.5);
+
 #[derive(Clone)]
 pub struct MTProtoUploader {
     pub client: Arc<Mutex<Client>>,
@@ -38,6 +46,7 @@ impl MTProtoUploader {
             server_addr: None,
             flood_sleep_threshold: 60,
             update_queue_limit: Some(100),
+            proxy_url: proxies().mtproto.clone(),
             ..Default::default()
         };
         
@@ -105,6 +114,7 @@ impl MTProtoUploader {
             server_addr: None,
             flood_sleep_threshold: 60,
             update_queue_limit: Some(100),
+            proxy_url: proxies().mtproto.clone(),
             ..Default::default()
         };
         
@@ -135,35 +145,25 @@ impl MTProtoUploader {
         F: Fn() -> Fut,
         Fut: std::future::Future<Output = Result<T, Box<dyn std::error::Error + Send + Sync>>>,
     {
-        let max_retries = 3;
-        for attempt in 0..max_retries {
-            let result = operation().await;
-            
-            match result {
+        let mut retry = RECONNECT_RETRY.start();
+        loop {
+            let e = match operation().await {
                 Ok(value) => return Ok(value),
-                Err(e) if e.to_string().contains("read 0 bytes") || 
-                          e.to_string().contains("ConnectionReset") ||
-                          e.to_string().contains("Connection lost") => {
-                    log::warn!("Connection lost, reconnecting... (attempt {}/{})", attempt + 1, max_retries);
-                    
-                    if let Err(reconnect_err) = Self::reconnect_client(&self.client).await {
-                        log::error!("Reconnection failed: {:?}", reconnect_err);
-                        if attempt == max_retries - 1 {
-                            return Err(e);
-                        }
-                    } else {
-                        log::info!("Client reconnected successfully");
-                        if attempt < max_retries - 1 {
-                            tokio::time::sleep(Duration::from_secs(2)).await;
-                        }
-                    }
-                }
-                Err(e) => return Err(e),
+                Err(e) => e,
+            };
+            let message = e.to_string();
+            if !(message.contains("read 0 bytes") || message.contains("ConnectionReset") || message.contains("Connection lost")) {
+                return Err(e);
+            }
+
+            log::warn!("Connection lost, reconnecting... (attempt {}/{})", retry.failed_attempts() + 1, RECONNECT_ATTEMPTS);
+            match Self::reconnect_client(&self.client).await {
+                Ok(()) => log::info!("Client reconnected successfully"),
+                Err(reconnect_err) => log::error!("Reconnection failed: {:?}", reconnect_err),
+            }
+            if !retry.wait_before_retry(RetryDecision::Retry).await {
+                return Err(e);
             }
         }
-        
-        // Если мы дошли до этой точки, то это ошибка, которая не связана с подключением
-        // или все попытки переподключения были безуспешны
-        Err(Box::new(std::io::Error::new(std::io::ErrorKind::Other, "Operation failed after retries")))
     }
 }
\ No newline at end of file
@@ -3,12 +3,13 @@ use tokio::fs;
 use tokio::process::Command;
 use anyhow::anyhow;
 
-use crate::utils::progress_bar::ProgressBar;
+use crate::utils::progress_sink::ProgressSink;
 use crate::mtproto_uploader::uploader::MTProtoUploader;
 use crate::mtproto_uploader::thumbnail::generate_thumbnail;
 use crate::mtproto_uploader::metadata::get_video_metadata;
 use crate::mtproto_uploader::file_uploader::{upload_file_in_parts_with_reconnect, upload_small_file_with_reconnect};
-use crate::mtproto_uploader::message_sender::send_media_with_retry;
+use crate::mtproto_uploader::message_sender::{send_media_with_retry, VideoKind};
+use crate::yt_dlp_interface::toolchain::use_toolchain;
 
 impl MTProtoUploader {
     async fn ensure_faststart_video(&self, file_path: &Path) -> Result<std::path::PathBuf, Box<dyn std::error::Error + Send + Sync>> {
@@ -45,7 +46,43 @@ impl MTProtoUploader {
         username: Option<String>,
         file_path: &Path,
         caption: &str,
-        progress_bar: &mut ProgressBar,
+        progress_bar: &mut dyn ProgressSink,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.upload_video_as(chat_id, username, file_path, caption, VideoKind::Regular, progress_bar).await
+    }
+
+    /// Sends a square video (see `media::video_note`) as a round video note
+    pub async fn upload_video_note(
+        &self,
+        chat_id: i64,
+        username: Option<String>,
+        file_path: &Path,
+        progress_bar: &mut dyn ProgressSink,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        // Video notes cannot carry a caption
+        self.upload_video_as(chat_id, username, file_path, "", VideoKind::RoundMessage, progress_bar).await
+    }
+
+    /// Sends a silent clip (see `media::animation`) as a GIF-style animation
+    pub async fn upload_animation(
+        &self,
+        chat_id: i64,
+        username: Option<String>,
+        file_path: &Path,
+        caption: &str,
+        progress_bar: &mut dyn ProgressSink,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.upload_video_as(chat_id, username, file_path, caption, VideoKind::Animation, progress_bar).await
+    }
+
+    async fn upload_video_as(
+        &self,
+        chat_id: i64,
+        username: Option<String>,
+        file_path: &Path,
+        caption: &str,
+        kind: VideoKind,
+        progress_bar: &mut dyn ProgressSink,
     ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         // RAII guard for automatic deletion of temporary faststart file
         struct TempVideoGuard {
@@ -74,8 +111,11 @@ impl MTProtoUploader {
             }
         }
 
+        // ffmpeg prepares everything up front, the binaries may be updated while the file uploads
+        let toolchain = use_toolchain().await;
+
         // Create temporary faststart file with guard
-        let (video_path, temp_guard) = if file_path.extension().map_or(false, |ext| ext == "mp4") {
+        let (video_path, temp_guard) = if file_path.extension().is_some_and(|ext| ext == "mp4") {
             match self.ensure_faststart_video(file_path).await {
                 Ok(temp_path) => {
                     let guard = TempVideoGuard::new(temp_path.clone());
@@ -92,24 +132,25 @@ impl MTProtoUploader {
             (file_path.to_path_buf(), None) // Use original file
         };
 
-        // Upload the main video file using reconnect mechanism
-        let (file_id, file_parts) = upload_file_in_parts_with_reconnect(self, &video_path, progress_bar, "video").await.map_err(|e| {
-            log::error!("Failed to upload video file {:?}: {:?}", file_path, e);
-            e
-        })?;
-
         // Get video metadata
         let video_metadata = get_video_metadata(self.ffprobe_path.to_string_lossy().as_ref(), &video_path).await.map_err(|e| {
             log::error!("Failed to get video metadata for {:?}: {:?}", file_path, e);
             e
         })?;
 
-        // Generate and upload thumbnail
+        // Generate thumbnail
         let thumbnail_path = file_path.with_extension("jpg");
         generate_thumbnail(&self.ffmpeg_path, file_path, &thumbnail_path).await.map_err(|e| {
             log::error!("Failed to generate thumbnail for {:?}: {:?}", file_path, e);
             e
         })?;
+        drop(toolchain);
+
+        // Upload the main video file using reconnect mechanism
+        let (file_id, file_parts) = upload_file_in_parts_with_reconnect(self, &video_path, progress_bar, "video").await.map_err(|e| {
+            log::error!("Failed to upload video file {:?}: {:?}", file_path, e);
+            e
+        })?;
 
         // Upload the thumbnail using the reconnect mechanism
         let (thumbnail_file_id, thumbnail_parts) = upload_small_file_with_reconnect(self, &thumbnail_path).await.map_err(|e| {
@@ -132,6 +173,7 @@ impl MTProtoUploader {
             video_metadata.width,
             video_metadata.height,
             caption,
+            kind,
         ).await.map_err(|e| {
             log::error!("Failed to send media: {:?}", e);
             e
@@ -1,24 +1,32 @@
 use reqwest::multipart::{Form, Part};
 use tokio::fs::File;
 use teloxide::types::ChatId;
-use crate::utils::progress_bar::ProgressBar;
+use crate::utils::progress_sink::ProgressSink;
+use crate::utils::transfer_rate::TransferRate;
+use crate::utils::rate_limiter::{bot_api_retry_after, telegram_rate_limiter};
+use crate::utils::proxy::http_client_builder;
 use crate::utils::progress_reader::ProgressReader;
+use crate::utils::temp_file::TempFile;
+use crate::config::BotApiConfig;
+use crate::media::audio_format::audio_mime_type;
+use crate::mtproto_uploader::audio_metadata::AudioMetadata;
+use crate::yt_dlp_interface::toolchain::{toolchain, use_toolchain};
 use tokio_util::io::ReaderStream;
 use tokio::process::Command;
 use std::path::Path;
 use std::path::PathBuf;
-use anyhow;
-use log;
+use std::sync::Arc;
+use tokio::sync::Mutex;
 
 async fn ensure_faststart_video(ffmpeg_path: &PathBuf, file_path: &Path) -> Result<std::path::PathBuf, Box<dyn std::error::Error + Send + Sync>> {
-    // Create a temporary file for the faststart-optimized video
-    let temp_dir = std::env::temp_dir();
+    // Next to the source, so a local Bot API server that sees the download directory sees the copy too
     let file_name = file_path.file_name()
         .and_then(|name| name.to_str())
         .unwrap_or("temp.mp4");
-    let temp_path = temp_dir.join(format!("faststart_{}", file_name));
+    let temp_path = file_path.with_file_name(format!("faststart_{}", file_name));
 
     let output = Command::new(ffmpeg_path)
+        .arg("-y")
         .arg("-i")
         .arg(file_path)
         .arg("-c")
@@ -38,44 +46,174 @@ async fn ensure_faststart_video(ffmpeg_path: &PathBuf, file_path: &Path) -> Resu
     Ok(temp_path)
 }
 
+/// Builds the `file://` URI a local telegram-bot-api server reads the upload from
+fn local_file_uri(file_path: &Path) -> anyhow::Result<String> {
+    let absolute_path = std::fs::canonicalize(file_path)?;
+    reqwest::Url::from_file_path(&absolute_path)
+        .map(|url| url.to_string())
+        .map_err(|_| anyhow::anyhow!("Failed to build file URI for {:?}", absolute_path))
+}
+
+/// Paths of ffmpeg and ffprobe from the toolchain resolved in main.rs
+fn ffmpeg_paths() -> anyhow::Result<(PathBuf, PathBuf)> {
+    let toolchain = toolchain();
+    Ok((toolchain.ffmpeg.clone(), toolchain.ffprobe.clone()))
+}
+
+/// Adds the media file to the form: a `file://` path in local mode, otherwise a
+/// streamed part that reports progress in the 80..=100% range
+async fn attach_media_file(
+    form: Form,
+    bot_api: &BotApiConfig,
+    field: &str,
+    file_path: &Path,
+    mime: &str,
+    progress_bar: &mut dyn ProgressSink,
+) -> anyhow::Result<Form> {
+    if bot_api.local_mode {
+        // The local server reads the file straight from disk, so there is nothing to stream
+        let _ = progress_bar.update(90, Some("📤 Sending via local Bot API server...")).await;
+        return Ok(form.text(field.to_string(), local_file_uri(file_path)?));
+    }
+
+    let file = File::open(file_path).await?;
+    let len = file.metadata().await?.len();
+
+    let pb_clone = progress_bar.clone_box();
+    // Keep track of the last update time to implement throttling
+    let last_update_time = Arc::new(Mutex::new(std::time::Instant::now()));
+    let last_update_time_clone = last_update_time.clone();
+    let rate = TransferRate::new();
+
+    let reader = ProgressReader::new(file, len, move |uploaded, total| {
+        let overall = 80.0 + (uploaded as f64 / total as f64) * 20.0;
+        let mut pb2 = pb_clone.clone();
+        let mut text = format!("📤 Uploading... {:.1}/{:.1} MB",
+            uploaded as f64 / 1_048_576.0,
+            total as f64 / 1_048_576.0);
+        let speed_eta = rate.describe(uploaded, total);
+        if !speed_eta.is_empty() {
+            text.push_str(&format!("\n{}", speed_eta));
+        }
+        let last_update_time = last_update_time_clone.clone();
+
+        tokio::spawn(async move {
+            // Implement throttling: minimum 1.5 seconds between updates
+            let min_update_interval = std::time::Duration::from_millis(1500);
+            let now = std::time::Instant::now();
+
+            let should_update = {
+                let mut last_time = last_update_time.lock().await;
+                if now.duration_since(*last_time) >= min_update_interval {
+                    *last_time = now;
+                    true
+                } else {
+                    false
+                }
+            };
+
+            if should_update || overall >= 100.0 {
+                let _ = pb2.update(overall.min(100.0) as u8, Some(&text)).await;
+            }
+        });
+    });
+
+    let stream_reader = ReaderStream::new(reader);
+
+    let part = Part::stream_with_length(reqwest::Body::wrap_stream(stream_reader), len)
+        .file_name(file_path.file_name().unwrap().to_string_lossy().to_string())
+        .mime_str(mime)?;
+    Ok(form.part(field.to_string(), part))
+}
+
+/// Sends the prepared form to a Bot API method through the shared rate limiter.
+///
+/// A 429 freezes the chat for `retry_after` seconds, so the caller's next attempt waits it out.
+async fn post_form(bot_api: &BotApiConfig, bot_token: &str, chat_id: ChatId, method: &str, form: Form) -> anyhow::Result<()> {
+    let limiter = telegram_rate_limiter();
+    limiter.acquire(chat_id.0).await;
+
+    let url = bot_api.method_url(bot_token, method);
+    let client = http_client_builder()?.build()?;
+    let resp = client.post(&url).multipart(form).send().await?;
+
+    let status = resp.status();
+    if !status.is_success() {
+        let body = resp.text().await.unwrap_or_default();
+        if status == reqwest::StatusCode::TOO_MANY_REQUESTS
+            && let Some(retry_after) = bot_api_retry_after(&body)
+        {
+            limiter.freeze(Some(chat_id.0), std::time::Duration::from_secs(retry_after));
+            return Err(anyhow::anyhow!("Bot API {} rate limited, retry after {}s", method, retry_after));
+        }
+        return Err(anyhow::anyhow!("Bot API {} failed: {} {}", method, status, body));
+    }
+    Ok(())
+}
+
 async fn get_video_metadata(ffprobe_path: &str, file_path: &Path) -> Result<crate::mtproto_uploader::video_metadata::Stream, Box<dyn std::error::Error + Send + Sync>> {
     // Reuse the existing function from mtproto_uploader
-    crate::mtproto_uploader::metadata::get_video_metadata(ffprobe_path, file_path).await.map_err(|e| e.into())
+    crate::mtproto_uploader::metadata::get_video_metadata(ffprobe_path, file_path).await
 }
 
 pub async fn send_video_with_progress_botapi(
+    bot_api: &BotApiConfig,
     bot_token: &str,
     chat_id: ChatId,
     file_path: &std::path::Path,
     caption: Option<&str>,
-    progress_bar: &mut ProgressBar,
+    progress_bar: &mut dyn ProgressSink,
 ) -> anyhow::Result<()> {
-    // Get paths for ffmpeg and ffprobe (using the same approach as in main.rs)
-    let libraries_dir = std::env::current_dir()? // Consider making this configurable or user-specific
-        .join("lib");
-    let ffmpeg_dir = libraries_dir.join("ffmpeg");
-    let ffmpeg_path = ffmpeg_dir.join(if cfg!(target_os = "windows") { "ffmpeg.exe" } else { "ffmpeg" });
-    let ffprobe_path = ffmpeg_dir.join(if cfg!(target_os = "windows") { "ffprobe.exe" } else { "ffprobe" });
+    send_video_file(bot_api, bot_token, chat_id, file_path, caption, "sendVideo", "video", progress_bar).await
+}
+
+/// Sends a silent MP4 clip as a GIF-style animation (`sendAnimation`)
+pub async fn send_animation_with_progress_botapi(
+    bot_api: &BotApiConfig,
+    bot_token: &str,
+    chat_id: ChatId,
+    file_path: &Path,
+    caption: Option<&str>,
+    progress_bar: &mut dyn ProgressSink,
+) -> anyhow::Result<()> {
+    send_video_file(bot_api, bot_token, chat_id, file_path, caption, "sendAnimation", "animation", progress_bar).await
+}
+
+/// Shared `sendVideo`/`sendAnimation` implementation: faststart remux, metadata and thumbnail
+#[allow(clippy::too_many_arguments)]
+async fn send_video_file(
+    bot_api: &BotApiConfig,
+    bot_token: &str,
+    chat_id: ChatId,
+    file_path: &Path,
+    caption: Option<&str>,
+    method: &str,
+    field: &str,
+    progress_bar: &mut dyn ProgressSink,
+) -> anyhow::Result<()> {
+    let (ffmpeg_path, ffprobe_path) = ffmpeg_paths()?;
     let ffprobe_path_str = ffprobe_path.to_string_lossy();
+    // Released before the upload starts
+    let toolchain = use_toolchain().await;
 
-    // First, remux with faststart
-    let (video_path, needs_cleanup) = if file_path.extension().map_or(false, |ext| ext == "mp4") {
+    // First, remux with faststart; the guards remove the copies also when the upload fails
+    let (video_path, _faststart_guard) = if file_path.extension().is_some_and(|ext| ext == "mp4") {
         match ensure_faststart_video(&ffmpeg_path, file_path).await {
-            Ok(temp_path) => (temp_path, true), // Use processed video and mark for cleanup
+            Ok(temp_path) => (temp_path.clone(), Some(TempFile::new(temp_path))),
             Err(e) => {
                 log::warn!("Failed to remux video with faststart for Bot API, proceeding with original: {:?}", e);
-                (file_path.to_path_buf(), false) // Use original file and no cleanup needed
+                (file_path.to_path_buf(), None) // Use original file and no cleanup needed
             }
         }
     } else {
-        (file_path.to_path_buf(), false) // Use original file and no cleanup needed
+        (file_path.to_path_buf(), None) // Use original file and no cleanup needed
     };
 
     // Get video metadata
     let meta = get_video_metadata(&ffprobe_path_str, &video_path).await.map_err(|e| {
         log::warn!("Failed to get video metadata, proceeding without: {:?}", e);
         e
-    }).unwrap_or_else(|_| crate::mtproto_uploader::video_metadata::Stream {
+    }).unwrap_or(crate::mtproto_uploader::video_metadata::Stream {
         width: 0,
         height: 0,
         duration: 0.0,
@@ -84,34 +222,16 @@ pub async fn send_video_with_progress_botapi(
     // Generate thumbnail
     let thumbnail_path = video_path.with_extension("jpg");
     let thumbnail_result = crate::mtproto_uploader::thumbnail::generate_thumbnail(&ffmpeg_path, &video_path, &thumbnail_path).await;
-    
-    let file = File::open(&video_path).await?;
-    let len = file.metadata().await?.len();
-
-    // 80..=100% - actual Bot API upload
-    let pb_clone = progress_bar.clone();
-    let reader = ProgressReader::new(file, len, move |uploaded, total| {
-        let overall = 80.0 + (uploaded as f64 / total as f64) * 20.0;
-        // Without await inside callback: move to task
-        let mut pb2 = pb_clone.clone();
-        let text = format!("📤 Uploading... {:.1}/{:.1} MB",
-            uploaded as f64 / 1_048_576.0,
-            total as f64 / 1_048_576.0);
-        tokio::spawn(async move {
-            let _ = pb2.update(overall.min(100.0) as u8, Some(&text)).await;
-        });
-    });
-
-    let stream_reader = ReaderStream::new(reader);
-
-    let part = Part::stream_with_length(reqwest::Body::wrap_stream(stream_reader), len)
-        .file_name(video_path.file_name().unwrap().to_string_lossy().to_string())
-        .mime_str("video/mp4")?;
+    let _thumbnail_guard = thumbnail_result.is_ok().then(|| TempFile::new(thumbnail_path.clone()));
+    drop(toolchain);
 
     let mut form = Form::new()
-        .text("chat_id", chat_id.0.to_string())
-        .part("video", part)
-        .text("supports_streaming", "true");
+        .text("chat_id", chat_id.0.to_string());
+    if field == "video" {
+        form = form.text("supports_streaming", "true");
+    }
+
+    form = attach_media_file(form, bot_api, field, &video_path, "video/mp4", progress_bar).await?;
 
     // Add width and height if available
     if meta.width > 0 {
@@ -125,122 +245,134 @@ pub async fn send_video_with_progress_botapi(
     }
 
     // Add thumbnail if successfully generated
-    if thumbnail_result.is_ok() {
-        if let Ok(thumb_part) = Part::file(&thumbnail_path).await.map(|p| p.mime_str("image/jpeg").unwrap()) {
-            form = form.part("thumbnail", thumb_part);
-        }
+    if thumbnail_result.is_ok()
+        && let Ok(thumb_part) = Part::file(&thumbnail_path).await.map(|p| p.mime_str("image/jpeg").unwrap())
+    {
+        form = form.part("thumbnail", thumb_part);
     }
 
     let form = if let Some(c) = caption {
         form.text("caption", c.to_string())
     } else { form };
 
-    let url = format!("https://api.telegram.org/bot{}/sendVideo", bot_token);
-    let client = reqwest::Client::new();
-    let resp = client.post(&url).multipart(form).send().await?;
-
-    if !resp.status().is_success() {
-        return Err(anyhow::anyhow!("Bot API sendVideo failed: {}", resp.status()));
-    }
+    post_form(bot_api, bot_token, chat_id, method, form).await?;
 
     // Success: hide progress bar immediately
-    progress_bar.delete().await?;
-    
-    // Clean up temporary files
-    if needs_cleanup {
-        tokio::fs::remove_file(&video_path).await?;
-    }
-    
-    if thumbnail_result.is_ok() {
-        tokio::fs::remove_file(&thumbnail_path).await?;
-    }
-    
+    progress_bar.finish().await?;
+
     Ok(())
 }
 
 pub async fn send_audio_with_progress_botapi(
+    bot_api: &BotApiConfig,
     bot_token: &str,
     chat_id: ChatId,
     file_path: &std::path::Path,
     caption: Option<&str>,
-    progress_bar: &mut ProgressBar,
+    metadata: &AudioMetadata,
+    progress_bar: &mut dyn ProgressSink,
 ) -> anyhow::Result<()> {
-    use reqwest::multipart::{Form, Part};
-    use tokio_util::io::ReaderStream;
-    use crate::utils::progress_reader::ProgressReader;
-    use tokio::fs::File;
+    let form = Form::new()
+        .text("chat_id", chat_id.0.to_string());
+    let mut form = attach_media_file(form, bot_api, "audio", file_path, audio_mime_type(file_path), progress_bar).await?;
 
-    let file = File::open(file_path).await?;
-    let len = file.metadata().await?.len();
-
-    let pb_clone = progress_bar.clone();
-    // Keep track of the last update time to implement throttling
-    use std::sync::Arc;
-    use tokio::sync::Mutex;
-    let last_update_time = Arc::new(Mutex::new(std::time::Instant::now()));
-    let last_update_time_clone = last_update_time.clone();
-    
-    let reader = ProgressReader::new(file, len, move |uploaded, total| {
-        let overall = 80.0 + (uploaded as f64 / total as f64) * 20.0;
-        let mut pb2 = pb_clone.clone();
-        let text = format!("📤 Uploading... {:.1}/{:.1} MB",
-            uploaded as f64 / 1_048_576.0,
-            total as f64 / 1_048_576.0);
-        let last_update_time = last_update_time_clone.clone();
-        
-        tokio::spawn(async move {
-            // Implement throttling: minimum 1.5 seconds between updates
-            let min_update_interval = std::time::Duration::from_millis(1500);
-            let now = std::time::Instant::now();
-            
-            let should_update = {
-                let mut last_time = last_update_time.lock().await;
-                if now.duration_since(*last_time) >= min_update_interval {
-                    *last_time = now;
-                    true
-                } else {
-                    false
-                }
-            };
-            
-            if should_update || overall >= 100.0 {
-                let _ = pb2.update(overall.min(100.0) as u8, Some(&text)).await;
-            }
-        });
-    });
+    if metadata.duration > 0.0 {
+        form = form.text("duration", metadata.duration.round().to_string());
+    }
+    if let Some(title) = &metadata.title {
+        form = form.text("title", title.clone());
+    }
+    if let Some(performer) = &metadata.performer {
+        form = form.text("performer", performer.clone());
+    }
+    if let Some(cover_path) = &metadata.cover_path
+        && let Ok(thumb_part) = Part::file(cover_path).await.map(|p| p.mime_str("image/jpeg").unwrap())
+    {
+        form = form.part("thumbnail", thumb_part);
+    }
 
-    let stream_reader = ReaderStream::new(reader);
+    if let Some(c) = caption {
+        form = form.text("caption", c.to_string());
+    }
 
-    let ext = file_path.extension().and_then(|s| s.to_str()).unwrap_or_default().to_lowercase();
-    let mime = match ext.as_str() {
-        "mp3" => "audio/mpeg",
-        "m4a" => "audio/mp4",
-        "aac" => "audio/aac",
-        "ogg" => "audio/ogg",
-        _ => "audio/mpeg",
-    };
+    post_form(bot_api, bot_token, chat_id, "sendAudio", form).await?;
 
-    let part = Part::stream_with_length(reqwest::Body::wrap_stream(stream_reader), len)
-        .file_name(file_path.file_name().unwrap().to_string_lossy().to_string())
-        .mime_str(mime)?;
+    progress_bar.finish().await?;
+    Ok(())
+}
 
-    let mut form = Form::new()
-        .text("chat_id", chat_id.0.to_string())
-        .part("audio", part);
+/// Sends an OGG/Opus file as a voice message (`sendVoice`)
+pub async fn send_voice_with_progress_botapi(
+    bot_api: &BotApiConfig,
+    bot_token: &str,
+    chat_id: ChatId,
+    file_path: &Path,
+    caption: Option<&str>,
+    duration: f64,
+    progress_bar: &mut dyn ProgressSink,
+) -> anyhow::Result<()> {
+    let form = Form::new()
+        .text("chat_id", chat_id.0.to_string());
+    let mut form = attach_media_file(form, bot_api, "voice", file_path, "audio/ogg", progress_bar).await?;
 
+    if duration > 0.0 {
+        form = form.text("duration", duration.round().to_string());
+    }
     if let Some(c) = caption {
         form = form.text("caption", c.to_string());
     }
 
-    let url = format!("https://api.telegram.org/bot{}/sendAudio", bot_token);
-    let client = reqwest::Client::new();
-    let resp = client.post(&url).multipart(form).send().await?;
+    post_form(bot_api, bot_token, chat_id, "sendVoice", form).await?;
+
+    progress_bar.finish().await?;
+    Ok(())
+}
+
+/// Sends a square video as a round video note (`sendVideoNote`)
+pub async fn send_video_note_with_progress_botapi(
+    bot_api: &BotApiConfig,
+    bot_token: &str,
+    chat_id: ChatId,
+    file_path: &Path,
+    progress_bar: &mut dyn ProgressSink,
+) -> anyhow::Result<()> {
+    let (ffmpeg_path, ffprobe_path) = ffmpeg_paths()?;
+    let toolchain = use_toolchain().await;
+
+    let meta = get_video_metadata(&ffprobe_path.to_string_lossy(), file_path).await.map_err(|e| {
+        log::warn!("Failed to get video note metadata, proceeding without: {:?}", e);
+        e
+    }).ok();
 
-    if !resp.status().is_success() {
-        return Err(anyhow::anyhow!("Bot API sendAudio failed: {}", resp.status()));
+    let thumbnail_path = file_path.with_extension("jpg");
+    let thumbnail_result = crate::mtproto_uploader::thumbnail::generate_thumbnail(&ffmpeg_path, file_path, &thumbnail_path).await;
+    drop(toolchain);
+
+    let form = Form::new()
+        .text("chat_id", chat_id.0.to_string());
+    let mut form = attach_media_file(form, bot_api, "video_note", file_path, "video/mp4", progress_bar).await?;
+
+    if let Some(meta) = meta {
+        if meta.width > 0 {
+            form = form.text("length", meta.width.to_string());
+        }
+        if meta.duration > 0.0 {
+            form = form.text("duration", meta.duration.floor().to_string());
+        }
+    }
+    if thumbnail_result.is_ok()
+        && let Ok(thumb_part) = Part::file(&thumbnail_path).await.map(|p| p.mime_str("image/jpeg").unwrap())
+    {
+        form = form.part("thumbnail", thumb_part);
     }
 
-    progress_bar.delete().await?;
+    let result = post_form(bot_api, bot_token, chat_id, "sendVideoNote", form).await;
+
+    if thumbnail_result.is_ok() {
+        let _ = tokio::fs::remove_file(&thumbnail_path).await;
+    }
+    result?;
+
+    progress_bar.finish().await?;
     Ok(())
 }
-
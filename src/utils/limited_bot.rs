@@ -0,0 +1,154 @@
+use std::future::IntoFuture;
+use futures::future::BoxFuture;
+use teloxide::net::Download;
+use teloxide::payloads::{
+    AnswerCallbackQuery, DeleteMessage, EditMessageReplyMarkup, EditMessageText, GetChatMember, GetFile, SendMessage,
+};
+use teloxide::prelude::*;
+use teloxide::requests::{HasPayload, JsonRequest, Output};
+use teloxide::types::{CallbackQueryId, FileId, MessageId, Recipient};
+use teloxide::{DownloadError, RequestError};
+use tokio::io::AsyncWrite;
+
+use crate::utils::rate_limiter::{telegram_rate_limiter, MAX_RETRY_AFTER_ATTEMPTS};
+
+/// The bot as handlers see it: every request waits for the shared `TelegramRateLimiter`
+/// and is sent again after a `retry_after` response.
+///
+/// Only the Bot API methods the bot uses are exposed, so nothing can skip the limiter.
+#[derive(Clone)]
+pub struct LimitedBot {
+    bot: Bot,
+}
+
+impl LimitedBot {
+    pub fn new(bot: Bot) -> Self {
+        Self { bot }
+    }
+
+    pub fn token(&self) -> &str {
+        self.bot.token()
+    }
+
+    pub fn send_message(&self, chat_id: impl Into<Recipient>, text: impl Into<String>) -> Limited<JsonRequest<SendMessage>> {
+        let chat_id = chat_id.into();
+        Limited::new(chat_of(&chat_id), self.bot.send_message(chat_id, text))
+    }
+
+    pub fn edit_message_text(
+        &self,
+        chat_id: impl Into<Recipient>,
+        message_id: MessageId,
+        text: impl Into<String>,
+    ) -> Limited<JsonRequest<EditMessageText>> {
+        let chat_id = chat_id.into();
+        Limited::new(chat_of(&chat_id), self.bot.edit_message_text(chat_id, message_id, text))
+    }
+
+    pub fn edit_message_reply_markup(
+        &self,
+        chat_id: impl Into<Recipient>,
+        message_id: MessageId,
+    ) -> Limited<JsonRequest<EditMessageReplyMarkup>> {
+        let chat_id = chat_id.into();
+        Limited::new(chat_of(&chat_id), self.bot.edit_message_reply_markup(chat_id, message_id))
+    }
+
+    pub fn delete_message(&self, chat_id: impl Into<Recipient>, message_id: MessageId) -> Limited<JsonRequest<DeleteMessage>> {
+        let chat_id = chat_id.into();
+        Limited::new(chat_of(&chat_id), self.bot.delete_message(chat_id, message_id))
+    }
+
+    pub fn answer_callback_query(&self, callback_query_id: CallbackQueryId) -> Limited<JsonRequest<AnswerCallbackQuery>> {
+        Limited::new(None, self.bot.answer_callback_query(callback_query_id))
+    }
+
+    pub fn get_chat_member(&self, chat_id: impl Into<Recipient>, user_id: UserId) -> Limited<JsonRequest<GetChatMember>> {
+        Limited::new(None, self.bot.get_chat_member(chat_id, user_id))
+    }
+
+    pub fn get_file(&self, file_id: FileId) -> Limited<JsonRequest<GetFile>> {
+        Limited::new(None, self.bot.get_file(file_id))
+    }
+
+    /// Downloads a file returned by `get_file`; file downloads don't count against the API limits
+    pub async fn download_file(&self, path: &str, destination: &mut (dyn AsyncWrite + Unpin + Send)) -> Result<(), DownloadError> {
+        self.bot.download_file(path, destination).await
+    }
+}
+
+/// Chats are limited by numeric id, requests to a `@channel` only by the global limit
+fn chat_of(recipient: &Recipient) -> Option<i64> {
+    match recipient {
+        Recipient::Id(chat_id) => Some(chat_id.0),
+        Recipient::ChannelUsername(_) => None,
+    }
+}
+
+/// A teloxide request sent through the shared limiter once awaited; setters work as on the request itself
+pub struct Limited<R> {
+    chat_id: Option<i64>,
+    request: R,
+}
+
+impl<R> Limited<R>
+where
+    R: Request<Err = RequestError>,
+{
+    fn new(chat_id: Option<i64>, request: R) -> Self {
+        Self { chat_id, request }
+    }
+
+    /// Sends only if the limits allow it right now, `None` when the request was dropped.
+    ///
+    /// For messages superseded by the next one anyway, such as progress edits.
+    pub async fn send_if_free(self) -> Option<Result<Output<R>, RequestError>> {
+        let limiter = telegram_rate_limiter();
+        if !limiter.try_acquire(self.chat_id) {
+            return None;
+        }
+        let result = self.request.send_ref().await;
+        if let Err(RequestError::RetryAfter(seconds)) = &result {
+            limiter.freeze(self.chat_id, seconds.duration());
+        }
+        Some(result)
+    }
+}
+
+impl<R: HasPayload> HasPayload for Limited<R> {
+    type Payload = R::Payload;
+
+    fn payload_mut(&mut self) -> &mut Self::Payload {
+        self.request.payload_mut()
+    }
+
+    fn payload_ref(&self) -> &Self::Payload {
+        self.request.payload_ref()
+    }
+}
+
+impl<R> IntoFuture for Limited<R>
+where
+    R: Request<Err = RequestError> + Send + 'static,
+    Output<R>: Send,
+{
+    type Output = Result<Output<R>, RequestError>;
+    type IntoFuture = BoxFuture<'static, Self::Output>;
+
+    fn into_future(self) -> Self::IntoFuture {
+        Box::pin(async move {
+            let limiter = telegram_rate_limiter();
+            let mut attempts = 0;
+            loop {
+                limiter.acquire(self.chat_id).await;
+                match self.request.send_ref().await {
+                    Err(RequestError::RetryAfter(seconds)) if attempts < MAX_RETRY_AFTER_ATTEMPTS => {
+                        attempts += 1;
+                        limiter.freeze(self.chat_id, seconds.duration());
+                    }
+                    result => return result,
+                }
+            }
+        })
+    }
+}
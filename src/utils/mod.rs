@@ -1,4 +1,11 @@
 pub mod progress_bar;
+pub mod progress_sink;
 pub mod progress_reader;
 pub mod task_manager;
 pub mod retry;
+pub mod pending_choices;
+pub mod transfer_rate;
+pub mod rate_limiter;
+pub mod limited_bot;
+pub mod proxy;
+pub mod temp_file;
@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::Mutex;
+use tokio::time::{Duration, Instant};
+use uuid::Uuid;
+
+use crate::yt_dlp_interface::clip::ClipRange;
+use crate::yt_dlp_interface::probe::{FormatVariant, ProbedVideo};
+
+/// How long the format picker buttons stay valid
+pub const PENDING_CHOICE_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// A link waiting for the user to pick a format
+#[derive(Debug, Clone)]
+pub struct PendingFormatChoice {
+    /// Telegram user who sent the link, the only one allowed to pick
+    pub requester_id: Option<u64>,
+    pub username: Option<String>,
+    pub url: String,
+    pub clip: Option<ClipRange>,
+    pub variants: Vec<FormatVariant>,
+    /// The probe the menu was built from, the download starts from it instead of probing again
+    pub probed: Arc<ProbedVideo>,
+    pub created_at: Instant,
+}
+
+/// Why a tapped format button doesn't start a download
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChoiceRefusal {
+    /// Unknown token, already taken or past the TTL
+    Expired,
+    /// Tapped by someone other than the user who sent the link
+    NotRequester,
+}
+
+/// Links waiting in "ask every time" mode, keyed by a short token used in callback data
+pub struct PendingFormatChoices {
+    ttl: Duration,
+    choices: Mutex<HashMap<String, PendingFormatChoice>>,
+}
+
+impl PendingFormatChoices {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            choices: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Stores a choice and returns its token, dropping expired ones on the way
+    pub async fn insert(&self, choice: PendingFormatChoice) -> String {
+        // 12 hex chars keep `pick:<token>:<index>` well under the 64 byte callback data limit
+        let token = Uuid::new_v4().simple().to_string()[..12].to_string();
+        let mut choices = self.choices.lock().await;
+        self.prune(&mut choices);
+        choices.insert(token.clone(), choice);
+        token
+    }
+
+    /// Removes and returns the choice if `user_id` requested it; others' taps leave it in place
+    pub async fn take(&self, token: &str, user_id: u64) -> Result<PendingFormatChoice, ChoiceRefusal> {
+        let mut choices = self.choices.lock().await;
+        self.prune(&mut choices);
+        match choices.get(token) {
+            None => Err(ChoiceRefusal::Expired),
+            Some(choice) if choice.requester_id != Some(user_id) => Err(ChoiceRefusal::NotRequester),
+            Some(_) => choices.remove(token).ok_or(ChoiceRefusal::Expired),
+        }
+    }
+
+    fn prune(&self, choices: &mut HashMap<String, PendingFormatChoice>) {
+        choices.retain(|_, pending| pending.created_at.elapsed() < self.ttl);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn choice() -> PendingFormatChoice {
+        PendingFormatChoice {
+            requester_id: Some(42),
+            username: None,
+            url: "https://www.tiktok.com/@user/video/1".to_string(),
+            clip: None,
+            variants: Vec::new(),
+            probed: Arc::new(ProbedVideo { info: Default::default(), json: Vec::new() }),
+            created_at: Instant::now(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_take_returns_choice_once() {
+        let pending = PendingFormatChoices::new(PENDING_CHOICE_TTL);
+        let token = pending.insert(choice()).await;
+
+        assert!(format!("pick:{}:10", token).len() <= 64);
+        // Someone else in the group can't start it
+        assert_eq!(pending.take(&token, 7).await.unwrap_err(), ChoiceRefusal::NotRequester);
+        assert_eq!(pending.take(&token, 42).await.map(|c| c.url), Ok(choice().url));
+        assert_eq!(pending.take(&token, 42).await.unwrap_err(), ChoiceRefusal::Expired);
+    }
+
+    #[tokio::test]
+    async fn test_expired_choice_is_rejected() {
+        let pending = PendingFormatChoices::new(Duration::ZERO);
+        let token = pending.insert(choice()).await;
+        assert_eq!(pending.take(&token, 42).await.unwrap_err(), ChoiceRefusal::Expired);
+        assert!(pending.choices.lock().await.is_empty());
+    }
+}
@@ -1,20 +1,24 @@
 use anyhow::Result; 
+use futures::future::BoxFuture;
 use teloxide::{
-    prelude::*,
-    requests::Requester,
+    RequestError,
     types::{ChatId, MessageId},
 };
 
+use crate::utils::limited_bot::LimitedBot;
+use crate::utils::progress_sink::ProgressSink;
+
+/// Progress shown by editing a single Telegram message
 #[derive(Clone)]
 pub struct ProgressBar {
-    bot: Bot,
+    bot: LimitedBot,
     chat_id: ChatId,
     message_id: Option<MessageId>,
     last_update: Option<tokio::time::Instant>, // Track last update time for throttling
 }
 
 impl ProgressBar {
-    pub fn new(bot: Bot, chat_id: ChatId) -> Self {
+    pub fn new(bot: LimitedBot, chat_id: ChatId) -> Self {
         Self {
             bot,
             chat_id,
@@ -23,18 +27,14 @@ impl ProgressBar {
         }
     }
 
-    pub fn new_silent() -> Self {
-        Self::new(Bot::new("DUMMY_TOKEN"), ChatId(0))
-    }
-
-    pub async fn start(&mut self, initial_text: &str) -> Result<(), anyhow::Error> {
+    async fn start_message(&mut self, initial_text: &str) -> Result<(), anyhow::Error> {
         let msg = self.bot.send_message(self.chat_id, initial_text).await?;
         self.message_id = Some(msg.id);
         self.last_update = Some(tokio::time::Instant::now());
         Ok(())
     }
 
-    pub async fn update(
+    async fn update_message(
         &mut self,
         percentage: u8,
         extra_info: Option<&str>,
@@ -45,36 +45,46 @@ impl ProgressBar {
         let now = tokio::time::Instant::now();
 
         // Check if enough time has passed since last update
-        if let Some(last) = self.last_update {
-            if now.duration_since(last) < MIN_UPDATE_INTERVAL && percentage < 100 {
-                // Skip update if not enough time passed (except for 100% completion)
-                // Additionally, skip updates that don't represent meaningful progress (at least 5% change)
-                if percentage < 100 {
-                    return Ok(());
-                }
+        if let Some(last) = self.last_update
+            && now.duration_since(last) < MIN_UPDATE_INTERVAL && percentage < 100
+        {
+            // Skip update if not enough time passed (except for 100% completion)
+            // Additionally, skip updates that don't represent meaningful progress (at least 5% change)
+            if percentage < 100 {
+                return Ok(());
             }
         }
 
-        // Update the time of last update
-        self.last_update = Some(now);
-
         if let Some(message_id) = self.message_id {
             let progress_text = self.create_progress_bar(percentage, extra_info);
-            let result = self
-                .bot
-                .edit_message_text(self.chat_id, message_id, progress_text)
-                .await;
-
-            // Handle API errors gracefully
-            if let Err(e) = result {
-                if !e.to_string().contains("message is not modified") {
+            let edit = self.bot.edit_message_text(self.chat_id, message_id, progress_text);
+            // Coalesce edits: when the chat (or the bot) is at its limit, drop this edit and let the
+            // next one carry the newer state. The final 100% edit waits for its turn instead.
+            let result = if percentage < 100 {
+                match edit.send_if_free().await {
+                    Some(result) => result,
+                    None => return Ok(()),
+                }
+            } else {
+                edit.await
+            };
+
+            // Update the time of last update
+            self.last_update = Some(now);
+
+            // Handle API errors gracefully, a flood wait already holds back the next edits
+            match result {
+                Err(RequestError::RetryAfter(_)) => {}
+                Err(e) if !e.to_string().contains("message is not modified") => {
                     log::warn!("Failed to update progress bar: {}", e);
                 }
+                _ => {}
             }
         } else {
             // If there's no message ID yet, send a new message
             let progress_text = self.create_progress_bar(percentage, extra_info);
             let result = self.bot.send_message(self.chat_id, progress_text).await;
+            self.last_update = Some(now);
             if let Ok(msg) = result {
                 self.message_id = Some(msg.id);
             } else {
@@ -105,7 +115,7 @@ impl ProgressBar {
         result
     }
 
-    pub async fn delete(&mut self) -> Result<(), anyhow::Error> {
+    async fn delete_message(&mut self) -> Result<(), anyhow::Error> {
         if let Some(message_id) = self.message_id {
             let _ = self.bot.delete_message(self.chat_id, message_id).await;
             self.message_id = None;
@@ -113,3 +123,21 @@ impl ProgressBar {
         Ok(())
     }
 }
+
+impl ProgressSink for ProgressBar {
+    fn start<'a>(&'a mut self, text: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(self.start_message(text))
+    }
+
+    fn update<'a>(&'a mut self, percentage: u8, extra_info: Option<&'a str>) -> BoxFuture<'a, Result<()>> {
+        Box::pin(self.update_message(percentage, extra_info))
+    }
+
+    fn finish(&mut self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(self.delete_message())
+    }
+
+    fn clone_box(&self) -> Box<dyn ProgressSink> {
+        Box::new(self.clone())
+    }
+}
@@ -0,0 +1,169 @@
+use anyhow::Result;
+use futures::future::BoxFuture;
+
+/// Receives progress of a download or upload.
+///
+/// `ProgressBar` edits a Telegram message, the other implementations cover places without a chat
+/// (thumbnails, failed status messages) and tests.
+pub trait ProgressSink: Send + Sync {
+    /// Shows the initial status text
+    fn start<'a>(&'a mut self, text: &'a str) -> BoxFuture<'a, Result<()>>;
+
+    /// Reports overall progress (0-100) with an optional detail line
+    fn update<'a>(&'a mut self, percentage: u8, extra_info: Option<&'a str>) -> BoxFuture<'a, Result<()>>;
+
+    /// Removes the progress display once the result has been sent or the job failed
+    fn finish(&mut self) -> BoxFuture<'_, Result<()>>;
+
+    /// Copy that reports to the same place, for spawned tasks and retry closures
+    fn clone_box(&self) -> Box<dyn ProgressSink>;
+}
+
+impl Clone for Box<dyn ProgressSink> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
+
+/// Discards all progress
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopProgress;
+
+impl ProgressSink for NoopProgress {
+    fn start<'a>(&'a mut self, _text: &'a str) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn update<'a>(&'a mut self, _percentage: u8, _extra_info: Option<&'a str>) -> BoxFuture<'a, Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn finish(&mut self) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async { Ok(()) })
+    }
+
+    fn clone_box(&self) -> Box<dyn ProgressSink> {
+        Box::new(*self)
+    }
+}
+
+/// Writes progress to the log, tagged with what is being processed
+#[derive(Debug, Clone)]
+pub struct LogProgress {
+    label: String,
+    last_percentage: Option<u8>,
+}
+
+impl LogProgress {
+    pub fn new(label: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            last_percentage: None,
+        }
+    }
+}
+
+impl ProgressSink for LogProgress {
+    fn start<'a>(&'a mut self, text: &'a str) -> BoxFuture<'a, Result<()>> {
+        log::info!("[{}] {}", self.label, text);
+        Box::pin(async { Ok(()) })
+    }
+
+    fn update<'a>(&'a mut self, percentage: u8, extra_info: Option<&'a str>) -> BoxFuture<'a, Result<()>> {
+        // Only log every 10% to keep the log readable
+        let bucket = percentage / 10;
+        if self.last_percentage.is_none_or(|last| last / 10 != bucket) || percentage == 100 {
+            self.last_percentage = Some(percentage);
+            log::info!("[{}] {}% {}", self.label, percentage, extra_info.unwrap_or_default().replace('\n', " "));
+        }
+        Box::pin(async { Ok(()) })
+    }
+
+    fn finish(&mut self) -> BoxFuture<'_, Result<()>> {
+        log::info!("[{}] finished", self.label);
+        Box::pin(async { Ok(()) })
+    }
+
+    fn clone_box(&self) -> Box<dyn ProgressSink> {
+        Box::new(self.clone())
+    }
+}
+
+/// One call received by `RecordingProgress`
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProgressEvent {
+    Start(String),
+    Update(u8, Option<String>),
+    Finish,
+}
+
+/// Keeps every call in memory, clones share the same record
+#[cfg(test)]
+#[derive(Debug, Clone, Default)]
+pub struct RecordingProgress {
+    events: std::sync::Arc<std::sync::Mutex<Vec<ProgressEvent>>>,
+}
+
+#[cfg(test)]
+impl RecordingProgress {
+    pub fn events(&self) -> Vec<ProgressEvent> {
+        self.events.lock().unwrap().clone()
+    }
+
+    fn record(&self, event: ProgressEvent) {
+        self.events.lock().unwrap().push(event);
+    }
+}
+
+#[cfg(test)]
+impl ProgressSink for RecordingProgress {
+    fn start<'a>(&'a mut self, text: &'a str) -> BoxFuture<'a, Result<()>> {
+        self.record(ProgressEvent::Start(text.to_string()));
+        Box::pin(async { Ok(()) })
+    }
+
+    fn update<'a>(&'a mut self, percentage: u8, extra_info: Option<&'a str>) -> BoxFuture<'a, Result<()>> {
+        self.record(ProgressEvent::Update(percentage, extra_info.map(str::to_string)));
+        Box::pin(async { Ok(()) })
+    }
+
+    fn finish(&mut self) -> BoxFuture<'_, Result<()>> {
+        self.record(ProgressEvent::Finish);
+        Box::pin(async { Ok(()) })
+    }
+
+    fn clone_box(&self) -> Box<dyn ProgressSink> {
+        Box::new(self.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_recording_progress_shares_events_between_clones() {
+        let mut progress = RecordingProgress::default();
+        let mut boxed: Box<dyn ProgressSink> = Box::new(progress.clone());
+
+        progress.start("Starting").await.unwrap();
+        boxed.clone().update(50, Some("half")).await.unwrap();
+        boxed.finish().await.unwrap();
+
+        assert_eq!(progress.events(), vec![
+            ProgressEvent::Start("Starting".to_string()),
+            ProgressEvent::Update(50, Some("half".to_string())),
+            ProgressEvent::Finish,
+        ]);
+    }
+
+    #[tokio::test]
+    async fn test_noop_and_log_progress_never_fail() {
+        for mut progress in [Box::new(NoopProgress) as Box<dyn ProgressSink>, Box::new(LogProgress::new("test"))] {
+            progress.start("Starting").await.unwrap();
+            progress.update(42, None).await.unwrap();
+            progress.finish().await.unwrap();
+        }
+    }
+}
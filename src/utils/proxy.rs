@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use anyhow::Result;
+
+use crate::config::{default_proxy_urls, http_proxy_url, mtproto_proxy_url, platform_proxy_urls};
+
+/// Proxies used in turn; the current one stays until a download through it is blocked
+#[derive(Debug, Default)]
+pub struct ProxyPool {
+    urls: Vec<String>,
+    next: AtomicUsize,
+}
+
+impl ProxyPool {
+    pub fn new(urls: Vec<String>) -> Self {
+        Self { urls, next: AtomicUsize::new(0) }
+    }
+
+    /// `None` when the pool is empty and traffic goes out directly
+    pub fn current(&self) -> Option<&str> {
+        if self.urls.is_empty() {
+            return None;
+        }
+        Some(&self.urls[self.next.load(Ordering::Relaxed) % self.urls.len()])
+    }
+
+    /// Moves on to the next proxy; `false` if there is no other one to try
+    pub fn rotate(&self) -> bool {
+        if self.urls.len() < 2 {
+            return false;
+        }
+        self.next.fetch_add(1, Ordering::Relaxed);
+        true
+    }
+}
+
+/// Platform a link belongs to, the second-level domain with common short hosts mapped,
+/// e.g. `vm.tiktok.com` → `tiktok`, `youtu.be` → `youtube`
+pub fn platform_of(url: &str) -> String {
+    let host = reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_lowercase))
+        .unwrap_or_default();
+    let mut labels = host.rsplit('.');
+    let name = match (labels.next(), labels.next()) {
+        (Some(_), Some(name)) => name,
+        (Some(name), None) => name,
+        _ => "",
+    };
+    match name {
+        "youtu" => "youtube",
+        "x" => "twitter",
+        "fb" => "facebook",
+        "instagr" => "instagram",
+        name => name,
+    }
+    .to_string()
+}
+
+/// Messages of downloads refused because of where the request came from rather than the video
+pub fn is_ip_block_error(message: &str) -> bool {
+    let markers = [
+        "not available in your country",
+        "not made this video available in your country",
+        "geo restriction",
+        "geo-restricted",
+        "IP address is blocked",
+        "HTTP Error 403",
+        "HTTP Error 429",
+        "confirm you're not a bot",
+        "confirm you’re not a bot",
+        "rate-limit reached",
+    ];
+    markers.iter().any(|marker| message.contains(marker))
+}
+
+/// Proxy settings of the process, read from the environment on first use
+#[derive(Debug, Default)]
+pub struct ProxyConfig {
+    default_pool: ProxyPool,
+    /// `PROXY_URLS_<PLATFORM>` pools, an empty one sends that platform out directly
+    platform_pools: HashMap<String, ProxyPool>,
+    pub http: Option<String>,
+    pub mtproto: Option<String>,
+}
+
+impl ProxyConfig {
+    pub fn from_env() -> Self {
+        Self {
+            default_pool: ProxyPool::new(default_proxy_urls()),
+            platform_pools: platform_proxy_urls().into_iter().map(|(platform, urls)| (platform, ProxyPool::new(urls))).collect(),
+            http: http_proxy_url(),
+            mtproto: mtproto_proxy_url(),
+        }
+    }
+
+    fn pool_for(&self, url: &str) -> &ProxyPool {
+        self.platform_pools.get(&platform_of(url)).unwrap_or(&self.default_pool)
+    }
+
+    /// Proxy yt-dlp uses for `url`
+    pub fn for_url(&self, url: &str) -> Option<String> {
+        self.pool_for(url).current().map(str::to_string)
+    }
+
+    /// Switches `url`'s platform to its next proxy after a block; `false` if there is none
+    pub fn rotate(&self, url: &str) -> bool {
+        let pool = self.pool_for(url);
+        let rotated = pool.rotate();
+        if rotated {
+            log::warn!("Download from {} was blocked, switching {} to proxy {}", platform_of(url), platform_of(url), pool.current().unwrap_or_default());
+        }
+        rotated
+    }
+}
+
+static PROXIES: OnceLock<ProxyConfig> = OnceLock::new();
+
+pub fn proxies() -> &'static ProxyConfig {
+    PROXIES.get_or_init(ProxyConfig::from_env)
+}
+
+/// `reqwest` client builder going through `HTTP_PROXY_URL` when one is set
+pub fn http_client_builder() -> Result<reqwest::ClientBuilder> {
+    with_http_proxy(reqwest::Client::builder())
+}
+
+/// Adds `HTTP_PROXY_URL` to an existing builder, e.g. teloxide's default settings
+pub fn with_http_proxy(builder: reqwest::ClientBuilder) -> Result<reqwest::ClientBuilder> {
+    Ok(match &proxies().http {
+        Some(proxy) => builder.proxy(reqwest::Proxy::all(proxy)?),
+        None => builder,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_platform_pools_and_rotation() {
+        assert_eq!(platform_of("https://vm.tiktok.com/ZM123/"), "tiktok");
+        assert_eq!(platform_of("https://youtu.be/abc"), "youtube");
+        assert_eq!(platform_of("https://x.com/user/status/1"), "twitter");
+        assert_eq!(platform_of("not a url"), "");
+
+        let config = ProxyConfig {
+            default_pool: ProxyPool::new(vec!["socks5://a:1080".to_string(), "http://b:3128".to_string()]),
+            platform_pools: HashMap::from([("tiktok".to_string(), ProxyPool::new(Vec::new()))]),
+            ..Default::default()
+        };
+        assert_eq!(config.for_url("https://www.tiktok.com/@user/video/1"), None);
+        assert!(!config.rotate("https://www.tiktok.com/@user/video/1"));
+
+        let url = "https://www.instagram.com/reel/abc/";
+        assert_eq!(config.for_url(url).as_deref(), Some("socks5://a:1080"));
+        assert!(config.rotate(url));
+        assert_eq!(config.for_url(url).as_deref(), Some("http://b:3128"));
+        assert!(config.rotate(url));
+        assert_eq!(config.for_url(url).as_deref(), Some("socks5://a:1080"));
+
+        assert!(is_ip_block_error("ERROR: [youtube] abc: Video unavailable. The uploader has not made this video available in your country"));
+        assert!(!is_ip_block_error("ERROR: Private video"));
+    }
+}
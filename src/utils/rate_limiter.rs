@@ -0,0 +1,180 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+use grammers_client::InvocationError;
+use tokio::time::{Duration, Instant};
+
+/// Telegram allows about one message per second in a single chat
+const PER_CHAT_INTERVAL: Duration = Duration::from_secs(1);
+/// ...and about 30 messages per second across all chats
+const GLOBAL_PER_SECOND: usize = 30;
+/// How many times a request rejected with `retry_after` is sent again
+pub const MAX_RETRY_AFTER_ATTEMPTS: u32 = 3;
+
+#[derive(Default)]
+struct LimiterState {
+    /// Send times within the last second, oldest first
+    recent: VecDeque<Instant>,
+    /// Earliest time the next message may go to a chat
+    next_per_chat: HashMap<i64, Instant>,
+    /// Set by a global flood wait
+    frozen_until: Option<Instant>,
+}
+
+/// Spaces out requests to the Telegram API, shared by the Bot API and MTProto paths.
+///
+/// Telegram counts limits per bot, so there is one instance per process, see `telegram_rate_limiter`.
+pub struct TelegramRateLimiter {
+    per_chat_interval: Duration,
+    global_per_second: usize,
+    state: Mutex<LimiterState>,
+}
+
+impl TelegramRateLimiter {
+    pub fn new(per_chat_interval: Duration, global_per_second: usize) -> Self {
+        Self {
+            per_chat_interval,
+            global_per_second,
+            state: Mutex::new(LimiterState::default()),
+        }
+    }
+
+    /// Time left until a request to `chat_id` may be sent, reserving the slot when it is zero.
+    ///
+    /// Requests that don't go to a chat (`None`) only count against the global limit.
+    fn reserve(&self, chat_id: Option<i64>) -> Duration {
+        let now = Instant::now();
+        let mut state = self.state.lock().unwrap();
+
+        while state.recent.front().is_some_and(|sent| now.duration_since(*sent) >= Duration::from_secs(1)) {
+            state.recent.pop_front();
+        }
+        // Chats that have been quiet for a while don't need an entry any more
+        if state.next_per_chat.len() > 1000 {
+            state.next_per_chat.retain(|_, next| *next > now);
+        }
+
+        let global_wait = match state.recent.front() {
+            Some(oldest) if state.recent.len() >= self.global_per_second => (*oldest + Duration::from_secs(1)) - now,
+            _ => Duration::ZERO,
+        };
+        let frozen_wait = state.frozen_until.map(|until| until.saturating_duration_since(now)).unwrap_or_default();
+        let chat_wait = chat_id.and_then(|chat_id| state.next_per_chat.get(&chat_id)).map(|next| next.saturating_duration_since(now)).unwrap_or_default();
+
+        let wait = global_wait.max(frozen_wait).max(chat_wait);
+        if wait.is_zero() {
+            state.recent.push_back(now);
+            if let Some(chat_id) = chat_id {
+                state.next_per_chat.insert(chat_id, now + self.per_chat_interval);
+            }
+        }
+        wait
+    }
+
+    /// Waits until a request to `chat_id` fits into the limits
+    pub async fn acquire(&self, chat_id: impl Into<Option<i64>>) {
+        let chat_id = chat_id.into();
+        loop {
+            let wait = self.reserve(chat_id);
+            if wait.is_zero() {
+                return;
+            }
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Takes a slot only if one is free right now, used to drop superseded progress edits
+    pub fn try_acquire(&self, chat_id: impl Into<Option<i64>>) -> bool {
+        self.reserve(chat_id.into()).is_zero()
+    }
+
+    /// Holds back requests after a `retry_after` / `FLOOD_WAIT_X`, for one chat or for everything
+    pub fn freeze(&self, chat_id: Option<i64>, duration: Duration) {
+        let until = Instant::now() + duration;
+        let mut state = self.state.lock().unwrap();
+        log::warn!("Telegram flood wait of {:?} for {}", duration, chat_id.map_or("all chats".to_string(), |id| format!("chat {}", id)));
+        match chat_id {
+            Some(chat_id) => {
+                let next = state.next_per_chat.entry(chat_id).or_insert(until);
+                *next = (*next).max(until);
+            }
+            None => state.frozen_until = Some(state.frozen_until.map_or(until, |current| current.max(until))),
+        }
+    }
+}
+
+/// The limiter shared by every outgoing Telegram request of this bot
+pub fn telegram_rate_limiter() -> &'static TelegramRateLimiter {
+    static LIMITER: OnceLock<TelegramRateLimiter> = OnceLock::new();
+    LIMITER.get_or_init(|| TelegramRateLimiter::new(PER_CHAT_INTERVAL, GLOBAL_PER_SECOND))
+}
+
+/// Wait time of an MTProto `FLOOD_WAIT_X` / `SLOWMODE_WAIT_X` error.
+///
+/// grammers strips the number from the name, it ends up in `value`.
+pub fn mtproto_flood_wait(error: &InvocationError) -> Option<Duration> {
+    match error {
+        InvocationError::Rpc(rpc) if rpc.is("FLOOD_WAIT") || rpc.is("FLOOD_PREMIUM_WAIT") || rpc.is("SLOWMODE_WAIT") => {
+            Some(Duration::from_secs(rpc.value.unwrap_or(1) as u64))
+        }
+        _ => None,
+    }
+}
+
+/// `parameters.retry_after` of a Bot API error response body
+pub fn bot_api_retry_after(body: &str) -> Option<u64> {
+    serde_json::from_str::<serde_json::Value>(body)
+        .ok()?
+        .get("parameters")?
+        .get("retry_after")?
+        .as_u64()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_per_chat_and_global_limits() {
+        let limiter = TelegramRateLimiter::new(Duration::from_secs(60), 2);
+        assert!(limiter.try_acquire(1));
+        // Same chat again within the interval
+        assert!(!limiter.try_acquire(1));
+        assert!(limiter.try_acquire(2));
+        // Global budget of 2 per second used up, also for requests outside a chat
+        assert!(!limiter.try_acquire(3));
+        assert!(!limiter.try_acquire(None));
+    }
+
+    #[test]
+    fn test_freeze() {
+        let limiter = TelegramRateLimiter::new(Duration::ZERO, 30);
+        limiter.freeze(Some(1), Duration::from_secs(60));
+        assert!(!limiter.try_acquire(1));
+        assert!(limiter.try_acquire(2));
+
+        limiter.freeze(None, Duration::from_secs(60));
+        assert!(!limiter.try_acquire(2));
+    }
+
+    #[test]
+    fn test_bot_api_retry_after() {
+        let body = r#"{"ok":false,"error_code":429,"description":"Too Many Requests: retry after 7","parameters":{"retry_after":7}}"#;
+        assert_eq!(bot_api_retry_after(body), Some(7));
+        assert_eq!(bot_api_retry_after(r#"{"ok":false,"error_code":400}"#), None);
+    }
+
+    #[test]
+    fn test_mtproto_flood_wait() {
+        let flood = InvocationError::Rpc(grammers_mtsender::RpcError::from(grammers_tl_types::types::RpcError {
+            error_code: 420,
+            error_message: "FLOOD_WAIT_31".to_string(),
+        }));
+        assert_eq!(mtproto_flood_wait(&flood), Some(Duration::from_secs(31)));
+
+        let other = InvocationError::Rpc(grammers_mtsender::RpcError::from(grammers_tl_types::types::RpcError {
+            error_code: 400,
+            error_message: "PEER_ID_INVALID".to_string(),
+        }));
+        assert_eq!(mtproto_flood_wait(&other), None);
+    }
+}
@@ -1,10 +1,228 @@
-
 use regex::Regex;
+use std::future::Future;
+use tokio::time::{Duration, Instant};
 
-/// Extract FLOOD_WAIT seconds from Telegram error
+/// Extract FLOOD_WAIT seconds from Telegram error, either the raw `FLOOD_WAIT_30`
+/// or grammers' `FLOOD_WAIT (value: 30)`
 pub fn extract_flood_wait(error_str: &str) -> Option<u64> {
-    let re = Regex::new(r"FLOOD_WAIT_(\d+)").unwrap();
+    let re = Regex::new(r"FLOOD_WAIT(?:_| \(value: )(\d+)").unwrap();
     re.captures(error_str)
         .and_then(|caps| caps.get(1))
         .and_then(|m| m.as_str().parse().ok())
-}
\ No newline at end of file
+}
+
+/// What to do after a failed attempt
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryDecision {
+    /// Try again after the policy's backoff
+    Retry,
+    /// Try again after exactly this long (Telegram flood waits)
+    RetryAfter(Duration),
+    /// Give up, retrying can't help
+    Fail,
+}
+
+/// Treats every error as transient, but waits out flood waits mentioned in the message
+pub fn retry_unless_flood_wait(error: &impl std::fmt::Display) -> RetryDecision {
+    match extract_flood_wait(&error.to_string()) {
+        Some(seconds) => RetryDecision::RetryAfter(Duration::from_secs(seconds)),
+        None => RetryDecision::Retry,
+    }
+}
+
+/// Attempts, exponential backoff with jitter and an overall deadline for retried operations
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    /// Fraction of each backoff that is randomized, so parallel jobs don't retry in lockstep
+    jitter: f64,
+    deadline: Option<Duration>,
+}
+
+impl RetryPolicy {
+    /// `max_attempts` includes the first try; backoff starts at 1 s and doubles up to 30 s
+    pub const fn new(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            base_delay: Duration::from_secs(1),
+            max_delay: Duration::from_secs(30),
+            jitter: 0.2,
+            deadline: None,
+        }
+    }
+
+    pub const fn with_backoff(mut self, base_delay: Duration, max_delay: Duration) -> Self {
+        self.base_delay = base_delay;
+        self.max_delay = max_delay;
+        self
+    }
+
+    pub const fn with_jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter;
+        self
+    }
+
+    /// No retry is started if it would begin after `deadline` from the first attempt
+    pub const fn with_deadline(mut self, deadline: Duration) -> Self {
+        self.deadline = Some(deadline);
+        self
+    }
+
+    /// Delay before attempt `failed_attempts + 1`
+    pub fn backoff(&self, failed_attempts: u32) -> Duration {
+        let exponent = failed_attempts.saturating_sub(1).min(31);
+        let delay = self.base_delay.saturating_mul(1 << exponent).min(self.max_delay);
+        if self.jitter > 0.0 {
+            delay.mul_f64(1.0 - self.jitter * rand::random::<f64>())
+        } else {
+            delay
+        }
+    }
+
+    /// Starts tracking attempts, for loops that can't be expressed as a closure (e.g. they
+    /// lend `&mut` state to every attempt)
+    pub fn start(&self) -> RetryState {
+        RetryState {
+            policy: *self,
+            failed_attempts: 0,
+            started: Instant::now(),
+        }
+    }
+
+    /// Runs `operation` until it succeeds, `classify` says to stop or the policy is exhausted
+    pub async fn run<T, E, F, Fut, C>(&self, mut operation: F, classify: C) -> Result<T, E>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+        C: Fn(&E) -> RetryDecision,
+    {
+        let mut state = self.start();
+        loop {
+            match operation().await {
+                Ok(value) => return Ok(value),
+                Err(e) => {
+                    if !state.wait_before_retry(classify(&e)).await {
+                        return Err(e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Progress of one retried operation, see `RetryPolicy::start`
+#[derive(Debug)]
+pub struct RetryState {
+    policy: RetryPolicy,
+    failed_attempts: u32,
+    started: Instant,
+}
+
+impl RetryState {
+    /// Number of failed attempts so far
+    pub fn failed_attempts(&self) -> u32 {
+        self.failed_attempts
+    }
+
+    /// Records a failed attempt and sleeps before the next one.
+    ///
+    /// Returns `false` without sleeping when the operation should be given up.
+    pub async fn wait_before_retry(&mut self, decision: RetryDecision) -> bool {
+        self.failed_attempts += 1;
+        let delay = match decision {
+            RetryDecision::Fail => return false,
+            _ if self.failed_attempts >= self.policy.max_attempts => return false,
+            RetryDecision::Retry => self.policy.backoff(self.failed_attempts),
+            RetryDecision::RetryAfter(delay) => delay,
+        };
+
+        if let Some(deadline) = self.policy.deadline
+            && self.started.elapsed() + delay > deadline
+        {
+            log::warn!("Giving up after {} attempts, the next retry would pass the {:?} deadline", self.failed_attempts, deadline);
+            return false;
+        }
+
+        log::warn!("Attempt {}/{} failed, retrying in {:?}", self.failed_attempts, self.policy.max_attempts, delay);
+        tokio::time::sleep(delay).await;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    const POLICY: RetryPolicy = RetryPolicy::new(3).with_jitter(0.0);
+
+    #[tokio::test(start_paused = true)]
+    async fn test_retries_with_exponential_backoff() {
+        let attempts = Cell::new(0);
+        let started = Instant::now();
+
+        let result: Result<u32, &str> = POLICY
+            .run(|| {
+                attempts.set(attempts.get() + 1);
+                async { if attempts.get() < 3 { Err("transient") } else { Ok(attempts.get()) } }
+            }, |_| RetryDecision::Retry)
+            .await;
+
+        assert_eq!(result, Ok(3));
+        // 1 s after the first failure, 2 s after the second
+        assert_eq!(started.elapsed(), Duration::from_secs(3));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_gives_up_after_max_attempts_or_fatal_error() {
+        let attempts = Cell::new(0);
+        let result: Result<(), &str> = POLICY
+            .run(|| {
+                attempts.set(attempts.get() + 1);
+                async { Err("transient") }
+            }, |_| RetryDecision::Retry)
+            .await;
+        assert_eq!(result, Err("transient"));
+        assert_eq!(attempts.get(), 3);
+
+        attempts.set(0);
+        let started = Instant::now();
+        let result: Result<(), &str> = POLICY
+            .run(|| {
+                attempts.set(attempts.get() + 1);
+                async { Err("fatal") }
+            }, |_| RetryDecision::Fail)
+            .await;
+        assert_eq!(result, Err("fatal"));
+        assert_eq!(attempts.get(), 1);
+        assert_eq!(started.elapsed(), Duration::ZERO);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_flood_wait_and_deadline() {
+        let started = Instant::now();
+        let mut state = POLICY.start();
+        let decision = retry_unless_flood_wait(&"rpc error 420: FLOOD_WAIT (value: 7)");
+        assert_eq!(decision, RetryDecision::RetryAfter(Duration::from_secs(7)));
+        assert!(state.wait_before_retry(decision).await);
+        assert_eq!(started.elapsed(), Duration::from_secs(7));
+
+        // A retry that would start after the deadline is not attempted
+        let mut state = POLICY.with_deadline(Duration::from_secs(5)).start();
+        assert!(!state.wait_before_retry(RetryDecision::RetryAfter(Duration::from_secs(10))).await);
+        assert_eq!(state.failed_attempts(), 1);
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let policy = RetryPolicy::new(10).with_jitter(0.0).with_backoff(Duration::from_secs(1), Duration::from_secs(5));
+        assert_eq!(policy.backoff(1), Duration::from_secs(1));
+        assert_eq!(policy.backoff(3), Duration::from_secs(4));
+        assert_eq!(policy.backoff(8), Duration::from_secs(5));
+
+        let jittered = RetryPolicy::new(3).backoff(2);
+        assert!(jittered <= Duration::from_secs(2) && jittered >= Duration::from_millis(1600));
+    }
+}
@@ -0,0 +1,28 @@
+use std::path::PathBuf;
+
+// RAII for automatic file cleanup
+pub struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        // Use blocking operation in Drop for guaranteed cleanup
+        // Drop should not panic, so we handle errors
+        if std::thread::panicking() {
+            // If already panicking, skip cleanup to avoid double panic
+            log::warn!("Skipping temp file cleanup during panic: {}", self.path.display());
+            return;
+        }
+        match std::fs::remove_file(&self.path) {
+            Ok(_) => log::debug!("Successfully removed temp file: {}", self.path.display()),
+            Err(e) => log::warn!("Failed to cleanup temp file {}: {}", self.path.display(), e),
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use std::time::Instant;
+
+/// Average speed and remaining time of a transfer, measured from its start
+#[derive(Debug, Clone, Copy)]
+pub struct TransferRate {
+    started: Instant,
+}
+
+impl TransferRate {
+    pub fn new() -> Self {
+        Self { started: Instant::now() }
+    }
+
+    /// Bytes per second, `None` right after the start
+    pub fn speed(&self, transferred: u64) -> Option<f64> {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        (elapsed > 0.1 && transferred > 0).then(|| transferred as f64 / elapsed)
+    }
+
+    /// `2.1 MB/s • ETA 0:05`, empty until the speed is known
+    pub fn describe(&self, transferred: u64, total: u64) -> String {
+        let Some(speed) = self.speed(transferred) else {
+            return String::new();
+        };
+        let eta = (total.saturating_sub(transferred) as f64 / speed) as u64;
+        format_speed_eta(Some(speed), Some(eta))
+    }
+}
+
+impl Default for TransferRate {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `512 KB/s`, `2.1 MB/s`
+pub fn format_speed(bytes_per_sec: f64) -> String {
+    if bytes_per_sec >= 1_048_576.0 {
+        format!("{:.1} MB/s", bytes_per_sec / 1_048_576.0)
+    } else {
+        format!("{:.0} KB/s", bytes_per_sec / 1024.0)
+    }
+}
+
+/// `0:05`, `12:30`, `1:02:03`
+pub fn format_eta(seconds: u64) -> String {
+    let (hours, minutes, secs) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, secs)
+    } else {
+        format!("{}:{:02}", minutes, secs)
+    }
+}
+
+/// Joins whatever is known of speed and ETA, e.g. `2.1 MB/s • ETA 0:05`
+pub fn format_speed_eta(speed: Option<f64>, eta: Option<u64>) -> String {
+    let speed = speed.filter(|speed| *speed > 0.0).map(format_speed);
+    let eta = eta.map(|eta| format!("ETA {}", format_eta(eta)));
+    speed.into_iter().chain(eta).collect::<Vec<_>>().join(" • ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_speed_eta() {
+        assert_eq!(format_speed(512.0 * 1024.0), "512 KB/s");
+        assert_eq!(format_speed(2.5 * 1_048_576.0), "2.5 MB/s");
+        assert_eq!(format_eta(5), "0:05");
+        assert_eq!(format_eta(3723), "1:02:03");
+        assert_eq!(format_speed_eta(Some(2_097_152.0), Some(65)), "2.0 MB/s • ETA 1:05");
+        assert_eq!(format_speed_eta(None, Some(3)), "ETA 0:03");
+        assert_eq!(format_speed_eta(None, None), "");
+    }
+}
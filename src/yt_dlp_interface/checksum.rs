@@ -0,0 +1,225 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use anyhow::{anyhow, Result};
+use sha2::{Digest, Sha256};
+use tokio::fs;
+
+use crate::utils::proxy::http_client_builder;
+use crate::yt_dlp_interface::downloader::download_file;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChecksumKind {
+    Sha256,
+    Md5,
+}
+
+/// Where the publisher lists the expected checksum of a download
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChecksumSource {
+    /// URL of the checksum file
+    pub url: String,
+    pub kind: ChecksumKind,
+    /// Name the download is listed under in that file
+    pub file_name: String,
+}
+
+impl ChecksumSource {
+    /// Checksum file published next to `download_url`, e.g. `.../download/SHA2-256SUMS`
+    fn sibling(download_url: &str, checksum_file: &str, kind: ChecksumKind) -> Option<Self> {
+        let (base, file_name) = download_url.rsplit_once('/')?;
+        Some(Self {
+            url: format!("{}/{}", base, checksum_file),
+            kind,
+            file_name: file_name.to_string(),
+        })
+    }
+}
+
+/// Checksum file for the binaries the bot downloads, `None` for sources that publish none
+pub fn checksum_source_for(download_url: &str) -> Option<ChecksumSource> {
+    if download_url.contains("github.com/yt-dlp/yt-dlp/releases/") || download_url.contains("github.com/yt-dlp/yt-dlp-nightly-builds/releases/") {
+        ChecksumSource::sibling(download_url, "SHA2-256SUMS", ChecksumKind::Sha256)
+    } else if download_url.contains("github.com/BtbN/FFmpeg-Builds/releases/") {
+        ChecksumSource::sibling(download_url, "checksums.sha256", ChecksumKind::Sha256)
+    } else if download_url.contains("johnvansickle.com/ffmpeg/") {
+        // One `<md5>  <file>` line next to every archive
+        Some(ChecksumSource {
+            url: format!("{}.md5", download_url),
+            kind: ChecksumKind::Md5,
+            file_name: download_url.rsplit('/').next().unwrap_or_default().to_string(),
+        })
+    } else {
+        None
+    }
+}
+
+/// `(digest, file name)` pairs of a `sha256sum`/`md5sum` style listing
+fn checksum_entries(content: &str) -> impl Iterator<Item = (&str, &str)> {
+    content.lines().filter_map(|line| {
+        let (digest, name) = line.trim().split_once(char::is_whitespace)?;
+        // `*` marks binary mode in the coreutils format
+        Some((digest, name.trim().trim_start_matches('*')))
+    })
+}
+
+/// Finds the digest for `file_name` in a `sha256sum`/`md5sum` style listing
+pub fn parse_checksum_file(content: &str, file_name: &str) -> Option<String> {
+    checksum_entries(content)
+        .find(|(_, name)| *name == file_name || name.rsplit('/').next() == Some(file_name))
+        .map(|(digest, _)| digest.to_lowercase())
+}
+
+/// File names in a checksum listing, the assets of the release it belongs to
+pub fn parse_checksum_names(content: &str) -> impl Iterator<Item = &str> {
+    checksum_entries(content).map(|(_, name)| name.rsplit('/').next().unwrap_or(name))
+}
+
+/// Hex digest of a file on disk
+pub async fn file_digest(path: &Path, kind: ChecksumKind) -> Result<String> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || -> Result<String> {
+        let mut file = std::fs::File::open(&path)?;
+        let mut buffer = vec![0; 64 * 1024];
+        match kind {
+            ChecksumKind::Sha256 => {
+                let mut hasher = Sha256::new();
+                loop {
+                    let read = file.read(&mut buffer)?;
+                    if read == 0 {
+                        break;
+                    }
+                    hasher.update(&buffer[..read]);
+                }
+                Ok(format!("{:x}", hasher.finalize()))
+            }
+            ChecksumKind::Md5 => {
+                let mut context = md5::Context::new();
+                loop {
+                    let read = file.read(&mut buffer)?;
+                    if read == 0 {
+                        break;
+                    }
+                    context.consume(&buffer[..read]);
+                }
+                Ok(format!("{:x}", context.compute()))
+            }
+        }
+    }).await?
+}
+
+/// Checks a downloaded file against the publisher's checksum and returns its digest
+pub async fn verify_file(path: &Path, source: &ChecksumSource) -> Result<String> {
+    let response = http_client_builder()?.build()?.get(&source.url).send().await?;
+    if !response.status().is_success() {
+        return Err(anyhow!("Failed to fetch checksums from {}: HTTP status {}", source.url, response.status()));
+    }
+    let content = response.text().await?;
+    let expected = parse_checksum_file(&content, &source.file_name)
+        .ok_or_else(|| anyhow!("{} is not listed in {}", source.file_name, source.url))?;
+
+    let actual = file_digest(path, source.kind).await?;
+    if actual != expected {
+        return Err(anyhow!(
+            "Checksum mismatch for {}: expected {}, got {}",
+            source.file_name, expected, actual
+        ));
+    }
+    log::info!("Verified checksum of {} ({:?} {})", source.file_name, source.kind, actual);
+    Ok(actual)
+}
+
+/// Downloads `url` to `path` and verifies it, removing the file again on a mismatch.
+///
+/// Returns the verified digest, or `None` when the source publishes no checksums.
+pub async fn download_verified(url: &str, path: &PathBuf, source: Option<&ChecksumSource>) -> Result<Option<String>> {
+    download_file(url, path).await?;
+
+    let Some(source) = source else {
+        log::warn!("No published checksum for {}, installing it unverified", url);
+        return Ok(None);
+    };
+    match verify_file(path, source).await {
+        Ok(digest) => Ok(Some(digest)),
+        Err(e) => {
+            log::error!("Refusing to install {}: {}", url, e);
+            fs::remove_file(path).await.ok();
+            Err(e)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serves `files` as `/name` over plain HTTP until the test ends
+    async fn serve(files: Vec<(&'static str, Vec<u8>)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut socket, _)) = listener.accept().await else { return };
+                let files = files.clone();
+                tokio::spawn(async move {
+                    let mut request = vec![0; 4096];
+                    let read = socket.read(&mut request).await.unwrap_or(0);
+                    let request = String::from_utf8_lossy(&request[..read]);
+                    let path = request.split_whitespace().nth(1).unwrap_or("/").trim_start_matches('/').to_string();
+                    let response = match files.iter().find(|(name, _)| *name == path) {
+                        Some((_, body)) => [format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", body.len()).into_bytes(), body.clone()].concat(),
+                        None => b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec(),
+                    };
+                    let _ = socket.write_all(&response).await;
+                });
+            }
+        });
+        format!("http://{}", address)
+    }
+
+    #[test]
+    fn test_checksum_sources_and_parsing() {
+        let source = checksum_source_for("https://github.com/yt-dlp/yt-dlp/releases/latest/download/yt-dlp_linux").unwrap();
+        assert_eq!(source.url, "https://github.com/yt-dlp/yt-dlp/releases/latest/download/SHA2-256SUMS");
+        assert_eq!(source.file_name, "yt-dlp_linux");
+
+        let source = checksum_source_for("https://johnvansickle.com/ffmpeg/builds/ffmpeg-git-amd64-static.tar.xz").unwrap();
+        assert_eq!(source.url, "https://johnvansickle.com/ffmpeg/builds/ffmpeg-git-amd64-static.tar.xz.md5");
+        assert_eq!(source.kind, ChecksumKind::Md5);
+        assert_eq!(checksum_source_for("https://evermeet.cx/ffmpeg/get/ffmpeg/7z"), None);
+
+        let sums = "ABC123  yt-dlp\ndef456 *yt-dlp_linux\n789abc  ffmpeg-git-20240101-amd64-static/ffmpeg-git-amd64-static.tar.xz\n";
+        assert_eq!(parse_checksum_file(sums, "yt-dlp"), Some("abc123".to_string()));
+        assert_eq!(parse_checksum_file(sums, "yt-dlp_linux"), Some("def456".to_string()));
+        assert_eq!(parse_checksum_file(sums, "ffmpeg-git-amd64-static.tar.xz"), Some("789abc".to_string()));
+        assert_eq!(parse_checksum_file(sums, "yt-dlp.exe"), None);
+    }
+
+    #[tokio::test]
+    async fn test_download_verified_against_local_server() {
+        let archive = b"fixture archive contents".to_vec();
+        let sha256 = format!("{:x}", Sha256::digest(&archive));
+        let md5 = format!("{:x}", md5::compute(&archive));
+        let base = serve(vec![
+            ("archive.tar.xz", archive.clone()),
+            ("checksums.sha256", format!("{}  archive.tar.xz\n", sha256).into_bytes()),
+            ("archive.tar.xz.md5", format!("{}  archive.tar.xz\n", md5).into_bytes()),
+            ("bad.sha256", format!("{}  archive.tar.xz\n", "0".repeat(64)).into_bytes()),
+        ]).await;
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("archive.tar.xz");
+        let url = format!("{}/archive.tar.xz", base);
+        let source = |file: &str, kind| ChecksumSource { url: format!("{}/{}", base, file), kind, file_name: "archive.tar.xz".to_string() };
+
+        let digest = download_verified(&url, &path, Some(&source("checksums.sha256", ChecksumKind::Sha256))).await.unwrap();
+        assert_eq!(digest, Some(sha256));
+        let digest = download_verified(&url, &path, Some(&source("archive.tar.xz.md5", ChecksumKind::Md5))).await.unwrap();
+        assert_eq!(digest, Some(md5));
+
+        // A mismatch refuses the download and leaves nothing behind
+        let result = download_verified(&url, &path, Some(&source("bad.sha256", ChecksumKind::Sha256))).await;
+        assert!(result.unwrap_err().to_string().contains("Checksum mismatch"));
+        assert!(!path.exists());
+    }
+}
@@ -0,0 +1,176 @@
+use anyhow::{anyhow, Result};
+
+/// Part of a video requested by the user, in seconds
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClipRange {
+    pub start: f64,
+    /// `None` means until the end of the video
+    pub end: Option<f64>,
+}
+
+impl ClipRange {
+    /// yt-dlp `--download-sections` arguments.
+    ///
+    /// Without `precise` the cut snaps to the nearest keyframes, which needs no re-encode but may
+    /// start a few seconds early. `--force-keyframes-at-cuts` re-encodes around the cut points instead.
+    pub fn download_sections_args(&self, precise: bool) -> Vec<String> {
+        let end = self.end.map(|end| end.to_string()).unwrap_or_else(|| "inf".to_string());
+        let mut args = vec![
+            "--download-sections".to_string(),
+            format!("*{}-{}", self.start, end),
+        ];
+        if precise {
+            args.push("--force-keyframes-at-cuts".to_string());
+        }
+        args
+    }
+
+    /// Seconds the clip covers of a video `duration` seconds long
+    pub fn length(&self, duration: Option<f64>) -> Option<f64> {
+        let end = match (self.end, duration) {
+            (Some(end), Some(duration)) => end.min(duration),
+            (Some(end), None) => end,
+            (None, duration) => duration?,
+        };
+        Some((end - self.start).max(0.0))
+    }
+
+    /// Human readable range for captions, e.g. `1:20-2:05`
+    pub fn label(&self) -> String {
+        match self.end {
+            Some(end) => format!("{}-{}", format_timestamp(self.start), format_timestamp(end)),
+            None => format!("from {}", format_timestamp(self.start)),
+        }
+    }
+}
+
+pub fn format_timestamp(seconds: f64) -> String {
+    let total = seconds as u64;
+    let (hours, minutes, secs) = (total / 3600, total / 60 % 60, total % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, secs)
+    } else {
+        format!("{}:{:02}", minutes, secs)
+    }
+}
+
+/// Parses `80`, `1:20`, `1:02:05` or `1:20.5` into seconds
+fn parse_timestamp(value: &str) -> Option<f64> {
+    let mut seconds = 0.0;
+    let parts: Vec<&str> = value.split(':').collect();
+    if parts.len() > 3 {
+        return None;
+    }
+    for (index, part) in parts.iter().enumerate() {
+        let is_last = index + 1 == parts.len();
+        let number: f64 = part.parse().ok()?;
+        if number < 0.0 || !number.is_finite() || (!is_last && part.contains('.')) {
+            return None;
+        }
+        seconds = seconds * 60.0 + number;
+    }
+    Some(seconds)
+}
+
+/// Parses the `t=` URL parameter: `80`, `80s` or `1h2m3s`
+fn parse_t_param(value: &str) -> Option<f64> {
+    if let Ok(seconds) = value.trim_end_matches('s').parse::<f64>() {
+        return (seconds >= 0.0).then_some(seconds);
+    }
+
+    let mut seconds = 0.0;
+    let mut number = String::new();
+    for c in value.chars() {
+        match c {
+            '0'..='9' | '.' => number.push(c),
+            'h' | 'm' | 's' => {
+                let multiplier = match c {
+                    'h' => 3600.0,
+                    'm' => 60.0,
+                    _ => 1.0,
+                };
+                seconds += number.parse::<f64>().ok()? * multiplier;
+                number.clear();
+            }
+            _ => return None,
+        }
+    }
+    number.is_empty().then_some(seconds)
+}
+
+/// `1:20-2:05`, `1:20-` (until the end) or `-2:05` (from the start)
+fn parse_range(token: &str) -> Option<Result<ClipRange>> {
+    let (start, end) = token.split_once('-')?;
+    let start = if start.is_empty() { Some(0.0) } else { parse_timestamp(start) };
+    let end = if end.is_empty() { Some(None) } else { parse_timestamp(end).map(Some) };
+    let (start, end) = (start?, end?);
+    if end.is_none() && start == 0.0 {
+        return None;
+    }
+
+    Some(match end {
+        Some(end) if end <= start => Err(anyhow!("the end of {} is not after its start", token)),
+        _ => Ok(ClipRange { start, end }),
+    })
+}
+
+/// Splits a message into the link and an optional time range.
+///
+/// The range comes from a separate token after the link (`<url> 1:20-2:05`) or, failing that,
+/// from a `t=` parameter of the link itself, which only sets the start.
+pub fn parse_clip_request(text: &str) -> Result<(&str, Option<ClipRange>)> {
+    let tokens: Vec<&str> = text.split_whitespace().collect();
+    let url = tokens
+        .iter()
+        .find(|token| token.contains("://") || token.contains("tiktok.com"))
+        .copied()
+        .unwrap_or(text.trim());
+
+    for token in tokens.iter().filter(|token| **token != url) {
+        if let Some(range) = parse_range(token) {
+            return Ok((url, Some(range.map_err(|e| anyhow!("Invalid time range: {}", e))?)));
+        }
+    }
+
+    let start = url
+        .split_once('?')
+        .map(|(_, query)| query.split(['&', '#']))
+        .into_iter()
+        .flatten()
+        .find_map(|param| param.strip_prefix("t="))
+        .and_then(parse_t_param)
+        .filter(|start| *start > 0.0);
+
+    Ok((url, start.map(|start| ClipRange { start, end: None })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_clip_request_with_range() {
+        let (url, clip) = parse_clip_request("https://www.tiktok.com/@user/video/1 1:20-2:05").unwrap();
+        assert_eq!(url, "https://www.tiktok.com/@user/video/1");
+        assert_eq!(clip, Some(ClipRange { start: 80.0, end: Some(125.0) }));
+        assert_eq!(clip.unwrap().label(), "1:20-2:05");
+        assert_eq!(clip.unwrap().download_sections_args(false), vec!["--download-sections", "*80-125"]);
+
+        let (_, clip) = parse_clip_request("https://youtu.be/x 1:00:00-").unwrap();
+        assert_eq!(clip, Some(ClipRange { start: 3600.0, end: None }));
+        assert_eq!(clip.unwrap().download_sections_args(true), vec!["--download-sections", "*3600-inf", "--force-keyframes-at-cuts"]);
+
+        assert!(parse_clip_request("https://youtu.be/x 2:05-1:20").is_err());
+    }
+
+    #[test]
+    fn test_parse_clip_request_with_t_param() {
+        let (url, clip) = parse_clip_request("https://youtu.be/x?si=abc&t=1m20s").unwrap();
+        assert_eq!(url, "https://youtu.be/x?si=abc&t=1m20s");
+        assert_eq!(clip, Some(ClipRange { start: 80.0, end: None }));
+
+        assert_eq!(parse_clip_request("https://youtu.be/x?t=95").unwrap().1.map(|c| c.start), Some(95.0));
+        assert_eq!(parse_clip_request("https://www.tiktok.com/@user/video/1").unwrap().1, None);
+        assert_eq!(parse_clip_request("https://youtu.be/x?t=0").unwrap().1, None);
+    }
+}
@@ -0,0 +1,265 @@
+use std::path::{Path, PathBuf};
+use anyhow::{anyhow, Result};
+use chrono::{Local, TimeZone};
+use tokio::fs;
+
+/// Largest cookies.txt accepted from an admin
+pub const MAX_COOKIE_FILE_SIZE: u32 = 1024 * 1024;
+
+/// What a validated cookies.txt contains
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CookieJarInfo {
+    pub cookies: usize,
+    /// Cookies already past their expiry
+    pub expired: usize,
+    /// Earliest expiry of the cookies that are still valid, `None` if all are session cookies
+    pub expires_at: Option<i64>,
+}
+
+impl CookieJarInfo {
+    pub fn expires_label(&self) -> String {
+        match self.expires_at.and_then(|at| Local.timestamp_opt(at, 0).single()) {
+            Some(at) => at.format("%Y-%m-%d %H:%M").to_string(),
+            None => "with the session".to_string(),
+        }
+    }
+
+    /// Whether a cookie runs out within `seconds` of `now`
+    pub fn expires_within(&self, now: i64, seconds: i64) -> bool {
+        self.expires_at.is_some_and(|at| at - now <= seconds)
+    }
+}
+
+/// Checks a Netscape cookies.txt (the format yt-dlp's `--cookies` reads) for `platform`.
+///
+/// Rejects files without a cookie for the platform's domain or whose cookies all expired.
+pub fn validate_cookie_file(content: &str, platform: &str, now: i64) -> Result<CookieJarInfo> {
+    let mut info = CookieJarInfo { cookies: 0, expired: 0, expires_at: None };
+    let mut platform_cookies = 0;
+    // Platforms named after an old domain keep their cookies under the new one
+    let domains: &[&str] = match platform {
+        "twitter" => &["twitter.", "x.com"],
+        "facebook" => &["facebook.", "fb."],
+        _ => &[platform],
+    };
+
+    for (number, line) in content.lines().enumerate() {
+        // `#HttpOnly_` marks a cookie, any other `#` line is a comment
+        let line = line.trim_end_matches('\r');
+        let line = line.strip_prefix("#HttpOnly_").unwrap_or(line);
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() != 7 {
+            return Err(anyhow!("Line {} is not a Netscape cookie (7 tab-separated fields expected)", number + 1));
+        }
+        let expires: i64 = fields[4].parse()
+            .map_err(|_| anyhow!("Line {} has an invalid expiry {:?}", number + 1, fields[4]))?;
+
+        info.cookies += 1;
+        let domain = fields[0].to_lowercase();
+        if domains.iter().any(|name| domain.contains(name)) {
+            platform_cookies += 1;
+        }
+        if expires > 0 && expires <= now {
+            info.expired += 1;
+        } else if expires > 0 {
+            info.expires_at = Some(info.expires_at.map_or(expires, |at| at.min(expires)));
+        }
+    }
+
+    if info.cookies == 0 {
+        return Err(anyhow!("No cookies found, export them in the Netscape cookies.txt format"));
+    }
+    if platform_cookies == 0 {
+        return Err(anyhow!("None of the cookies belong to {}", platform));
+    }
+    if info.expired == info.cookies {
+        return Err(anyhow!("All {} cookies have expired", info.cookies));
+    }
+    Ok(info)
+}
+
+/// Per-platform cookies.txt files passed to yt-dlp, readable only by the bot's user
+#[derive(Debug, Clone)]
+pub struct CookieJars {
+    dir: PathBuf,
+}
+
+impl CookieJars {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn path(&self, platform: &str) -> PathBuf {
+        self.dir.join(format!("{}.txt", platform))
+    }
+
+    /// cookies.txt for `platform`, if an admin uploaded one
+    pub fn jar_for(&self, platform: &str) -> Option<PathBuf> {
+        let path = self.path(platform);
+        path.is_file().then_some(path)
+    }
+
+    /// Private copy of `platform`'s jar for one yt-dlp run, `None` if there is no jar.
+    ///
+    /// yt-dlp writes the jar back when it exits, so concurrent runs never get the stored file itself.
+    pub async fn checkout(&self, platform: &str) -> Result<Option<JarCopy>> {
+        let Some(jar) = self.jar_for(platform) else { return Ok(None) };
+        // Dot-prefixed like the staged uploads, so `list` skips it
+        let path = self.dir.join(format!(".{}-{}.txt", platform, uuid::Uuid::new_v4().simple()));
+        let copy = JarCopy { path };
+        fs::copy(&jar, &copy.path).await?;
+        restrict_permissions(&copy.path, 0o600).await?;
+        Ok(Some(copy))
+    }
+
+    /// Validates and stores `content` as the jar of `platform`, replacing the previous one
+    pub async fn store(&self, platform: &str, content: &str) -> Result<CookieJarInfo> {
+        if platform.is_empty() || !platform.chars().all(|c| c.is_ascii_alphanumeric()) {
+            return Err(anyhow!("Invalid platform name {:?}", platform));
+        }
+        let info = validate_cookie_file(content, platform, Local::now().timestamp())?;
+
+        fs::create_dir_all(&self.dir).await?;
+        restrict_permissions(&self.dir, 0o700).await?;
+        // Written under a temporary name so a running yt-dlp never reads half a jar
+        let staged = self.dir.join(format!(".{}.txt.new", platform));
+        fs::write(&staged, content).await?;
+        restrict_permissions(&staged, 0o600).await?;
+        fs::rename(&staged, self.path(platform)).await?;
+        log::info!("Stored {} cookies for {}, earliest expiry {}", info.cookies, platform, info.expires_label());
+        Ok(info)
+    }
+
+    /// Platforms with a jar and what is in them, for reports and expiry warnings
+    pub async fn list(&self) -> Vec<(String, Result<CookieJarInfo>)> {
+        let mut jars = Vec::new();
+        let Ok(mut entries) = fs::read_dir(&self.dir).await else { return jars };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            let path = entry.path();
+            let Some(platform) = path.file_stem().and_then(|stem| stem.to_str()).map(str::to_string) else { continue };
+            if platform.starts_with('.') || path.extension().is_none_or(|extension| extension != "txt") {
+                continue;
+            }
+            // yt-dlp only ever gets copies, so this is the jar as uploaded
+            let info = match fs::read_to_string(&path).await {
+                Ok(content) => validate_cookie_file(&content, &platform, Local::now().timestamp()),
+                Err(e) => Err(e.into()),
+            };
+            jars.push((platform, info));
+        }
+        jars.sort_by(|a, b| a.0.cmp(&b.0));
+        jars
+    }
+
+    /// Warnings about jars that stopped working or run out within `within_seconds`
+    pub async fn expiry_warnings(&self, within_seconds: i64) -> Vec<String> {
+        let now = Local::now().timestamp();
+        self.list().await
+            .into_iter()
+            .filter_map(|(platform, info)| match info {
+                Ok(info) if info.expires_within(now, within_seconds) => Some(format!(
+                    "🍪 Cookies for {} expire {}, upload fresh ones with /setcookies {}",
+                    platform, info.expires_label(), platform
+                )),
+                Ok(_) => None,
+                Err(e) => Some(format!("🍪 Cookies for {} are no longer usable ({}), upload fresh ones with /setcookies {}", platform, e, platform)),
+            })
+            .collect()
+    }
+}
+
+/// A jar copied for one yt-dlp run, deleted when dropped
+#[derive(Debug)]
+pub struct JarCopy {
+    path: PathBuf,
+}
+
+impl JarCopy {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for JarCopy {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(unix)]
+async fn restrict_permissions(path: &Path, mode: u32) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, std::fs::Permissions::from_mode(mode)).await?;
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn restrict_permissions(_path: &Path, _mode: u32) -> Result<()> {
+    Ok(())
+}
+
+/// When the earliest cookie of a jar expires, as shown to admins
+pub fn format_expiry(info: &CookieJarInfo) -> String {
+    let expired = if info.expired > 0 { format!(", {} already expired", info.expired) } else { String::new() };
+    format!("{} cookies, earliest expiry {}{}", info.cookies, info.expires_label(), expired)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NOW: i64 = 1_750_000_000;
+
+    #[test]
+    fn test_validate_cookie_file() {
+        let jar = "# Netscape HTTP Cookie File\n\
+            .tiktok.com\tTRUE\t/\tTRUE\t1760000000\tsessionid\tabc\n\
+            #HttpOnly_.tiktok.com\tTRUE\t/\tTRUE\t1755000000\tsid_tt\tdef\n\
+            .tiktok.com\tTRUE\t/\tFALSE\t1700000000\told\tghi\n\
+            .tiktok.com\tTRUE\t/\tFALSE\t0\tsession\tjkl\n";
+        let info = validate_cookie_file(jar, "tiktok", NOW).unwrap();
+        assert_eq!(info, CookieJarInfo { cookies: 4, expired: 1, expires_at: Some(1755000000) });
+        assert!(info.expires_within(NOW, 60 * 24 * 3600));
+        assert!(!info.expires_within(NOW, 7 * 24 * 3600));
+
+        assert!(validate_cookie_file(jar, "instagram", NOW).unwrap_err().to_string().contains("belong to instagram"));
+        assert!(validate_cookie_file("sessionid=abc; path=/", "tiktok", NOW).is_err());
+        assert!(validate_cookie_file(".tiktok.com\tTRUE\t/\tTRUE\t1700000000\tsessionid\tabc", "tiktok", NOW).unwrap_err().to_string().contains("expired"));
+    }
+
+    #[tokio::test]
+    async fn test_store_and_list_jars() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let jars = CookieJars::new(dir.path().join("cookies"));
+        let jar = ".instagram.com\tTRUE\t/\tTRUE\t4102444800\tsessionid\tabc\n";
+
+        assert!(jars.store("../etc", jar).await.is_err());
+        jars.store("instagram", jar).await.unwrap();
+        assert_eq!(jars.jar_for("instagram"), Some(dir.path().join("cookies").join("instagram.txt")));
+        assert_eq!(jars.jar_for("tiktok"), None);
+
+        let copy = jars.checkout("instagram").await.unwrap().unwrap();
+        assert_eq!(std::fs::read_to_string(copy.path()).unwrap(), jar);
+        assert!(jars.checkout("tiktok").await.unwrap().is_none());
+
+        let listed = jars.list().await;
+        assert_eq!(listed.len(), 1);
+        assert_eq!(listed[0].0, "instagram");
+        assert!(jars.expiry_warnings(24 * 3600).await.is_empty());
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(dir.path().join("cookies").join("instagram.txt")).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+            assert_eq!(std::fs::metadata(copy.path()).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+
+        let copy_path = copy.path().to_path_buf();
+        drop(copy);
+        assert!(!copy_path.exists());
+    }
+}
@@ -1,49 +1,133 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use reqwest::StatusCode;
+use reqwest::header::{CONTENT_RANGE, RANGE};
 use tokio::fs;
-use tokio::io;
+use tokio::io::AsyncWriteExt;
 use zip::ZipArchive;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+
+use crate::utils::proxy::http_client_builder;
+use crate::utils::retry::{RetryDecision, RetryPolicy};
 
 #[cfg(target_os = "macos")]
 use sevenz_rust::decompress_file as decompress_7z;
 
-pub async fn download_file(url: &str, path: &PathBuf) -> Result<()> {
+/// Binaries are tens of MB from GitHub / johnvansickle; interrupted transfers resume where they stopped
+const BINARY_DOWNLOAD_RETRY: RetryPolicy = RetryPolicy::new(5).with_deadline(Duration::from_secs(30 * 60));
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+/// A stalled transfer fails after this long without a byte and is resumed by the next attempt
+const READ_TIMEOUT: Duration = Duration::from_secs(60);
+/// Progress is logged this often when the size isn't known in advance
+const PROGRESS_LOG_BYTES: u64 = 10 * 1024 * 1024;
+
+/// `<file>.partial`, where a download collects until it is complete
+pub fn partial_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".partial");
+    path.with_file_name(name)
+}
+
+/// Downloads `url` to `path`, resuming interrupted attempts with HTTP Range requests. `path` only appears once the
+/// whole body arrived and matches the announced size, so a cut-off download is never mistaken
+/// for a binary.
+pub async fn download_file(url: &str, path: &Path) -> Result<()> {
+    download_file_with(url, path, &BINARY_DOWNLOAD_RETRY).await
+}
+
+async fn download_file_with(url: &str, path: &Path, retry: &RetryPolicy) -> Result<()> {
     log::info!("Downloading from {} to {:?}", url, path);
-    
-    let client = reqwest::Client::new();
-    let mut response = client.get(url).send().await.map_err(|e| {
-        log::error!("Failed to send GET request to {}: {:?}", url, e);
-        anyhow::anyhow!("Failed to send GET request to {}: {:?}", url, e)
-    })?;
-    
-    if !response.status().is_success() {
-        log::error!("Download failed for {}: HTTP status {}", url, response.status());
-        return Err(anyhow::anyhow!("Download failed for {}: HTTP status {}", url, response.status()));
-    }
+    let client = http_client_builder()?
+        .connect_timeout(CONNECT_TIMEOUT)
+        .read_timeout(READ_TIMEOUT)
+        .build()?;
+    let partial = partial_path(path);
 
-    let mut file = fs::File::create(path).await.map_err(|e| {
-        log::error!("Failed to create file {:?}: {:?}", path, e);
-        anyhow::anyhow!("Failed to create file {:?}: {:?}", path, e)
-    })?;
-    
-    // Read the response body in chunks and write to the file
-    while let Some(chunk) = response.chunk().await.map_err(|e| {
-        log::error!("Failed to read chunk from response for {}: {:?}", url, e);
-        anyhow::anyhow!("Failed to read chunk from response for {}: {:?}", url, e)
-    })? {
-        io::copy(&mut chunk.as_ref(), &mut file).await.map_err(|e| {
-            log::error!("Failed to write chunk to file {:?}: {:?}", path, e);
-            anyhow::anyhow!("Failed to write chunk to file {:?}: {:?}", path, e)
-        })?;
+    // A leftover from an earlier run may belong to an older release, never resume it
+    fs::remove_file(&partial).await.ok();
+    if let Err(e) = retry.run(|| download_attempt(&client, url, &partial), classify_download_error).await {
+        fs::remove_file(&partial).await.ok();
+        return Err(e.context(format!("Failed to download {}", url)));
     }
-    
+
+    fs::rename(&partial, path).await.map_err(|e| anyhow!("Failed to move {:?} to {:?}: {}", partial, path, e))?;
     log::info!("Download completed successfully to {:?}", path);
     Ok(())
 }
 
-pub async fn extract_ffmpeg_windows(zip_path: &PathBuf, extract_to: &PathBuf) -> Result<()> {
-    let zip_path = zip_path.clone();
-    let extract_to = extract_to.clone();
+/// Client errors (a wrong URL) won't go away; timeouts, resets and server errors might
+fn classify_download_error(error: &anyhow::Error) -> RetryDecision {
+    match error.downcast_ref::<reqwest::Error>().and_then(reqwest::Error::status) {
+        Some(status) if status.is_client_error()
+            && status != StatusCode::REQUEST_TIMEOUT
+            && status != StatusCode::TOO_MANY_REQUESTS
+            && status != StatusCode::RANGE_NOT_SATISFIABLE => RetryDecision::Fail,
+        _ => RetryDecision::Retry,
+    }
+}
+
+/// Fetches what is missing from `partial`, continuing it when the server honours the Range
+async fn download_attempt(client: &reqwest::Client, url: &str, partial: &Path) -> Result<()> {
+    let resume_from = fs::metadata(partial).await.map(|metadata| metadata.len()).unwrap_or(0);
+    let mut request = client.get(url);
+    if resume_from > 0 {
+        request = request.header(RANGE, format!("bytes={}-", resume_from));
+    }
+    let mut response = request.send().await?;
+
+    if response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+        // The file changed upstream or the partial is bogus, start over next time
+        fs::remove_file(partial).await.ok();
+    }
+    let response_status = response.status();
+    response = response.error_for_status()?;
+
+    let resumed = resume_from > 0 && response_status == StatusCode::PARTIAL_CONTENT;
+    let (mut written, total) = if resumed {
+        let total = response.headers().get(CONTENT_RANGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.rsplit('/').next())
+            .and_then(|total| total.parse::<u64>().ok());
+        log::info!("Resuming {} at {} bytes", url, resume_from);
+        (resume_from, total)
+    } else {
+        (0, response.content_length())
+    };
+
+    let mut file = if resumed {
+        fs::OpenOptions::new().append(true).open(partial).await?
+    } else {
+        fs::File::create(partial).await?
+    };
+
+    let mut logged = written;
+    while let Some(chunk) = response.chunk().await? {
+        file.write_all(&chunk).await?;
+        written += chunk.len() as u64;
+        let step = total.map_or(PROGRESS_LOG_BYTES, |total| (total / 10).max(1));
+        if written - logged >= step {
+            logged = written;
+            match total {
+                Some(total) => log::info!("Downloaded {}% of {} ({} / {} bytes)", written * 100 / total.max(1), url, written, total),
+                None => log::info!("Downloaded {} bytes of {}", written, url),
+            }
+        }
+    }
+    file.flush().await?;
+
+    match total {
+        Some(total) if written < total => Err(anyhow!("Download of {} stopped at {} of {} bytes", url, written, total)),
+        Some(total) if written > total => {
+            fs::remove_file(partial).await.ok();
+            Err(anyhow!("Download of {} is {} bytes, more than the announced {}", url, written, total))
+        }
+        _ => Ok(()),
+    }
+}
+
+pub async fn extract_ffmpeg_windows(zip_path: &Path, extract_to: &Path) -> Result<()> {
+    let zip_path = zip_path.to_path_buf();
+    let extract_to = extract_to.to_path_buf();
 
     tokio::task::spawn_blocking(move || -> Result<()> {
         let file = std::fs::File::open(&zip_path)?;
@@ -81,12 +165,12 @@ pub async fn extract_ffmpeg_windows(zip_path: &PathBuf, extract_to: &PathBuf) ->
 }
 
 #[cfg(target_os = "macos")]
-pub async fn extract_ffmpeg_macos(archive_path: &PathBuf, extract_to: &PathBuf) -> Result<()> {
+pub async fn extract_ffmpeg_macos(archive_path: &Path, extract_to: &Path) -> Result<()> {
     use tokio::fs;
     fs::create_dir_all(extract_to).await?;
 
-    let archive_path = archive_path.clone();
-    let extract_to = extract_to.clone();
+    let archive_path = archive_path.to_path_buf();
+    let extract_to = extract_to.to_path_buf();
 
     tokio::task::spawn_blocking(move || -> Result<()> {
         let archive_result = decompress_7z(archive_path.as_path(), extract_to.as_path());
@@ -163,12 +247,12 @@ pub async fn extract_ffmpeg_macos(archive_path: &PathBuf, extract_to: &PathBuf)
 }
 
 #[cfg(all(unix, not(target_os = "macos")))]
-pub async fn extract_ffmpeg_unix(archive_path: &PathBuf, extract_to: &PathBuf) -> Result<()> {
+pub async fn extract_ffmpeg_unix(archive_path: &Path, extract_to: &Path) -> Result<()> {
     use tokio::fs;
     fs::create_dir_all(extract_to).await?;
 
-    let archive_path = archive_path.clone();
-    let extract_to = extract_to.clone();
+    let archive_path = archive_path.to_path_buf();
+    let extract_to = extract_to.to_path_buf();
 
     tokio::task::spawn_blocking(move || -> Result<()> {
         use std::fs::File;
@@ -186,7 +270,7 @@ pub async fn extract_ffmpeg_unix(archive_path: &PathBuf, extract_to: &PathBuf) -
             let mut entry = entry?;
             let entry_path = entry.path()?;
             
-            if entry_path.file_name().map_or(false, |name| name == "ffmpeg") {
+            if entry_path.file_name().is_some_and(|name| name == "ffmpeg") {
                 let output_path = extract_to.join("ffmpeg");
                 let mut outfile = std::fs::File::create(&output_path)?;
                 std::io::copy(&mut entry, &mut outfile)?;
@@ -198,7 +282,7 @@ pub async fn extract_ffmpeg_unix(archive_path: &PathBuf, extract_to: &PathBuf) -
                 
                 log::info!("Extracted ffmpeg to {:?}", output_path);
                 ffmpeg_extracted = true;
-            } else if entry_path.file_name().map_or(false, |name| name == "ffprobe") {
+            } else if entry_path.file_name().is_some_and(|name| name == "ffprobe") {
                 let output_path = extract_to.join("ffprobe");
                 let mut outfile = std::fs::File::create(&output_path)?;
                 std::io::copy(&mut entry, &mut outfile)?;
@@ -228,4 +312,60 @@ pub async fn extract_ffmpeg_unix(archive_path: &PathBuf, extract_to: &PathBuf) -
     }).await??;
 
     Ok(())
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    const FAST_RETRY: RetryPolicy = RetryPolicy::new(3).with_backoff(Duration::from_millis(10), Duration::from_millis(10));
+
+    /// Serves `body` at `/file`, cutting the first full response off halfway and answering
+    /// Range requests with the rest; anything else is a 404
+    async fn serve_flaky(body: Vec<u8>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut socket, _)) = listener.accept().await else { return };
+                let mut request = vec![0; 4096];
+                let read = socket.read(&mut request).await.unwrap_or(0);
+                let request = String::from_utf8_lossy(&request[..read]).to_lowercase();
+                let range_start = request.lines()
+                    .find_map(|line| line.strip_prefix("range: bytes="))
+                    .and_then(|range| range.trim_end_matches('-').parse::<usize>().ok());
+                let response = match (request.starts_with("get /file "), range_start) {
+                    (true, Some(start)) => [
+                        format!("HTTP/1.1 206 Partial Content\r\nContent-Length: {}\r\nContent-Range: bytes {}-{}/{}\r\nConnection: close\r\n\r\n", body.len() - start, start, body.len() - 1, body.len()).into_bytes(),
+                        body[start..].to_vec(),
+                    ].concat(),
+                    (true, None) => [
+                        format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", body.len()).into_bytes(),
+                        body[..body.len() / 2].to_vec(),
+                    ].concat(),
+                    _ => b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec(),
+                };
+                let _ = socket.write_all(&response).await;
+            }
+        });
+        format!("http://{}", address)
+    }
+
+    #[tokio::test]
+    async fn test_interrupted_download_resumes_into_partial_file() {
+        let body: Vec<u8> = (0..100_000u32).flat_map(|i| i.to_le_bytes()).collect();
+        let base = serve_flaky(body.clone()).await;
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("ffmpeg.tar.xz");
+
+        download_file_with(&format!("{}/file", base), &path, &FAST_RETRY).await.unwrap();
+        assert_eq!(std::fs::read(&path).unwrap(), body);
+        assert!(!partial_path(&path).exists());
+
+        // A missing file isn't retried and leaves no binary behind
+        let missing = dir.path().join("missing");
+        assert!(download_file_with(&format!("{}/missing", base), &missing, &FAST_RETRY).await.is_err());
+        assert!(!missing.exists());
+    }
+}
@@ -3,8 +3,13 @@ use tokio::fs;
 use anyhow::Result;
 
 use crate::yt_dlp_interface::utils::is_executable_present;
-use crate::yt_dlp_interface::urls::{get_latest_yt_dlp_url, get_latest_ffmpeg_url};
-use crate::yt_dlp_interface::downloader::{download_file, extract_ffmpeg_windows};
+use crate::yt_dlp_interface::urls::{ffmpeg_pinned_url, ffmpeg_url, yt_dlp_url, Platform};
+use crate::yt_dlp_interface::downloader::extract_ffmpeg_windows;
+use crate::yt_dlp_interface::checksum::{checksum_source_for, download_verified, file_digest, ChecksumKind};
+use crate::auto_update::version_manager::VersionManager;
+use crate::auto_update::policy::UpdatePolicy;
+use crate::config::update_policy;
+use crate::yt_dlp_interface::toolchain::{validate_external_tools, Toolchain};
 
 #[cfg(target_os = "macos")]
 use crate::yt_dlp_interface::downloader::extract_ffmpeg_macos;
@@ -12,41 +17,68 @@ use crate::yt_dlp_interface::downloader::extract_ffmpeg_macos;
 #[cfg(all(unix, not(target_os = "macos")))]
 use crate::yt_dlp_interface::downloader::extract_ffmpeg_unix;
 
-pub async fn ensure_binaries(libraries_dir: &Path, output_dir: &Path) -> Result<()> {
+/// Downloads the managed tools of `toolchain` that are missing and validates the external ones
+pub async fn ensure_binaries(toolchain: &Toolchain, libraries_dir: &Path, output_dir: &Path) -> Result<()> {
     fs::create_dir_all(libraries_dir).await?;
     fs::create_dir_all(output_dir).await?;
     
-    let yt_dlp_path = libraries_dir.join(if cfg!(target_os = "windows") { "yt-dlp.exe" } else { "yt-dlp" });
+    let yt_dlp_path = toolchain.yt_dlp.clone();
     let ffmpeg_zip_path = libraries_dir.join("ffmpeg-release.zip");
-    let ffmpeg_dir_path = libraries_dir.join("ffmpeg");
-    let ffmpeg_path = ffmpeg_dir_path.join(if cfg!(target_os = "windows") { "ffmpeg.exe" } else { "ffmpeg" });
-    let ffprobe_path = ffmpeg_dir_path.join(if cfg!(target_os = "windows") { "ffprobe.exe" } else { "ffprobe" });
+    let ffmpeg_dir_path = toolchain.ffmpeg_dir();
+    let ffmpeg_path = toolchain.ffmpeg.clone();
+    let ffprobe_path = toolchain.ffprobe.clone();
+    let version_manager = VersionManager::new(libraries_dir.join(".versions"));
+
+    // Tools provided by the system or an explicit path are only checked, never downloaded
+    validate_external_tools(toolchain).await?;
 
     // Check and download/update yt-dlp
-    if !is_executable_present(&yt_dlp_path) {
+    if !toolchain.yt_dlp_managed {
+        log::info!("yt-dlp is provided externally at {:?}, not managing it", yt_dlp_path);
+    } else if !is_executable_present(&yt_dlp_path) {
         log::info!("yt-dlp not found, downloading latest version...");
-        let yt_dlp_url = get_latest_yt_dlp_url();
-        download_file(&yt_dlp_url, &yt_dlp_path).await?;
+        // A pinned version is installed right away, otherwise the latest of the channel
+        let policy = update_policy("yt-dlp");
+        let yt_dlp_url = match &policy {
+            UpdatePolicy::Pinned(version) => yt_dlp_url(&Platform::current(), policy.channel(), Some(version))?,
+            _ => yt_dlp_url(&Platform::current(), policy.channel(), None)?,
+        };
+        // Only a verified download ends up under the real name
+        let download_path = yt_dlp_path.with_extension("download");
+        let checksum = download_verified(&yt_dlp_url, &download_path, checksum_source_for(&yt_dlp_url).as_ref()).await?;
         
         #[cfg(unix)]
         {
             use std::os::unix::fs::PermissionsExt;
-            let mut perms = fs::metadata(&yt_dlp_path).await?.permissions();
+            let mut perms = fs::metadata(&download_path).await?.permissions();
             perms.set_mode(0o755);  // Make executable
-            fs::set_permissions(&yt_dlp_path, perms).await?;
+            fs::set_permissions(&download_path, perms).await?;
+        }
+        fs::rename(&download_path, &yt_dlp_path).await?;
+        if let Some(checksum) = checksum {
+            version_manager.save_checksum("yt-dlp", &checksum).await?;
         }
     } else {
         log::info!("yt-dlp already exists at {:?}", yt_dlp_path);
+        // The stored digest is the one the publisher listed, so a mismatch means the file changed since
+        let stored = version_manager.get_stored_checksum("yt-dlp").await.unwrap_or_default();
+        if !stored.is_empty() && file_digest(&yt_dlp_path, ChecksumKind::Sha256).await? != stored {
+            log::warn!("yt-dlp at {:?} no longer matches the checksum it was installed with", yt_dlp_path);
+        }
     }
 
     // Check and download/update ffmpeg and ffprobe
-    if !is_executable_present(&ffmpeg_path) || !is_executable_present(&ffprobe_path) {
+    if !toolchain.ffmpeg_managed {
+        log::info!("FFmpeg is provided externally at {:?}, not managing it", ffmpeg_path);
+    } else if !is_executable_present(&ffmpeg_path) || !is_executable_present(&ffprobe_path) {
         log::info!("FFmpeg or FFprobe not found, downloading latest version...");
         
         if cfg!(target_os = "windows") {
             // Download the zip file for Windows
-            let ffmpeg_url = get_latest_ffmpeg_url();
-            download_file(&ffmpeg_url, &ffmpeg_zip_path).await?;
+            let ffmpeg_url = latest_or_pinned_ffmpeg_url().await?;
+            if let Some(checksum) = download_verified(&ffmpeg_url, &ffmpeg_zip_path, checksum_source_for(&ffmpeg_url).as_ref()).await? {
+                version_manager.save_checksum("ffmpeg", &checksum).await?;
+            }
             
             // Extract ffmpeg.exe and ffprobe.exe from the zip file
             fs::create_dir_all(&ffmpeg_dir_path).await?;
@@ -76,11 +108,13 @@ pub async fn ensure_binaries(libraries_dir: &Path, output_dir: &Path) -> Result<
             // For non-Windows (Linux/macOS), we download and extract the appropriate archive
             log::info!("Downloading FFmpeg and FFprobe for non-Windows platform...");
             
-            let ffmpeg_url = get_latest_ffmpeg_url();
+            let ffmpeg_url = latest_or_pinned_ffmpeg_url().await?;
             if cfg!(target_os = "macos") {
                 // For macOS, download and extract 7z archive
                 let ffmpeg_archive_path = libraries_dir.join("ffmpeg.7z");
-                download_file(&ffmpeg_url, &ffmpeg_archive_path).await?;
+                if let Some(checksum) = download_verified(&ffmpeg_url, &ffmpeg_archive_path, checksum_source_for(&ffmpeg_url).as_ref()).await? {
+                    version_manager.save_checksum("ffmpeg", &checksum).await?;
+                }
                 
                 // Extract the archive
                 fs::create_dir_all(&ffmpeg_dir_path).await?;
@@ -92,7 +126,9 @@ pub async fn ensure_binaries(libraries_dir: &Path, output_dir: &Path) -> Result<
             } else {
                 // For Linux, download and extract tar.xz archive
                 let ffmpeg_archive_path = libraries_dir.join("ffmpeg.tar.xz");
-                download_file(&ffmpeg_url, &ffmpeg_archive_path).await?;
+                if let Some(checksum) = download_verified(&ffmpeg_url, &ffmpeg_archive_path, checksum_source_for(&ffmpeg_url).as_ref()).await? {
+                    version_manager.save_checksum("ffmpeg", &checksum).await?;
+                }
                 
                 // Extract the archive
                 fs::create_dir_all(&ffmpeg_dir_path).await?;
@@ -117,9 +153,16 @@ pub async fn ensure_binaries(libraries_dir: &Path, output_dir: &Path) -> Result<
     Ok(())
 }
 
+async fn latest_or_pinned_ffmpeg_url() -> Result<String> {
+    match update_policy("ffmpeg") {
+        UpdatePolicy::Pinned(version) => ffmpeg_pinned_url(&Platform::current(), &version),
+        policy => ffmpeg_url(&Platform::current(), policy.channel()).await,
+    }
+}
+
 // Helper function to find ffmpeg.exe in the extracted directory structure
-pub async fn find_binary_in_extracted_dir(base_dir: &PathBuf, binary_name: &str) -> Option<PathBuf> {
-    let mut stack = vec![base_dir.clone()];
+pub async fn find_binary_in_extracted_dir(base_dir: &Path, binary_name: &str) -> Option<PathBuf> {
+    let mut stack = vec![base_dir.to_path_buf()];
     
     while let Some(current_dir) = stack.pop() {
         if let Ok(mut entries) = tokio::fs::read_dir(&current_dir).await {
@@ -128,7 +171,7 @@ pub async fn find_binary_in_extracted_dir(base_dir: &PathBuf, binary_name: &str)
                     let path = entry.path();
                     
                     if path.is_file() && 
-                       path.file_name().map_or(false, |name| name == binary_name) {
+                       path.file_name().is_some_and(|name| name == binary_name) {
                         return Some(path);
                     } else if path.is_dir() {
                         stack.push(path);
@@ -1,32 +1,76 @@
 use std::path::PathBuf;
+use std::sync::Arc;
 use tokio::process::Command;
 use tokio::io::{BufReader, AsyncBufReadExt};
 use anyhow::Result;
 use regex::Regex;
 
-use crate::utils::progress_bar::ProgressBar;
+use crate::utils::progress_sink::ProgressSink;
+use crate::utils::transfer_rate::format_speed_eta;
+use crate::media::audio_format::AudioFormat;
+use crate::config::precise_clip_cuts;
+use crate::utils::proxy::{platform_of, proxies};
+use crate::config::DownloadLimits;
+use crate::yt_dlp_interface::cookies::{CookieJars, JarCopy};
+use crate::yt_dlp_interface::probe::{check_limits, ProbedVideo};
+use crate::yt_dlp_interface::clip::ClipRange;
 
 #[derive(Clone)]
 pub struct YoutubeFetcher {
     pub yt_dlp_path: PathBuf,
     pub output_dir: PathBuf,
     pub ffmpeg_dir: PathBuf,
+    pub cookie_jars: CookieJars,
+    pub limits: DownloadLimits,
 }
 
 impl YoutubeFetcher {
-    pub fn new(yt_dlp_path: PathBuf, output_dir: PathBuf, ffmpeg_dir: PathBuf) -> Result<Self> {
+    pub fn new(yt_dlp_path: PathBuf, output_dir: PathBuf, ffmpeg_dir: PathBuf, cookie_jars: CookieJars, limits: DownloadLimits) -> Result<Self> {
         Ok(YoutubeFetcher {
             yt_dlp_path,
             output_dir,
             ffmpeg_dir,
+            cookie_jars,
+            limits,
         })
     }
 
-pub async fn download_video_from_url(&self,url: String,filename_stem: &str,quality: &str,progress_bar: &mut ProgressBar) -> Result<std::path::PathBuf> {
+    /// `--proxy` and `--cookies` for the platform `url` belongs to
+    pub(crate) async fn access_args(&self, url: &str) -> Result<AccessArgs> {
+        let mut args = Vec::new();
+        if let Some(proxy) = proxies().for_url(url) {
+            args.push("--proxy".into());
+            args.push(proxy.into());
+        }
+        let jar = self.cookie_jars.checkout(&platform_of(url)).await?;
+        if let Some(jar) = &jar {
+            args.push("--cookies".into());
+            args.push(jar.path().into());
+        }
+        Ok(AccessArgs { args, _jar: jar })
+    }
+
+/// Downloads `url`; `probed` skips the metadata probe when the link was just probed, e.g. for the format picker
+#[allow(clippy::too_many_arguments)]
+pub async fn download_video_from_url(&self,url: String,filename_stem: &str,quality: &str,max_height: Option<u32>,audio_format: AudioFormat,clip: Option<ClipRange>,probed: Option<Arc<ProbedVideo>>,progress_bar: &mut dyn ProgressSink) -> Result<DownloadedMedia> {
         log::info!("Starting download for URL: {}", url);
         let start_time = std::time::Instant::now();
 
-        let output_template = if quality == "audio" {
+        // Long videos, huge files and live streams are refused before anything is downloaded
+        progress_bar.update(3, Some("🔎 Checking the video...")).await?;
+        let probed = match probed {
+            Some(probed) => probed,
+            None => Arc::new(self.probe_video(&url).await?),
+        };
+        check_limits(&probed.info, &self.limits, quality, max_height, clip)?;
+
+        // The download starts from the probed metadata instead of extracting the page a second time
+        let info_json = self.output_dir.join(format!("probe-{}.json", uuid::Uuid::new_v4()));
+        tokio::fs::create_dir_all(&self.output_dir).await?;
+        tokio::fs::write(&info_json, &probed.json).await?;
+        let _info_json_guard = RemoveOnDrop(info_json.clone());
+
+        let output_template = if quality == "audio" || quality == "voice" {
             self.output_dir.join(format!("{}.%(ext)s", filename_stem))
         } else {
             self.output_dir.join(format!("{}.mp4", filename_stem))
@@ -41,34 +85,21 @@ pub async fn download_video_from_url(&self,url: String,filename_stem: &str,quali
            .arg("--no-mtime")
            .arg("--ffmpeg-location")
            .arg(&self.ffmpeg_dir)
-           .arg(&url)
+           .arg("--load-info-json")
+           .arg(&info_json)
            .arg("--progress")
            .arg("--newline")
+           .arg("--progress-template")
+           .arg(PROGRESS_TEMPLATE)
            .stdout(std::process::Stdio::piped())
            .stderr(std::process::Stdio::piped());
 
-        match quality {
-            "h265" => {
-                // Сортировка: предпочитаем высокое разрешение, битрейт и h265 (hevc)
-                cmd.arg("--format-sort").arg("res,br,vcodec:hevc");
-                // Формат: лучшее видео с h265 + лучшее аудио, fallback на лучший mp4
-                // Учитываем также bytevc1, используемый TikTok для H.265
-                cmd.arg("--format").arg("bestvideo[vcodec~='hevc|bytevc1'][ext=mp4]+bestaudio[ext=m4a]/best[ext=mp4]");
-            }
-            "h264" => {
-                // Для h264: использовать только h264 форматы, выбираем лучший доступный
-                cmd.arg("--format").arg("best[ext=mp4][vcodec=h264][height<=1080]/best[ext=mp4][vcodec=h264]/best[ext=mp4]");
-            }
-            "audio" => {
-                // Для аудио: извлекаем аудио в формате mp3
-                cmd.arg("--extract-audio").arg("--audio-format").arg("mp3").arg("--audio-quality").arg("0");
-                // Более широкий формат для аудио, чтобы yt-dlp мог обработать больше типов контента
-                cmd.arg("--format").arg("bestaudio/best[ext=mp4]/best");
-            }
-            _ => {
-                // Fallback для других качеств
-                cmd.arg("--format").arg("best[ext=mp4]");
-            }
+        cmd.args(format_selection_args(quality, max_height, audio_format));
+        let access = self.access_args(&url).await?;
+        cmd.args(&access.args);
+        if let Some(clip) = clip {
+            log::info!("Downloading only {} of {}", clip.label(), url);
+            cmd.args(clip.download_sections_args(precise_clip_cuts()));
         }
 
         let mut child = cmd.spawn()?;
@@ -80,52 +111,27 @@ pub async fn download_video_from_url(&self,url: String,filename_stem: &str,quali
 
         let mut last_percentage = 0.0f64;
         let mut last_update_time = std::time::Instant::now();
-        const MIN_UPDATE_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500); // Minimum 500ms between updates
+        let mut stdout_done = false;
+        let mut stderr_done = false;
 
-        loop {
+        while !stdout_done {
             tokio::select! {
                 line = stdout_reader.next_line() => {
                     match line {
                         Ok(Some(line)) => {
                             log::trace!("yt-dlp stdout: {}", line);
-                            if let Some((percentage, total_size)) = parse_progress_line(&line) {
-                                if percentage > last_percentage {
-                                    let now = std::time::Instant::now();
-                                    if now.duration_since(last_update_time) >= MIN_UPDATE_INTERVAL {
-                                        last_percentage = percentage;
-                                        last_update_time = now;
-                                        // KEY CHANGE: scale 0-100% yt-dlp to 0-80% of overall progress
-                                        let overall_percentage = (percentage * 0.8) as u8; // 0-80%
-                                        let info = format!("⬇️ Downloading: {:.1}% ({:.1} MB)", percentage, total_size as f64 / 1_048_576.0);
-                                        progress_bar.update(overall_percentage, Some(&info)).await?;
-                                    }
-                                }
-                            }
+                            report_output_line(&line, &mut last_percentage, &mut last_update_time, progress_bar).await?;
                         },
-                        Ok(None) => break,
-                        Err(_) => break,
+                        Ok(None) | Err(_) => stdout_done = true,
                     }
                 },
-                line = stderr_reader.next_line() => {
+                line = stderr_reader.next_line(), if !stderr_done => {
                     match line {
                         Ok(Some(line)) => {
                             log::trace!("yt-dlp stderr: {}", line);
-                            if let Some((percentage, total_size)) = parse_progress_line(&line) {
-                                if percentage > last_percentage {
-                                    let now = std::time::Instant::now();
-                                    if now.duration_since(last_update_time) >= MIN_UPDATE_INTERVAL {
-                                        last_percentage = percentage;
-                                        last_update_time = now;
-                                        let current_size = (total_size as f64 * (percentage / 100.0)) as u64;
-                                        let overall_percentage = ((current_size as f64 / total_size as f64 * 80.0).min(80.0).max(0.0)) as u8;
-                                        let info = format!("⬇️ Downloading: {:.1}% ({:.1} MB)", percentage, total_size as f64 / 1_048_576.0);
-                                        progress_bar.update(overall_percentage, Some(&info)).await?;
-                                    }
-                                }
-                            }
+                            report_output_line(&line, &mut last_percentage, &mut last_update_time, progress_bar).await?;
                         },
-                        Ok(None) => {},
-                        Err(_) => {},
+                        Ok(None) | Err(_) => stderr_done = true,
                     }
                 }
             }
@@ -141,54 +147,11 @@ pub async fn download_video_from_url(&self,url: String,filename_stem: &str,quali
             // After download completion, show 80%
             progress_bar.update(80, Some("⬇️ Download completed")).await?;
             
-            let parent = self.output_dir.clone();
-            let stem = PathBuf::from(filename_stem);
-
-            // Log the contents of the output directory to debug what files were actually created
-            log::debug!("Looking for files in: {:?}", parent);
-            if let Ok(entries) = tokio::fs::read_dir(&parent).await {
-                let mut entry = entries;
-                while let Ok(Some(file)) = entry.next_entry().await {
-                    if let Ok(file_type) = file.file_type().await {
-                        if file_type.is_file() {
-                            if let Some(filename) = file.file_name().to_str() {
-                                if filename.starts_with(stem.to_string_lossy().as_ref()) {
-                                    let path = parent.join(filename);
-                                    log::info!("Found unexpected file for download: {:?}", path);
-                                    return Ok(path);
-                                }
-                            }
-                        }
-                    }
-                }
+            if let Some(path) = find_downloaded_file(&self.output_dir, filename_stem).await {
+                log::info!("Download completed successfully in {:.2?} for: {} with file: {:?}", elapsed, url, path);
+                return Ok(DownloadedMedia { path, title: probed.info.title.clone() });
             }
 
-            for ext in [".mp4", ".mov", ".webm", ".mkv", ".flv", ".m4a", ".mp3", ".ogg", ".aac"] {
-                let alt_path = parent.join(format!("{}{}", stem.to_string_lossy(), ext));
-                if alt_path.exists() {
-                    log::info!("Download completed successfully in {:.2?} for: {} with file: {:?}", elapsed, url, alt_path);
-                    return Ok(alt_path);
-                }
-            }
-            
-            // If we can't find with expected extensions, try to find any file that starts with the stem
-            if let Ok(entries) = tokio::fs::read_dir(&parent).await {
-                let mut entry = entries;
-                while let Ok(Some(file)) = entry.next_entry().await {
-                    if let Ok(file_type) = file.file_type().await {
-                        if file_type.is_file() {
-                            if let Some(filename) = file.file_name().to_str() {
-                                if filename.starts_with(stem.to_string_lossy().as_ref()) {
-                                    let path = parent.join(filename);
-                                    log::info!("Found unexpected file for download: {:?}", path);
-                                    return Ok(path);
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-            
             log::error!("Downloaded file not found after successful yt-dlp execution for: {}", url);
             Err(anyhow::anyhow!("Downloaded file not found"))
         } else {
@@ -208,6 +171,162 @@ pub async fn download_video_from_url(&self,url: String,filename_stem: &str,quali
     }
 }
 
+/// A finished download and the title yt-dlp reported for it
+pub struct DownloadedMedia {
+    pub path: PathBuf,
+    pub title: Option<String>,
+}
+
+/// yt-dlp arguments reaching a platform, keep them until the process has exited
+pub(crate) struct AccessArgs {
+    pub args: Vec<std::ffi::OsString>,
+    /// The cookie jar copy `--cookies` points at
+    _jar: Option<JarCopy>,
+}
+
+/// Media extensions yt-dlp can leave behind, looked up before anything else
+const MEDIA_EXTENSIONS: [&str; 11] = [".mp4", ".mov", ".webm", ".mkv", ".flv", ".m4a", ".mp3", ".opus", ".flac", ".ogg", ".aac"];
+
+/// Files written next to the media (audio tags, cover, unfinished fragments), never the download itself
+const SIDECAR_SUFFIXES: [&str; 4] = [".info.json", ".jpg", ".webp", ".part"];
+
+/// The file a download with `filename_stem` produced in `dir`
+async fn find_downloaded_file(dir: &std::path::Path, filename_stem: &str) -> Option<PathBuf> {
+    for ext in MEDIA_EXTENSIONS {
+        let path = dir.join(format!("{}{}", filename_stem, ext));
+        if path.exists() {
+            return Some(path);
+        }
+    }
+
+    // If we can't find with expected extensions, try any other file that starts with the stem
+    log::debug!("Looking for files in: {:?}", dir);
+    let mut entries = tokio::fs::read_dir(dir).await.ok()?;
+    while let Ok(Some(file)) = entries.next_entry().await {
+        if let Ok(file_type) = file.file_type().await
+            && file_type.is_file()
+            && let Some(filename) = file.file_name().to_str()
+            && filename.starts_with(filename_stem)
+            && !SIDECAR_SUFFIXES.iter().any(|suffix| filename.ends_with(suffix))
+        {
+            let path = dir.join(filename);
+            log::info!("Found unexpected file for download: {:?}", path);
+            return Some(path);
+        }
+    }
+    None
+}
+
+/// Deletes a scratch file however the download ends
+struct RemoveOnDrop(PathBuf);
+
+impl Drop for RemoveOnDrop {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Machine readable progress line, see `parse_template_progress`
+const PROGRESS_TEMPLATE: &str = "download:[progress] %(progress.downloaded_bytes)s|%(progress.total_bytes)s|%(progress.total_bytes_estimate)s|%(progress.speed)s|%(progress.eta)s";
+
+/// Minimum time between progress message edits during the download
+const MIN_UPDATE_INTERVAL: std::time::Duration = std::time::Duration::from_millis(500);
+
+/// One line of `PROGRESS_TEMPLATE` output
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct DownloadProgress {
+    downloaded: u64,
+    total: Option<u64>,
+    /// Bytes per second
+    speed: Option<f64>,
+    /// Seconds
+    eta: Option<u64>,
+}
+
+impl DownloadProgress {
+    fn percentage(&self) -> Option<f64> {
+        let total = self.total.filter(|total| *total > 0)?;
+        Some((self.downloaded as f64 / total as f64 * 100.0).min(100.0))
+    }
+}
+
+/// Parses a `[progress] downloaded|total|estimate|speed|eta` line, yt-dlp prints `NA` for unknown values
+fn parse_template_progress(line: &str) -> Option<DownloadProgress> {
+    let fields: Vec<&str> = line.trim().strip_prefix("[progress] ")?.split('|').collect();
+    let [downloaded, total, estimate, speed, eta] = fields[..] else {
+        return None;
+    };
+    let number = |value: &str| value.parse::<f64>().ok().filter(|n| n.is_finite() && *n >= 0.0);
+
+    Some(DownloadProgress {
+        downloaded: number(downloaded)? as u64,
+        total: number(total).or_else(|| number(estimate)).map(|n| n as u64),
+        speed: number(speed),
+        eta: number(eta).map(|n| n as u64),
+    })
+}
+
+/// Post-processing stage announced by a yt-dlp output line
+fn parse_stage(line: &str) -> Option<&'static str> {
+    let line = line.trim_start();
+    if line.starts_with("[Merger]") {
+        Some("🔀 Merging video and audio...")
+    } else if line.starts_with("[VideoRemuxer]") || line.starts_with("[VideoConvertor]") || line.starts_with("[Fixup") {
+        Some("📦 Remuxing...")
+    } else if line.starts_with("[ExtractAudio]") {
+        Some("🎵 Extracting audio...")
+    } else if line.starts_with("[ThumbnailsConvertor]") || line.starts_with("[EmbedThumbnail]") || line.contains("Writing video thumbnail") {
+        Some("🖼️ Processing thumbnail...")
+    } else {
+        None
+    }
+}
+
+/// Updates the progress message for one line of yt-dlp output (download progress or a new stage)
+async fn report_output_line(
+    line: &str,
+    last_percentage: &mut f64,
+    last_update_time: &mut std::time::Instant,
+    progress_bar: &mut dyn ProgressSink,
+) -> Result<()> {
+    if let Some(stage) = parse_stage(line) {
+        *last_update_time = std::time::Instant::now();
+        progress_bar.update(80, Some(stage)).await?;
+        return Ok(());
+    }
+
+    let (percentage, info) = if let Some(progress) = parse_template_progress(line) {
+        let Some(percentage) = progress.percentage() else {
+            return Ok(());
+        };
+        let mut info = format!(
+            "⬇️ Downloading: {:.1}% of {:.1} MB",
+            percentage,
+            progress.total.unwrap_or_default() as f64 / 1_048_576.0
+        );
+        let speed_eta = format_speed_eta(progress.speed, progress.eta);
+        if !speed_eta.is_empty() {
+            info.push_str(&format!("\n{}", speed_eta));
+        }
+        (percentage, info)
+    } else if let Some((percentage, total_size)) = parse_progress_line(line) {
+        // Older yt-dlp builds without --progress-template support
+        (percentage, format!("⬇️ Downloading: {:.1}% ({:.1} MB)", percentage, total_size as f64 / 1_048_576.0))
+    } else {
+        return Ok(());
+    };
+
+    let now = std::time::Instant::now();
+    if percentage > *last_percentage && now.duration_since(*last_update_time) >= MIN_UPDATE_INTERVAL {
+        *last_percentage = percentage;
+        *last_update_time = now;
+        // Scale 0-100% of yt-dlp to 0-80% of the overall progress
+        let overall_percentage = (percentage * 0.8).clamp(0.0, 80.0) as u8;
+        progress_bar.update(overall_percentage, Some(&info)).await?;
+    }
+    Ok(())
+}
+
 fn parse_progress_line(line: &str) -> Option<(f64, u64)> {
     let clean_line = remove_ansi_codes(line);
     let patterns = [
@@ -217,17 +336,16 @@ fn parse_progress_line(line: &str) -> Option<(f64, u64)> {
     ];
 
     for pattern in patterns {
-        if let Ok(re) = Regex::new(pattern) {
-            if let Some(caps) = re.captures(&clean_line) {
-                if let Ok(percentage) = caps[1].parse::<f64>() {
-                    let total_size = if caps.len() > 2 {
-                        parse_size_string(&caps[2])
-                    } else {
-                        10_485_760
-                    };
-                    return Some((percentage, total_size));
-                }
-            }
+        if let Ok(re) = Regex::new(pattern)
+            && let Some(caps) = re.captures(&clean_line)
+            && let Ok(percentage) = caps[1].parse::<f64>()
+        {
+            let total_size = if caps.len() > 2 {
+                parse_size_string(&caps[2])
+            } else {
+                10_485_760
+            };
+            return Some((percentage, total_size));
         }
     }
     None
@@ -254,6 +372,73 @@ fn parse_size_string(s: &str) -> u64 {
     number_str.parse::<f64>().unwrap_or(1.0) as u64 * multiplier
 }
 
+/// Maximum resolution for a stored resolution preference ("480", "720", "1080"), `None` for "best"
+/// Resolution cap of users who haven't picked one, the 1080p the h264 format was always limited to
+pub const DEFAULT_RESOLUTION: &str = "1080";
+
+pub fn max_height_from_preference(resolution: &str) -> Option<u32> {
+    resolution.trim_end_matches('p').parse().ok()
+}
+
+/// yt-dlp `--format`/`--format-sort` (and post-processing) arguments for a quality preference.
+///
+/// The resolution cap goes into `--format-sort res:N`, which yt-dlp measures on the smaller
+/// dimension so portrait TikToks are capped the same way as landscape videos, and which falls
+/// back to the smallest available format when nothing fits under the cap.
+pub fn format_selection_args(quality: &str, max_height: Option<u32>, audio_format: AudioFormat) -> Vec<String> {
+    if let Some(format_id) = quality.strip_prefix("format:") {
+        // Exact variant picked by the user, best mp4 if it is gone by now
+        return vec!["--format".to_string(), format!("{}/best[ext=mp4]", format_id)];
+    }
+
+    let res_sort = match max_height {
+        Some(height) => format!("res:{}", height),
+        None => "res".to_string(),
+    };
+    let args: Vec<&str> = match quality {
+        "h265" => {
+            // Сортировка: предпочитаем высокое разрешение (с учётом лимита), битрейт и h265 (hevc)
+            // Формат: лучшее видео с h265 + лучшее аудио, fallback на лучший mp4
+            // Учитываем также bytevc1, используемый TikTok для H.265
+            return vec![
+                "--format-sort".to_string(),
+                format!("{},br,vcodec:hevc", res_sort),
+                "--format".to_string(),
+                "bestvideo[vcodec~='hevc|bytevc1'][ext=mp4]+bestaudio[ext=m4a]/best[ext=mp4]".to_string(),
+            ];
+        }
+        "h264" | "circle" | "animation" => {
+            // Для h264: использовать только h264 форматы, выбираем лучший доступный
+            // Video notes and animations are converted afterwards, h264 keeps the input cheap to decode
+            vec!["--format", "best[ext=mp4][vcodec=h264]/best[ext=mp4]"]
+        }
+        "audio" => {
+            // Для аудио: извлекаем аудио в выбранном пользователем формате
+            // Sidecars for audio tags and cover: <stem>.info.json and <stem>.jpg
+            let mut args = audio_format.yt_dlp_args();
+            args.extend(
+                ["--write-info-json", "--write-thumbnail", "--convert-thumbnails", "jpg"].iter().map(|arg| arg.to_string()),
+            );
+            return args;
+        }
+        "voice" => {
+            // Voice messages are re-encoded to OGG/Opus afterwards, any audio source is fine
+            return vec!["--format".to_string(), "bestaudio/best".to_string()];
+        }
+        _ => {
+            // Fallback для других качеств
+            vec!["--format", "best[ext=mp4]"]
+        }
+    };
+
+    let mut args: Vec<String> = args.into_iter().map(|arg| arg.to_string()).collect();
+    if max_height.is_some() {
+        args.push("--format-sort".to_string());
+        args.push(res_sort);
+    }
+    args
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -286,4 +471,61 @@ mod tests {
         assert_eq!(percentage, 50.0);
         assert_eq!(total_size, 10_485_760); // 10 MiB
     }
+
+    #[test]
+    fn test_parse_template_progress() {
+        let progress = parse_template_progress("[progress] 1048576|4194304|NA|524288.5|6").unwrap();
+        assert_eq!(progress.percentage(), Some(25.0));
+        assert_eq!(progress.speed, Some(524288.5));
+        assert_eq!(progress.eta, Some(6));
+
+        // Only an estimate, no speed yet
+        let progress = parse_template_progress("[progress] 100|NA|400.0|NA|NA").unwrap();
+        assert_eq!(progress.total, Some(400));
+        assert_eq!(progress.speed, None);
+        assert_eq!(parse_template_progress("[progress] 100|NA|NA|NA|NA").unwrap().percentage(), None);
+
+        assert_eq!(parse_template_progress("[download]  50.0% of 10.00MiB"), None);
+        assert_eq!(parse_stage("[Merger] Merging formats into \"a.mp4\""), Some("🔀 Merging video and audio..."));
+        assert_eq!(parse_stage("[download] Destination: a.mp4"), None);
+    }
+
+    #[tokio::test]
+    async fn test_find_downloaded_file_skips_sidecars() {
+        let dir = tempfile::TempDir::new().unwrap();
+        for name in ["abc.info.json", "abc.jpg", "abc.webm.part"] {
+            std::fs::write(dir.path().join(name), b"sidecar").unwrap();
+        }
+        assert_eq!(find_downloaded_file(dir.path(), "abc").await, None);
+
+        std::fs::write(dir.path().join("abc.m4a"), b"audio").unwrap();
+        assert_eq!(find_downloaded_file(dir.path(), "abc").await, Some(dir.path().join("abc.m4a")));
+
+        std::fs::remove_file(dir.path().join("abc.m4a")).unwrap();
+        std::fs::write(dir.path().join("abc.wav"), b"audio").unwrap();
+        assert_eq!(find_downloaded_file(dir.path(), "abc").await, Some(dir.path().join("abc.wav")));
+    }
+
+    #[test]
+    fn test_format_selection_applies_resolution_cap() {
+        assert_eq!(max_height_from_preference("720"), Some(720));
+        assert_eq!(max_height_from_preference("1080p"), Some(1080));
+        assert_eq!(max_height_from_preference("best"), None);
+
+        let h265 = format_selection_args("h265", Some(720), AudioFormat::default());
+        assert_eq!(h265[0], "--format-sort");
+        assert_eq!(h265[1], "res:720,br,vcodec:hevc");
+        assert_eq!(format_selection_args("h265", None, AudioFormat::default())[1], "res,br,vcodec:hevc");
+
+        let h264 = format_selection_args("h264", Some(480), AudioFormat::default());
+        assert_eq!(h264, vec!["--format", "best[ext=mp4][vcodec=h264]/best[ext=mp4]", "--format-sort", "res:480"]);
+        assert_eq!(format_selection_args("h264", None, AudioFormat::default()).len(), 2);
+        // Users who never set a resolution keep the 1080p h264 downloads they always got
+        let default_h264 = format_selection_args("h264", max_height_from_preference(DEFAULT_RESOLUTION), AudioFormat::default());
+        assert_eq!(default_h264[2..], ["--format-sort", "res:1080"]);
+
+        // Audio modes ignore the cap
+        assert!(!format_selection_args("audio", Some(480), AudioFormat::default()).contains(&"--format-sort".to_string()));
+        assert!(!format_selection_args("voice", Some(480), AudioFormat::default()).contains(&"--format-sort".to_string()));
+    }
 }
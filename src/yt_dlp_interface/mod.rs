@@ -2,7 +2,12 @@ pub mod fetcher;
 pub mod utils;
 pub mod urls;
 pub mod downloader;
+pub mod checksum;
+pub mod probe;
+pub mod clip;
 pub mod ensure;
+pub mod toolchain;
+pub mod cookies;
 
 pub use fetcher::YoutubeFetcher;
 pub use utils::is_executable_present;
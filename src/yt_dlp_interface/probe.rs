@@ -0,0 +1,339 @@
+use serde::Deserialize;
+use tokio::process::Command;
+use tokio::time::{timeout, Duration};
+use anyhow::{anyhow, Result};
+
+use crate::config::DownloadLimits;
+use crate::yt_dlp_interface::YoutubeFetcher;
+use crate::yt_dlp_interface::clip::{format_timestamp, ClipRange};
+
+/// Metadata probes only fetch the page, so they should finish quickly
+const PROBE_TIMEOUT: Duration = Duration::from_secs(60);
+/// Upper bound on the number of video variants offered to the user
+const MAX_VIDEO_VARIANTS: usize = 6;
+
+/// One entry of the `formats` list in yt-dlp's JSON output
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ProbedFormat {
+    #[serde(default)]
+    pub format_id: String,
+    #[serde(default)]
+    pub ext: Option<String>,
+    #[serde(default)]
+    pub vcodec: Option<String>,
+    #[serde(default)]
+    pub acodec: Option<String>,
+    #[serde(default)]
+    pub width: Option<u32>,
+    #[serde(default)]
+    pub height: Option<u32>,
+    #[serde(default)]
+    pub filesize: Option<u64>,
+    #[serde(default)]
+    pub filesize_approx: Option<u64>,
+    /// Total bitrate in KBit/s
+    #[serde(default)]
+    pub tbr: Option<f64>,
+}
+
+impl ProbedFormat {
+    /// Downloads are always written as `<stem>.mp4`
+    fn is_mp4(&self) -> bool {
+        self.ext.as_deref().is_none_or(|ext| ext == "mp4")
+    }
+
+    fn has_video(&self) -> bool {
+        self.vcodec.as_deref().is_some_and(|codec| codec != "none")
+    }
+
+    fn has_audio(&self) -> bool {
+        self.acodec.as_deref().is_some_and(|codec| codec != "none")
+    }
+
+    /// Codec family shown to the user, matching the names used in the format menu
+    fn codec_family(&self) -> String {
+        let codec = self.vcodec.as_deref().unwrap_or("").to_lowercase();
+        if codec.starts_with("avc") || codec.starts_with("h264") {
+            "h264".to_string()
+        } else if codec.starts_with("hevc") || codec.starts_with("hvc") || codec.starts_with("h265") || codec.starts_with("bytevc1") {
+            "h265".to_string()
+        } else {
+            codec.split('.').next().unwrap_or("video").to_string()
+        }
+    }
+
+    /// Resolution on the smaller side, so portrait 1080x1920 is reported as 1080p
+    fn short_side(&self) -> Option<u32> {
+        match (self.width, self.height) {
+            (Some(w), Some(h)) => Some(w.min(h)),
+            (None, Some(h)) => Some(h),
+            (Some(w), None) => Some(w),
+            (None, None) => None,
+        }
+    }
+
+    /// Exact or estimated size in bytes
+    fn approx_size(&self, duration: Option<f64>) -> Option<u64> {
+        self.filesize
+            .or(self.filesize_approx)
+            .or_else(|| Some((self.tbr? * 1000.0 / 8.0 * duration?) as u64))
+    }
+}
+
+/// Result of `yt-dlp --dump-single-json --skip-download`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct VideoInfo {
+    #[serde(default)]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub duration: Option<f64>,
+    #[serde(default)]
+    pub is_live: Option<bool>,
+    /// `is_live`, `is_upcoming`, `was_live`, `not_live` ...
+    #[serde(default)]
+    pub live_status: Option<String>,
+    /// Size of the format yt-dlp picked by default, when the extractor knows it
+    #[serde(default)]
+    pub filesize: Option<u64>,
+    #[serde(default)]
+    pub filesize_approx: Option<u64>,
+    #[serde(default)]
+    pub formats: Vec<ProbedFormat>,
+}
+
+impl VideoInfo {
+    pub fn is_live_stream(&self) -> bool {
+        self.is_live == Some(true) || matches!(self.live_status.as_deref(), Some("is_live" | "is_upcoming"))
+    }
+
+    /// Estimated size of what `quality` and `max_height` download, the largest matching format
+    /// since yt-dlp prefers the best one
+    pub fn estimated_size(&self, quality: &str, max_height: Option<u32>) -> Option<u64> {
+        let size = |format: &ProbedFormat| format.approx_size(self.duration);
+        if let Some(format_id) = quality.strip_prefix("format:") {
+            return self.formats.iter().find(|format| format.format_id == format_id).and_then(size);
+        }
+        let best_audio = self.formats.iter().filter(|format| format.has_audio() && !format.has_video()).filter_map(size).max();
+        if matches!(quality, "audio" | "voice") {
+            return best_audio.or(self.filesize.or(self.filesize_approx));
+        }
+        self.formats
+            .iter()
+            .filter(|format| format.has_video())
+            .filter(|format| max_height.is_none_or(|max| format.short_side().is_none_or(|side| side <= max)))
+            .filter_map(|format| Some(size(format)? + if format.has_audio() { 0 } else { best_audio.unwrap_or(0) }))
+            .max()
+            .or(self.filesize.or(self.filesize_approx))
+    }
+}
+
+/// Why a link is refused before downloading, shown to the user as is
+#[derive(Debug, Clone, PartialEq)]
+pub enum LimitViolation {
+    Live,
+    TooLong { duration: f64, max: f64 },
+    TooLarge { size: u64, max: u64 },
+}
+
+impl std::fmt::Display for LimitViolation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LimitViolation::Live => write!(f, "🔴 Live streams can't be downloaded, try again once the stream has ended"),
+            LimitViolation::TooLong { duration, max } => write!(
+                f,
+                "⏱️ The video is {} long, the limit is {}. Send the link with a time range (e.g. <link> 1:20-2:05) to get a part of it",
+                format_timestamp(*duration), format_timestamp(*max)
+            ),
+            LimitViolation::TooLarge { size, max } => write!(
+                f,
+                "📦 The video would be about {}, the limit is {}. Pick a lower resolution or a time range",
+                format_size_label(*size), format_size_label(*max)
+            ),
+        }
+    }
+}
+
+impl std::error::Error for LimitViolation {}
+
+/// Checks a probed video against `limits` for the given quality; a clip counts with its own length
+pub fn check_limits(info: &VideoInfo, limits: &DownloadLimits, quality: &str, max_height: Option<u32>, clip: Option<ClipRange>) -> Result<(), LimitViolation> {
+    if info.is_live_stream() && !limits.allow_live {
+        return Err(LimitViolation::Live);
+    }
+    let length = match clip {
+        Some(clip) => clip.length(info.duration),
+        None => info.duration,
+    };
+    if let (Some(duration), Some(max)) = (length, limits.max_duration)
+        && duration > max
+    {
+        return Err(LimitViolation::TooLong { duration, max });
+    }
+    // Sizes are for the whole video, a clip gets its share
+    let share = match (length, info.duration) {
+        (Some(length), Some(duration)) if duration > 0.0 => (length / duration).min(1.0),
+        _ => 1.0,
+    };
+    if let (Some(size), Some(max)) = (info.estimated_size(quality, max_height), limits.max_filesize) {
+        let size = (size as f64 * share) as u64;
+        if size > max {
+            return Err(LimitViolation::TooLarge { size, max });
+        }
+    }
+    Ok(())
+}
+
+/// A downloadable variant offered in the format picker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FormatVariant {
+    /// Value passed as `quality` to `download_video_from_url`
+    pub selector: String,
+    pub label: String,
+}
+
+/// Human readable size, e.g. `~12.3 MB`
+pub fn format_size_label(bytes: u64) -> String {
+    format!("~{:.1} MB", bytes as f64 / 1_000_000.0)
+}
+
+/// Picks the best muxed mp4 format per (codec, resolution) and adds an audio-only entry.
+///
+/// Variants are ordered by resolution (highest first) with h265 before h264 at equal size.
+pub fn format_variants(info: &VideoInfo) -> Vec<FormatVariant> {
+    let mut best: Vec<(&ProbedFormat, String, u32)> = Vec::new();
+    for format in info.formats.iter().filter(|f| f.is_mp4() && f.has_video() && f.has_audio() && !f.format_id.is_empty()) {
+        let Some(short_side) = format.short_side() else {
+            continue;
+        };
+        let family = format.codec_family();
+        match best.iter_mut().find(|(_, f, s)| *f == family && *s == short_side) {
+            Some(entry) => {
+                if format.tbr.unwrap_or(0.0) > entry.0.tbr.unwrap_or(0.0) {
+                    entry.0 = format;
+                }
+            }
+            None => best.push((format, family, short_side)),
+        }
+    }
+
+    best.sort_by(|a, b| b.2.cmp(&a.2).then_with(|| (a.1 != "h265").cmp(&(b.1 != "h265"))));
+
+    let mut variants: Vec<FormatVariant> = best
+        .into_iter()
+        .take(MAX_VIDEO_VARIANTS)
+        .map(|(format, family, short_side)| {
+            let mut label = format!("{} {}p", family, short_side);
+            if let Some(size) = format.approx_size(info.duration) {
+                label.push_str(&format!(" {}", format_size_label(size)));
+            }
+            FormatVariant {
+                selector: format!("format:{}", format.format_id),
+                label,
+            }
+        })
+        .collect();
+
+    variants.push(FormatVariant {
+        selector: "audio".to_string(),
+        label: "🎵 audio only".to_string(),
+    });
+    variants
+}
+
+/// Metadata of one link together with yt-dlp's JSON, which the download reuses via `--load-info-json`
+#[derive(Debug)]
+pub struct ProbedVideo {
+    pub info: VideoInfo,
+    pub json: Vec<u8>,
+}
+
+impl YoutubeFetcher {
+    /// Runs `--dump-single-json --skip-download` and keeps the raw JSON
+    pub async fn probe_video(&self, url: &str) -> Result<ProbedVideo> {
+        let access = self.access_args(url).await?;
+        let mut cmd = Command::new(&self.yt_dlp_path);
+        cmd.arg("--extractor-args")
+            .arg("tiktok:skip=feed")
+            .arg("--dump-single-json")
+            .arg("--skip-download")
+            .arg("--no-warnings")
+            .arg(url)
+            .args(&access.args)
+            .kill_on_drop(true);
+        let output = cmd.output();
+
+        let output = timeout(PROBE_TIMEOUT, output)
+            .await
+            .map_err(|_| anyhow!("yt-dlp metadata probe timeout"))??;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            log::error!("yt-dlp metadata probe failed: {}", stderr);
+            return Err(anyhow!("yt-dlp metadata probe failed: {}", stderr));
+        }
+
+        let info = serde_json::from_slice(&output.stdout)?;
+        Ok(ProbedVideo { info, json: output.stdout })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_variants_groups_by_codec_and_resolution() {
+        let info: VideoInfo = serde_json::from_str(r#"{
+            "title": "clip",
+            "duration": 10,
+            "formats": [
+                {"format_id": "h264_540", "vcodec": "h264", "acodec": "aac", "width": 576, "height": 1024, "tbr": 800, "filesize": 1000000},
+                {"format_id": "h264_720_low", "vcodec": "h264", "acodec": "aac", "width": 720, "height": 1280, "tbr": 1200},
+                {"format_id": "h264_720_high", "vcodec": "h264", "acodec": "aac", "width": 720, "height": 1280, "tbr": 1600},
+                {"format_id": "hevc_720", "vcodec": "bytevc1", "acodec": "aac", "width": 720, "height": 1280, "tbr": 900, "filesize_approx": 1125000},
+                {"format_id": "audio_only", "vcodec": "none", "acodec": "aac", "tbr": 128},
+                {"format_id": "video_only", "vcodec": "h264", "acodec": "none", "width": 1080, "height": 1920},
+                {"format_id": "webm_1080", "ext": "webm", "vcodec": "vp9", "acodec": "opus", "width": 1080, "height": 1920}
+            ]
+        }"#).unwrap();
+
+        let variants = format_variants(&info);
+        assert_eq!(variants, vec![
+            FormatVariant { selector: "format:hevc_720".to_string(), label: "h265 720p ~1.1 MB".to_string() },
+            FormatVariant { selector: "format:h264_720_high".to_string(), label: "h264 720p ~2.0 MB".to_string() },
+            FormatVariant { selector: "format:h264_540".to_string(), label: "h264 576p ~1.0 MB".to_string() },
+            FormatVariant { selector: "audio".to_string(), label: "🎵 audio only".to_string() },
+        ]);
+    }
+
+    #[test]
+    fn test_check_limits() {
+        let limits = DownloadLimits { max_duration: Some(3600.0), max_filesize: Some(500_000_000), allow_live: false };
+        let info: VideoInfo = serde_json::from_str(r#"{
+            "duration": 10800,
+            "formats": [
+                {"format_id": "audio", "vcodec": "none", "acodec": "opus", "filesize": 100000000},
+                {"format_id": "360", "vcodec": "avc1", "acodec": "none", "width": 640, "height": 360, "filesize": 300000000},
+                {"format_id": "1080", "vcodec": "avc1", "acodec": "none", "width": 1920, "height": 1080, "filesize": 3000000000}
+            ]
+        }"#).unwrap();
+
+        assert_eq!(check_limits(&info, &limits, "best", None, None), Err(LimitViolation::TooLong { duration: 10800.0, max: 3600.0 }));
+        // A 30 minute clip is short enough, but at 1080p its share is still too big
+        let clip = Some(ClipRange { start: 600.0, end: Some(2400.0) });
+        assert_eq!(check_limits(&info, &limits, "best", None, clip), Err(LimitViolation::TooLarge { size: 516_666_666, max: 500_000_000 }));
+        assert_eq!(check_limits(&info, &limits, "best", Some(360), clip), Ok(()));
+        assert_eq!(check_limits(&info, &limits, "audio", None, clip), Ok(()));
+
+        let live = VideoInfo { live_status: Some("is_live".to_string()), ..VideoInfo::default() };
+        assert_eq!(check_limits(&live, &limits, "best", None, None), Err(LimitViolation::Live));
+        assert_eq!(check_limits(&live, &DownloadLimits { allow_live: true, ..limits }, "best", None, None), Ok(()));
+    }
+
+    #[test]
+    fn test_format_variants_without_formats_offers_audio() {
+        let variants = format_variants(&VideoInfo::default());
+        assert_eq!(variants.len(), 1);
+        assert_eq!(variants[0].selector, "audio");
+    }
+}
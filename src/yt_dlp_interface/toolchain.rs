@@ -0,0 +1,209 @@
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+use anyhow::{anyhow, Result};
+use tokio::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::auto_update::policy::{matches_pin, UpdatePolicy};
+use crate::auto_update::version_manager::detect_version;
+use crate::config::{tool_source, update_policy, ToolSource};
+
+fn executable_name(name: &str) -> String {
+    if cfg!(target_os = "windows") { format!("{}.exe", name) } else { name.to_string() }
+}
+
+/// Looks `name` up in a `PATH`-style list of directories
+pub fn find_in(search_path: &OsStr, name: &str) -> Option<PathBuf> {
+    let file_name = executable_name(name);
+    std::env::split_paths(search_path)
+        .map(|dir| dir.join(&file_name))
+        .find(|candidate| candidate.is_file())
+}
+
+pub fn find_in_path(name: &str) -> Option<PathBuf> {
+    find_in(&std::env::var_os("PATH")?, name)
+}
+
+/// Paths of the external tools the bot runs, and which of them it downloads and updates itself
+#[derive(Debug, Clone)]
+pub struct Toolchain {
+    pub yt_dlp: PathBuf,
+    pub ffmpeg: PathBuf,
+    pub ffprobe: PathBuf,
+    /// Downloaded into the libraries directory and kept up to date by `AutoUpdater`
+    pub yt_dlp_managed: bool,
+    pub ffmpeg_managed: bool,
+}
+
+impl Toolchain {
+    /// Bundled copies in `libraries_dir`, the layout `ensure_binaries` downloads into
+    pub fn managed(libraries_dir: &Path) -> Self {
+        let ffmpeg_dir = libraries_dir.join("ffmpeg");
+        Self {
+            yt_dlp: libraries_dir.join(executable_name("yt-dlp")),
+            ffmpeg: ffmpeg_dir.join(executable_name("ffmpeg")),
+            ffprobe: ffmpeg_dir.join(executable_name("ffprobe")),
+            yt_dlp_managed: true,
+            ffmpeg_managed: true,
+        }
+    }
+
+    /// Applies `YT_DLP_PATH`, `FFMPEG_PATH` and `FFPROBE_PATH` on top of the bundled layout
+    pub fn resolve(libraries_dir: &Path) -> Result<Self> {
+        let mut toolchain = Self::managed(libraries_dir);
+
+        match tool_source("YT_DLP_PATH") {
+            ToolSource::Managed => {}
+            source => {
+                toolchain.yt_dlp = resolve_external(&source, "yt-dlp")?;
+                toolchain.yt_dlp_managed = false;
+            }
+        }
+
+        let ffmpeg_source = tool_source("FFMPEG_PATH");
+        if ffmpeg_source != ToolSource::Managed {
+            toolchain.ffmpeg = resolve_external(&ffmpeg_source, "ffmpeg")?;
+            toolchain.ffmpeg_managed = false;
+        }
+        // ffprobe comes from wherever ffmpeg does unless it is configured separately
+        toolchain.ffprobe = match (tool_source("FFPROBE_PATH"), &ffmpeg_source) {
+            (ToolSource::Managed, ToolSource::Managed) => toolchain.ffprobe,
+            (ToolSource::Managed, ToolSource::Path(_)) => toolchain.ffmpeg.with_file_name(executable_name("ffprobe")),
+            (ToolSource::Managed, source) => resolve_external(source, "ffprobe")?,
+            (source, _) => resolve_external(&source, "ffprobe")?,
+        };
+
+        Ok(toolchain)
+    }
+
+    /// Directory passed to yt-dlp as `--ffmpeg-location`
+    pub fn ffmpeg_dir(&self) -> PathBuf {
+        self.ffmpeg.parent().map(Path::to_path_buf).unwrap_or_default()
+    }
+
+    pub fn is_managed(&self, binary_name: &str) -> bool {
+        match binary_name {
+            "yt-dlp" => self.yt_dlp_managed,
+            _ => self.ffmpeg_managed,
+        }
+    }
+
+    pub fn path_of(&self, binary_name: &str) -> &Path {
+        match binary_name {
+            "yt-dlp" => &self.yt_dlp,
+            "ffprobe" => &self.ffprobe,
+            _ => &self.ffmpeg,
+        }
+    }
+}
+
+fn resolve_external(source: &ToolSource, name: &str) -> Result<PathBuf> {
+    match source {
+        ToolSource::Path(path) if path.is_file() => Ok(path.clone()),
+        ToolSource::Path(path) => Err(anyhow!("{} not found at the configured path {:?}", name, path)),
+        _ => find_in_path(name).ok_or_else(|| anyhow!("{} is configured to come from the system but isn't on PATH", name)),
+    }
+}
+
+/// Checks that externally provided tools run, since nothing will download or update them
+pub async fn validate_external_tools(toolchain: &Toolchain) -> Result<()> {
+    for binary_name in ["yt-dlp", "ffmpeg"] {
+        if toolchain.is_managed(binary_name) {
+            continue;
+        }
+        let path = toolchain.path_of(binary_name);
+        let version = detect_version(binary_name, path).await
+            .map_err(|e| e.context(format!("External {} at {:?} is not usable", binary_name, path)))?;
+        log::info!("Using external {} {} at {:?}", binary_name, version, path);
+
+        if let UpdatePolicy::Pinned(pinned) = update_policy(binary_name)
+            && !matches_pin(&version, &pinned)
+        {
+            log::warn!("External {} is {}, not the pinned {}; it is not managed by the bot", binary_name, version, pinned);
+        }
+    }
+    detect_version("ffprobe", &toolchain.ffprobe).await
+        .map_err(|e| e.context(format!("ffprobe at {:?} is not usable", toolchain.ffprobe)))?;
+    Ok(())
+}
+
+static TOOLCHAIN: OnceLock<Toolchain> = OnceLock::new();
+
+/// Makes the resolved toolchain available to code that isn't handed the paths, set once at startup
+pub fn set_toolchain(toolchain: Toolchain) {
+    if TOOLCHAIN.set(toolchain).is_err() {
+        log::warn!("Toolchain was already set, keeping the first one");
+    }
+}
+
+/// The toolchain set at startup, or the bundled layout next to the executable
+pub fn toolchain() -> &'static Toolchain {
+    TOOLCHAIN.get_or_init(|| {
+        let exe_dir = std::env::current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(Path::to_path_buf))
+            .unwrap_or_default();
+        Toolchain::managed(&exe_dir.join("lib"))
+    })
+}
+
+/// Read-held by jobs running yt-dlp/ffmpeg, write-held while the binaries are replaced.
+///
+/// tokio's lock is fair: once an update waits, new jobs queue behind it instead of starving it.
+static TOOLCHAIN_LOCK: OnceLock<RwLock<()>> = OnceLock::new();
+
+fn toolchain_lock() -> &'static RwLock<()> {
+    TOOLCHAIN_LOCK.get_or_init(|| RwLock::new(()))
+}
+
+/// Keeps the binaries from being swapped until the returned guard is dropped
+pub async fn use_toolchain() -> RwLockReadGuard<'static, ()> {
+    toolchain_lock().read().await
+}
+
+/// Waits for running jobs to finish, `None` if they don't within `max_wait`
+pub async fn lock_toolchain_for_update(max_wait: Duration) -> Option<RwLockWriteGuard<'static, ()>> {
+    tokio::time::timeout(max_wait, toolchain_lock().write()).await.ok()
+}
+
+/// Waits as long as it takes, for rollbacks that can't be postponed
+pub async fn lock_toolchain() -> RwLockWriteGuard<'static, ()> {
+    toolchain_lock().write().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_in_search_path() {
+        let first = tempfile::TempDir::new().unwrap();
+        let second = tempfile::TempDir::new().unwrap();
+        let ffmpeg = second.path().join(executable_name("ffmpeg"));
+        std::fs::write(&ffmpeg, "").unwrap();
+
+        let search_path = std::env::join_paths([first.path(), second.path()]).unwrap();
+        assert_eq!(find_in(&search_path, "ffmpeg"), Some(ffmpeg));
+        assert_eq!(find_in(&search_path, "yt-dlp"), None);
+
+        let toolchain = Toolchain::managed(Path::new("lib"));
+        assert!(toolchain.is_managed("ffmpeg"));
+        assert_eq!(toolchain.ffmpeg_dir(), Path::new("lib").join("ffmpeg"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_update_waits_for_jobs_and_holds_off_new_ones() {
+        let job = use_toolchain().await;
+        // A running job outlasts the wait: the update is postponed
+        assert!(lock_toolchain_for_update(Duration::from_secs(5)).await.is_none());
+
+        let update = tokio::spawn(async { lock_toolchain_for_update(Duration::from_secs(60)).await.is_some() });
+        tokio::time::sleep(Duration::from_secs(1)).await;
+        // A new job queues behind the waiting update
+        assert!(tokio::time::timeout(Duration::from_secs(1), use_toolchain()).await.is_err());
+        drop(job);
+        assert!(update.await.unwrap());
+        assert!(tokio::time::timeout(Duration::from_secs(1), use_toolchain()).await.is_ok());
+    }
+}
@@ -1,25 +1,269 @@
-pub fn get_latest_yt_dlp_url() -> String {
-    let os = if cfg!(target_os = "windows") {
-        "yt-dlp.exe"
-    } else if cfg!(target_os = "linux") {
-        "yt-dlp_linux"
-    } else if cfg!(target_os = "macos") {
-        "yt-dlp_macos"
-    } else {
-        "yt-dlp"  // fallback
-    };
-    
-    // This downloads the latest release from GitHub
-    format!("https://github.com/yt-dlp/yt-dlp/releases/latest/download/{}", os)
-}
-
-pub fn get_latest_ffmpeg_url() -> String {
-    if cfg!(target_os = "windows") {
-        "https://github.com/BtbN/FFmpeg-Builds/releases/latest/download/ffmpeg-master-latest-win64-gpl.zip".to_string()
-    } else if cfg!(target_os = "linux") {
-        // For Linux, use the correct path for git builds from johnvansickle.com
-        "https://johnvansickle.com/ffmpeg/builds/ffmpeg-git-amd64-static.tar.xz".to_string()
-    } else {
-        "https://evermeet.cx/ffmpeg/get/ffmpeg/7z".to_string() // For macOS as fallback
-    }
-}
\ No newline at end of file
+use anyhow::{anyhow, Result};
+
+use crate::auto_update::policy::Channel;
+use crate::utils::proxy::http_client_builder;
+use crate::yt_dlp_interface::checksum::parse_checksum_names;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Os {
+    Windows,
+    Linux,
+    Macos,
+    Other,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arch {
+    X86_64,
+    Aarch64,
+    Armv7,
+    X86,
+    Other,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Libc {
+    Glibc,
+    Musl,
+}
+
+/// What the downloaded yt-dlp and ffmpeg builds have to run on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Platform {
+    pub os: Os,
+    pub arch: Arch,
+    /// Only meaningful on Linux
+    pub libc: Libc,
+}
+
+impl Platform {
+    /// The machine the bot runs on. The C library is detected at runtime, since a static
+    /// musl build of the bot also runs on glibc systems and the other way round in containers
+    pub fn current() -> Self {
+        let os = if cfg!(target_os = "windows") {
+            Os::Windows
+        } else if cfg!(target_os = "linux") {
+            Os::Linux
+        } else if cfg!(target_os = "macos") {
+            Os::Macos
+        } else {
+            Os::Other
+        };
+        let arch = match std::env::consts::ARCH {
+            "x86_64" => Arch::X86_64,
+            "aarch64" => Arch::Aarch64,
+            "arm" => Arch::Armv7,
+            "x86" => Arch::X86,
+            _ => Arch::Other,
+        };
+        let libc = if os == Os::Linux && is_musl_system() { Libc::Musl } else { Libc::Glibc };
+        Self { os, arch, libc }
+    }
+}
+
+impl std::fmt::Display for Platform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.os {
+            Os::Linux => write!(f, "{:?} {:?} ({:?})", self.os, self.arch, self.libc),
+            _ => write!(f, "{:?} {:?}", self.os, self.arch),
+        }
+    }
+}
+
+/// musl systems (Alpine and friends) ship the dynamic loader as `/lib/ld-musl-<arch>.so.1`;
+/// glibc systems with the musl package installed have both loaders and count as glibc
+fn is_musl_system() -> bool {
+    let loaders: Vec<String> = ["/lib", "/lib64"]
+        .iter()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flat_map(|entries| entries.flatten().map(|entry| entry.file_name().to_string_lossy().into_owned()))
+        .filter(|name| name.starts_with("ld-"))
+        .collect();
+    loaders.iter().any(|name| name.starts_with("ld-musl-")) && !loaders.iter().any(|name| name.starts_with("ld-linux"))
+}
+
+fn yt_dlp_asset_name(platform: &Platform) -> Result<&'static str> {
+    match (platform.os, platform.arch, platform.libc) {
+        (Os::Windows, Arch::X86_64, _) => Ok("yt-dlp.exe"),
+        (Os::Windows, Arch::Aarch64, _) => Ok("yt-dlp_arm64.exe"),
+        (Os::Windows, Arch::X86, _) => Ok("yt-dlp_x86.exe"),
+        (Os::Linux, Arch::X86_64, Libc::Glibc) => Ok("yt-dlp_linux"),
+        (Os::Linux, Arch::Aarch64, Libc::Glibc) => Ok("yt-dlp_linux_aarch64"),
+        (Os::Linux, Arch::Armv7, Libc::Glibc) => Ok("yt-dlp_linux_armv7l"),
+        (Os::Linux, Arch::X86_64, Libc::Musl) => Ok("yt-dlp_musllinux"),
+        (Os::Linux, Arch::Aarch64, Libc::Musl) => Ok("yt-dlp_musllinux_aarch64"),
+        // Universal binary
+        (Os::Macos, _, _) => Ok("yt-dlp_macos"),
+        _ => Err(anyhow!("yt-dlp publishes no standalone build for {}; set YT_DLP_PATH to a system install", platform)),
+    }
+}
+
+fn yt_dlp_repository(channel: Channel) -> &'static str {
+    match channel {
+        Channel::Stable => "yt-dlp/yt-dlp",
+        Channel::Nightly => "yt-dlp/yt-dlp-nightly-builds",
+    }
+}
+
+/// Atom feed of the channel's releases, the newest entry's tag is the latest version
+pub fn yt_dlp_feed_url(channel: Channel) -> String {
+    format!("https://github.com/{}/releases.atom", yt_dlp_repository(channel))
+}
+
+/// yt-dlp of release `tag`, or the latest release of the channel
+pub fn yt_dlp_url(platform: &Platform, channel: Channel, tag: Option<&str>) -> Result<String> {
+    let asset = yt_dlp_asset_name(platform)?;
+    Ok(match tag {
+        Some(tag) => format!("https://github.com/{}/releases/download/{}/{}", yt_dlp_repository(channel), tag, asset),
+        None => format!("https://github.com/{}/releases/latest/download/{}", yt_dlp_repository(channel), asset),
+    })
+}
+
+/// Where the publisher of the platform's ffmpeg builds announces a new one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FfmpegVersionSource {
+    /// BtbN's atom feed, changes with every daily build
+    Feed(&'static str),
+    /// johnvansickle's readme next to the builds, its `version:` line names the build
+    Readme(&'static str),
+    /// evermeet's JSON description of the build, with a `version` field
+    Info(&'static str),
+}
+
+/// Each publisher has its own signal, so a rebuild at BtbN doesn't reinstall the same Linux or macOS build
+pub fn ffmpeg_version_source(platform: &Platform, channel: Channel) -> Result<FfmpegVersionSource> {
+    match (platform.os, channel) {
+        (Os::Windows, _) => Ok(FfmpegVersionSource::Feed("https://github.com/BtbN/FFmpeg-Builds/releases.atom")),
+        (Os::Linux, Channel::Stable) => Ok(FfmpegVersionSource::Readme("https://johnvansickle.com/ffmpeg/release-readme.txt")),
+        (Os::Linux, Channel::Nightly) => Ok(FfmpegVersionSource::Readme("https://johnvansickle.com/ffmpeg/git-readme.txt")),
+        (Os::Macos, Channel::Stable) => Ok(FfmpegVersionSource::Info("https://evermeet.cx/ffmpeg/info/ffmpeg/release")),
+        (Os::Macos, Channel::Nightly) => Ok(FfmpegVersionSource::Info("https://evermeet.cx/ffmpeg/info/ffmpeg/snapshot")),
+        (Os::Other, _) => Err(anyhow!("No ffmpeg builds for {}; set FFMPEG_PATH to a system install", platform)),
+    }
+}
+
+/// The build named by the `version: 7.0.2` line of a johnvansickle readme
+pub fn parse_readme_version(readme: &str) -> Option<String> {
+    readme
+        .lines()
+        .find_map(|line| line.trim().strip_prefix("version:"))
+        .map(|version| version.trim().to_string())
+        .filter(|version| !version.is_empty())
+}
+
+/// johnvansickle's static builds run on glibc and musl alike
+fn johnvansickle_arch(platform: &Platform) -> Result<&'static str> {
+    match platform.arch {
+        Arch::X86_64 => Ok("amd64"),
+        Arch::Aarch64 => Ok("arm64"),
+        Arch::Armv7 => Ok("armhf"),
+        Arch::X86 => Ok("i686"),
+        Arch::Other => Err(anyhow!("No static ffmpeg build for {}; set FFMPEG_PATH to a system install", platform)),
+    }
+}
+
+fn btbn_target(platform: &Platform) -> Result<&'static str> {
+    match platform.arch {
+        Arch::X86_64 => Ok("win64"),
+        Arch::Aarch64 => Ok("winarm64"),
+        _ => Err(anyhow!("No ffmpeg build for {}; set FFMPEG_PATH to a system install", platform)),
+    }
+}
+
+/// Newest release branch build among the assets of a BtbN release, e.g. `ffmpeg-n7.1-latest-win64-gpl-7.1.zip`
+pub fn btbn_stable_asset<'a>(platform: &Platform, asset_names: impl IntoIterator<Item = &'a str>) -> Result<String> {
+    let target = btbn_target(platform)?;
+    let marker = format!("-latest-{}-gpl-", target);
+    asset_names
+        .into_iter()
+        .filter(|name| name.starts_with("ffmpeg-n"))
+        .filter_map(|name| {
+            // The shared builds are `-gpl-shared-7.1.zip` and don't parse as a version
+            let (_, version) = name.strip_suffix(".zip")?.split_once(&marker)?;
+            let version: Vec<u32> = version.split('.').map(|part| part.parse().ok()).collect::<Option<_>>()?;
+            Some((version, name))
+        })
+        .max()
+        .map(|(_, name)| name.to_string())
+        .ok_or_else(|| anyhow!("BtbN's latest release has no release branch build for {}", target))
+}
+
+/// Latest ffmpeg build of the channel: release branch builds for stable, master / git snapshots for nightly.
+///
+/// BtbN names its release builds after the branch, so on Windows the current one is looked up in the release.
+pub async fn ffmpeg_url(platform: &Platform, channel: Channel) -> Result<String> {
+    match (platform.os, channel) {
+        (Os::Windows, Channel::Stable) => {
+            // The checksum file lists every asset of the release
+            let listing = http_client_builder()?.build()?
+                .get("https://github.com/BtbN/FFmpeg-Builds/releases/latest/download/checksums.sha256")
+                .send().await?
+                .error_for_status()?
+                .text().await?;
+            let asset = btbn_stable_asset(platform, parse_checksum_names(&listing))?;
+            Ok(format!("https://github.com/BtbN/FFmpeg-Builds/releases/latest/download/{}", asset))
+        }
+        (Os::Windows, Channel::Nightly) => Ok(format!("https://github.com/BtbN/FFmpeg-Builds/releases/latest/download/ffmpeg-master-latest-{}-gpl.zip", btbn_target(platform)?)),
+        (Os::Linux, Channel::Stable) => Ok(format!("https://johnvansickle.com/ffmpeg/releases/ffmpeg-release-{}-static.tar.xz", johnvansickle_arch(platform)?)),
+        (Os::Linux, Channel::Nightly) => Ok(format!("https://johnvansickle.com/ffmpeg/builds/ffmpeg-git-{}-static.tar.xz", johnvansickle_arch(platform)?)),
+        // Intel builds, Apple Silicon runs them through Rosetta
+        (Os::Macos, Channel::Stable) => Ok("https://evermeet.cx/ffmpeg/getrelease/ffmpeg/7z".to_string()),
+        (Os::Macos, Channel::Nightly) => Ok("https://evermeet.cx/ffmpeg/get/ffmpeg/7z".to_string()),
+        (Os::Other, _) => Err(anyhow!("No ffmpeg builds for {}; set FFMPEG_PATH to a system install", platform)),
+    }
+}
+
+/// ffmpeg release `version`, from the publishers that keep old releases around
+pub fn ffmpeg_pinned_url(platform: &Platform, version: &str) -> Result<String> {
+    match (platform.os, platform.arch) {
+        (Os::Windows, Arch::X86_64) => Ok(format!("https://github.com/GyanD/codexffmpeg/releases/download/{0}/ffmpeg-{0}-essentials_build.zip", version)),
+        (Os::Linux, _) => Ok(format!("https://johnvansickle.com/ffmpeg/old-releases/ffmpeg-{}-{}-static.tar.xz", version, johnvansickle_arch(platform)?)),
+        (Os::Macos, _) => Ok(format!("https://evermeet.cx/ffmpeg/ffmpeg-{}.7z", version)),
+        _ => Err(anyhow!("No source of pinned ffmpeg builds for {}", platform)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_assets_follow_architecture_and_libc() {
+        let linux = |arch, libc| Platform { os: Os::Linux, arch, libc };
+
+        let url = yt_dlp_url(&linux(Arch::Aarch64, Libc::Glibc), Channel::Stable, None).unwrap();
+        assert_eq!(url, "https://github.com/yt-dlp/yt-dlp/releases/latest/download/yt-dlp_linux_aarch64");
+        let url = yt_dlp_url(&linux(Arch::X86_64, Libc::Musl), Channel::Nightly, Some("2024.12.23.232653")).unwrap();
+        assert_eq!(url, "https://github.com/yt-dlp/yt-dlp-nightly-builds/releases/download/2024.12.23.232653/yt-dlp_musllinux");
+        assert!(yt_dlp_url(&linux(Arch::Armv7, Libc::Musl), Channel::Stable, None).is_err());
+
+        // The static ffmpeg builds don't depend on the C library
+        let url = ffmpeg_url(&linux(Arch::Armv7, Libc::Musl), Channel::Stable).await.unwrap();
+        assert_eq!(url, "https://johnvansickle.com/ffmpeg/releases/ffmpeg-release-armhf-static.tar.xz");
+        let url = ffmpeg_pinned_url(&linux(Arch::Aarch64, Libc::Glibc), "7.0.2").unwrap();
+        assert_eq!(url, "https://johnvansickle.com/ffmpeg/old-releases/ffmpeg-7.0.2-arm64-static.tar.xz");
+
+        let windows_arm = Platform { os: Os::Windows, arch: Arch::Aarch64, libc: Libc::Glibc };
+        assert!(ffmpeg_url(&windows_arm, Channel::Nightly).await.unwrap().ends_with("ffmpeg-master-latest-winarm64-gpl.zip"));
+        assert!(ffmpeg_pinned_url(&windows_arm, "7.1").is_err());
+
+        // Stable follows the newest release branch BtbN builds, whatever its number
+        let assets = [
+            "ffmpeg-master-latest-winarm64-gpl.zip",
+            "ffmpeg-n7.1-latest-winarm64-gpl-7.1.zip",
+            "ffmpeg-n7.1-latest-winarm64-gpl-shared-7.1.zip",
+            "ffmpeg-n8.0-latest-winarm64-lgpl-8.0.zip",
+            "ffmpeg-n8.0-latest-winarm64-gpl-8.0.zip",
+            "ffmpeg-n8.0-latest-win64-gpl-8.0.zip",
+        ];
+        assert_eq!(btbn_stable_asset(&windows_arm, assets).unwrap(), "ffmpeg-n8.0-latest-winarm64-gpl-8.0.zip");
+
+        // Linux builds are announced by johnvansickle, not by BtbN's feed
+        assert_eq!(
+            ffmpeg_version_source(&linux(Arch::X86_64, Libc::Glibc), Channel::Stable).unwrap(),
+            FfmpegVersionSource::Readme("https://johnvansickle.com/ffmpeg/release-readme.txt")
+        );
+        let readme = "              ffmpeg static build\n      version: 7.0.2\n      build: ffmpeg-7.0.2-amd64-static.tar.xz\n";
+        assert_eq!(parse_readme_version(readme).as_deref(), Some("7.0.2"));
+    }
+}
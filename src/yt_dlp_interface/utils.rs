@@ -12,7 +12,7 @@ pub fn is_executable(path: &PathBuf) -> bool {
     #[cfg(not(windows))]
     {
         use std::os::unix::fs::PermissionsExt;
-        std::fs::metadata(path).map_or(false, |metadata| {
+        std::fs::metadata(path).is_ok_and(|metadata| {
             let permissions = metadata.permissions();
             permissions.mode() & 0o111 != 0
         })